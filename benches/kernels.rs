@@ -0,0 +1,93 @@
+//! Compares the SIMD levels of the conversion kernels on a 1080p frame.
+//!
+//! Run with `cargo bench --bench kernels`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use wgc::kernels::{RowKernel, SimdLevel, TensorKernel, YuvKernel};
+use wgc::*;
+
+const PIXELS: usize = 1920 * 1080;
+
+fn source() -> Vec<u8> {
+    (0..PIXELS * 4).map(|i| (i * 7 + i / 4) as u8).collect()
+}
+
+fn row_kernels(c: &mut Criterion) {
+    let source = source();
+    let targets = [
+        ("bgra8", PixelFormat::BGRA8),
+        ("rgb24", PixelFormat::RGB24),
+        ("gray8", PixelFormat::GRAY8),
+        ("gray16", PixelFormat::GRAY16),
+    ];
+    for (name, target_format) in targets {
+        let mut group = c.benchmark_group(format!("rgba8_to_{name}"));
+        group.throughput(Throughput::Elements(PIXELS as u64));
+        let mut target = vec![0; PIXELS * target_format.bytes_per_pixel() as usize];
+        for level in SimdLevel::supported() {
+            let kernel = RowKernel::new(PixelFormat::RGBA8, target_format)
+                .unwrap()
+                .with_level(level)
+                .unwrap();
+            group.bench_function(format!("{level:?}"), |b| {
+                b.iter(|| kernel.run(black_box(&source), &mut target))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn tensor_kernels(c: &mut Criterion) {
+    let source = source();
+    let mut group = c.benchmark_group("rgba8_to_imagenet_f32");
+    group.throughput(Throughput::Elements(PIXELS as u64));
+    let mut target = vec![0.0; PIXELS * 3];
+    for level in SimdLevel::supported() {
+        let kernel = TensorKernel::new(PixelFormat::RGBA8, &TensorOptions::imagenet())
+            .unwrap()
+            .with_level(level)
+            .unwrap();
+        group.bench_function(format!("{level:?}"), |b| {
+            b.iter(|| kernel.run(black_box(&source), &mut target))
+        });
+    }
+    group.finish();
+}
+
+fn yuv_kernels(c: &mut Criterion) {
+    let source = source();
+    let yuv = PixelFormat::yuv(YuvLayout::Nv12, ColorMatrix::Bt709, ColorRange::Limited);
+    let (mut luma, mut u, mut v) = (vec![0; PIXELS], vec![0.0; PIXELS], vec![0.0; PIXELS]);
+    let mut group = c.benchmark_group("rgba8_to_yuv");
+    group.throughput(Throughput::Elements(PIXELS as u64));
+    for level in SimdLevel::supported() {
+        let kernel = YuvKernel::new(PixelFormat::RGBA8, yuv)
+            .unwrap()
+            .with_level(level)
+            .unwrap();
+        group.bench_function(format!("{level:?}"), |b| {
+            b.iter(|| kernel.to_yuv(black_box(&source), &mut luma, &mut u, &mut v))
+        });
+    }
+    group.finish();
+
+    let chroma = &source[..PIXELS / 2];
+    let mut target = vec![0; PIXELS * 4];
+    let mut group = c.benchmark_group("yuv_to_rgba8");
+    group.throughput(Throughput::Elements(PIXELS as u64));
+    for level in SimdLevel::supported() {
+        let kernel = YuvKernel::new(PixelFormat::RGBA8, yuv)
+            .unwrap()
+            .with_level(level)
+            .unwrap();
+        group.bench_function(format!("{level:?}"), |b| {
+            b.iter(|| kernel.to_rgb(black_box(&source[..PIXELS]), chroma, chroma, &mut target))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, row_kernels, tensor_kernels, yuv_kernels);
+criterion_main!(benches);
@@ -8,8 +8,10 @@
 //! cargo run --example capabilities
 //! ```
 
+#[cfg(windows)]
 use wgc::capabilities;
 
+#[cfg(windows)]
 fn main() -> anyhow::Result<()> {
     println!("=== Windows Graphics Capture Capabilities Demo ===\n");
     println!("This example demonstrates all the `is_*` functions from `capabilities.rs`.");
@@ -96,3 +98,9 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// The capture API only exists on Windows.
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("This example requires Windows.");
+}
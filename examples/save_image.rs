@@ -1,6 +1,9 @@
+#[cfg(windows)]
 use image::{ImageBuffer, Rgba};
+#[cfg(windows)]
 use wgc::*;
 
+#[cfg(windows)]
 fn main() -> anyhow::Result<()> {
     // run with `cargo run --example save_image --features tracing` to see debug output,
     // set `RUST_LOG=trace` environment variable to see verbose output
@@ -37,3 +40,9 @@ fn main() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+// The capture API only exists on Windows.
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("This example requires Windows.");
+}
@@ -1,6 +1,9 @@
+#[cfg(windows)]
 use show_image::{ImageInfo, ImageView};
+#[cfg(windows)]
 use wgc::*;
 
+#[cfg(windows)]
 #[show_image::main]
 fn main() -> anyhow::Result<()> {
     // run with `cargo run --example show_image --features tracing` to see debug output,
@@ -42,3 +45,9 @@ fn main() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+// The capture API only exists on Windows.
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("This example requires Windows.");
+}
@@ -14,10 +14,12 @@
 //! cargo run --example tutorial --features tracing
 //! ```
 
+#[cfg(windows)]
 use windows::Win32::{
     Graphics::Gdi::MonitorFromWindow, UI::WindowsAndMessaging::GetForegroundWindow,
 };
 
+#[cfg(windows)]
 fn main() -> anyhow::Result<()> {
     // Initialize tracing subscriber for logging and debugging output.
     // The log level can be controlled via the RUST_LOG environment variable.
@@ -77,7 +79,7 @@ fn main() -> anyhow::Result<()> {
     // Create a new `Wgc` instance with the configured item and settings.
     // This sets up the Windows Graphics Capture pipeline and prepares for
     // frame acquisition.
-    let wgc = wgc::Wgc::new(item, settings)?;
+    let mut wgc = wgc::Wgc::new(item, settings)?;
 
     // ========================================================================
     // Step 4: Capture and process frames
@@ -88,7 +90,7 @@ fn main() -> anyhow::Result<()> {
     // Each frame contains metadata such as `render_time`, which indicates
     // when the frame was rendered by the GPU. This is useful for measuring
     // capture latency and synchronization.
-    for (i, frame) in wgc.take(3).enumerate() {
+    for (i, frame) in wgc.by_ref().take(3).enumerate() {
         let frame = frame?;
         let render_time = frame.render_time()?;
 
@@ -102,5 +104,25 @@ fn main() -> anyhow::Result<()> {
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
 
+    // ========================================================================
+    // Step 5: Inspect the capture metrics
+    // ========================================================================
+    // The session keeps rolling histograms of the capture-to-delivery latency
+    // and of read durations, along with the delivered frame rate and drops.
+    // With the `tracing` feature, a summary is also logged periodically.
+    let metrics = wgc.metrics();
+    println!(
+        "{} frames at {:.1} fps, median latency {:?}",
+        metrics.delivered,
+        metrics.frame_rate,
+        metrics.latency.percentile(50.0).unwrap_or_default()
+    );
+
     Ok(())
 }
+
+// The capture API only exists on Windows.
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("This example requires Windows.");
+}
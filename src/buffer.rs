@@ -0,0 +1,238 @@
+//! Reusable pixel buffers.
+//!
+//! Reading every frame into a fresh `Vec` puts an allocation on the hot path of a capture
+//! loop. [`BufferPool`] recycles buffers instead, and
+//! [`CaptureFrame::read_pixels_into`] reads into any caller-provided buffer.
+
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use crate::*;
+
+/// Returns the number of bytes needed to hold a frame of the given size and format whose
+/// rows are `stride` bytes apart. The last row need not be followed by padding.
+pub(crate) fn required_len(
+    pixel_format: PixelFormat,
+    size: FrameSize,
+    stride: usize,
+) -> std::result::Result<usize, WgcError> {
+    if pixel_format.yuv_layout().is_some() {
+        return Err(WgcError::UnsupportedPixelFormat(pixel_format));
+    }
+    let row_bytes = size.width as usize * pixel_format.bytes_per_pixel() as usize;
+    if stride < row_bytes {
+        return Err(WgcError::InvalidStride { stride, row_bytes });
+    }
+    Ok(match size.height as usize {
+        0 => 0,
+        height => stride * (height - 1) + row_bytes,
+    })
+}
+
+/// Checks that a buffer of `len` bytes can hold a frame of the given size and format whose
+/// rows are `stride` bytes apart.
+pub(crate) fn check_buffer(
+    pixel_format: PixelFormat,
+    size: FrameSize,
+    len: usize,
+    stride: usize,
+) -> std::result::Result<(), WgcError> {
+    let expected = required_len(pixel_format, size, stride)?;
+    if len < expected {
+        return Err(WgcError::BufferTooSmall {
+            expected,
+            actual: len,
+        });
+    }
+    Ok(())
+}
+
+/// Copies `rows` rows of `row_bytes` bytes between buffers with different strides.
+pub(crate) fn copy_rows(
+    source: &[u8],
+    source_stride: usize,
+    destination: &mut [u8],
+    destination_stride: usize,
+    row_bytes: usize,
+    rows: usize,
+) {
+    for y in 0..rows {
+        let source_row = &source[y * source_stride..y * source_stride + row_bytes];
+        destination[y * destination_stride..y * destination_stride + row_bytes]
+            .copy_from_slice(source_row);
+    }
+}
+
+type BufferKey = (FrameSize, PixelFormat);
+
+#[derive(Debug)]
+struct Shared {
+    buffers: HashMap<BufferKey, Vec<Vec<u8>>>,
+    max_buffers_per_key: usize,
+}
+
+/// A pool of pixel buffers, recycled by frame size and pixel format.
+///
+/// [`get`](Self::get) hands out a [`PooledBuffer`] of the
+/// [`buffer_len`](PixelFormat::buffer_len) of the given size and format, reusing a buffer
+/// returned earlier when one is available. Dropping a `PooledBuffer` returns it to the
+/// pool, which keeps at most [`max_buffers_per_key`](Self::max_buffers_per_key) free
+/// buffers for each size and format.
+///
+/// Clones of a pool share its buffers, so a pool can be handed to other threads along with
+/// the buffers it hands out.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let item = SyntheticItem {
+///     size: FrameSize::new(8, 2),
+///     frame_count: Some(3),
+///     ..Default::default()
+/// };
+/// let pool = BufferPool::new();
+/// for frame in SyntheticSource::new(item, WgcSettings::default())? {
+///     let pixels = frame?.read_pixels_pooled(None, &pool)?;
+///     assert_eq!(pixels.len(), 8 * 2 * 4);
+/// }
+/// // The same buffer was used for every frame.
+/// assert_eq!(pool.available(FrameSize::new(8, 2), PixelFormat::RGBA8), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl BufferPool {
+    /// Creates an empty pool that keeps up to 4 free buffers for each size and format.
+    pub fn new() -> Self {
+        Self::with_max_buffers_per_key(4)
+    }
+
+    /// Creates an empty pool that keeps up to `max_buffers_per_key` free buffers for each
+    /// size and format. Buffers returned beyond that limit are freed.
+    pub fn with_max_buffers_per_key(max_buffers_per_key: usize) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                buffers: HashMap::new(),
+                max_buffers_per_key,
+            })),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Shared> {
+        // The shared state is consistent between any two statements, so a panic while it
+        // was locked cannot have corrupted it.
+        self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the maximum number of free buffers kept for each size and format.
+    pub fn max_buffers_per_key(&self) -> usize {
+        self.lock().max_buffers_per_key
+    }
+
+    /// Returns a buffer for a frame of the given size and format.
+    ///
+    /// Recycled buffers hold the pixels they were last filled with; new buffers are
+    /// zeroed.
+    pub fn get(&self, size: FrameSize, pixel_format: PixelFormat) -> PooledBuffer {
+        let recycled = self
+            .lock()
+            .buffers
+            .get_mut(&(size, pixel_format))
+            .and_then(Vec::pop);
+        PooledBuffer {
+            data: recycled.unwrap_or_else(|| vec![0; pixel_format.buffer_len(size)]),
+            size,
+            pixel_format,
+            pool: Arc::downgrade(&self.shared),
+        }
+    }
+
+    /// Returns the number of free buffers of the given size and format.
+    pub fn available(&self, size: FrameSize, pixel_format: PixelFormat) -> usize {
+        self.lock()
+            .buffers
+            .get(&(size, pixel_format))
+            .map_or(0, Vec::len)
+    }
+
+    /// Frees all buffers of the pool, for example after the capture item has been
+    /// resized and buffers of the old size will not be used again.
+    pub fn clear(&self) {
+        self.lock().buffers.clear();
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A pixel buffer handed out by a [`BufferPool`], returned to the pool when dropped.
+///
+/// It dereferences to the bytes of the buffer, which are tightly packed when filled by
+/// [`CaptureFrame::read_pixels_pooled`].
+#[derive(Debug)]
+pub struct PooledBuffer {
+    data: Vec<u8>,
+    size: FrameSize,
+    pixel_format: PixelFormat,
+    pool: Weak<Mutex<Shared>>,
+}
+
+impl PooledBuffer {
+    /// Returns the frame size the buffer was requested for.
+    pub fn size(&self) -> FrameSize {
+        self.size
+    }
+
+    /// Returns the pixel format the buffer was requested for.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Detaches the buffer from its pool and returns its bytes.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.pool = Weak::new();
+        std::mem::take(&mut self.data)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let Some(shared) = self.pool.upgrade() else {
+            return;
+        };
+        let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+        let max_buffers_per_key = shared.max_buffers_per_key;
+        let buffers = shared
+            .buffers
+            .entry((self.size, self.pixel_format))
+            .or_default();
+        if buffers.len() < max_buffers_per_key {
+            buffers.push(std::mem::take(&mut self.data));
+        }
+    }
+}
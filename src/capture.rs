@@ -3,11 +3,10 @@ use windows::{
     Graphics::{
         Capture::{Direct3D11CaptureFramePool, GraphicsCaptureItem, GraphicsCaptureSession},
         DirectX::Direct3D11::IDirect3DDevice,
-        SizeInt32,
     },
     System::DispatcherQueueController,
     Win32::{
-        Foundation::{GetLastError, HMODULE},
+        Foundation::{GetLastError, HMODULE, WAIT_FAILED},
         Graphics::{
             Direct2D::{
                 D2D1_DEVICE_CONTEXT_OPTIONS_NONE, D2D1_FACTORY_TYPE_SINGLE_THREADED,
@@ -17,17 +16,22 @@ use windows::{
             Direct3D11::*,
             Dxgi::IDXGIDevice,
         },
+        System::Threading::INFINITE,
         System::WinRT::{
             CreateDispatcherQueueController, DQTAT_COM_NONE, DQTYPE_THREAD_CURRENT,
             Direct3D11::CreateDirect3D11DeviceFromDXGIDevice, DispatcherQueueOptions,
         },
         UI::WindowsAndMessaging::{
-            DispatchMessageW, GetMessageW, MSG, PostQuitMessage, TranslateMessage,
+            DispatchMessageW, MSG, MWMO_INPUTAVAILABLE, MsgWaitForMultipleObjectsEx, PM_REMOVE,
+            PeekMessageW, PostQuitMessage, QS_ALLINPUT, TranslateMessage, WM_QUIT,
         },
     },
     core::*,
 };
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::*;
 
 /// The main entry point for Windows Graphics Capture (WGC) functionality.
@@ -67,7 +71,9 @@ use crate::*;
 /// # Important Notes
 /// - The `Wgc` struct owns all capture resources and must be kept alive while capturing.
 /// - It implements [`Iterator`] with [`Frame`] as the item type (wrapped in `Result`).
-/// - The iterator will block until a frame is available or an error occurs.
+/// - The iterator will block until a frame is available or an error occurs. Use the
+///   [`FrameWaiter`] methods, such as [`next_timeout`](FrameWaiter::next_timeout), to bound
+///   the wait.
 /// - Use [`WgcSettings`] to configure frame buffering, pixel format, and other options.
 ///
 pub struct Wgc {
@@ -76,7 +82,12 @@ pub struct Wgc {
     _item: GraphicsCaptureItem,
     frame_pool: Direct3D11CaptureFramePool,
     settings: WgcSettings,
-    buffer_size: SizeInt32,
+    pool_state: FramePoolState,
+    counters: FrameCounters,
+    overflow: OverflowEstimator,
+    waited: bool,
+    closed: bool,
+    metrics: Arc<CaptureMetrics>,
     direct3d_device: IDirect3DDevice,
     d2d1_context: ID2D1DeviceContext,
 }
@@ -102,7 +113,7 @@ impl Wgc {
         let buffer_size = item.Size()?;
         let frame_pool = Direct3D11CaptureFramePool::Create(
             &direct3d_device,
-            settings.pixel_format.into(),
+            settings.pixel_format.surface_format().into(),
             settings.frame_queue_length,
             buffer_size,
         )?;
@@ -135,9 +146,13 @@ impl Wgc {
             session.SetMinUpdateInterval(min_update_interval.into())?;
         }
 
+        let metrics = Arc::new(CaptureMetrics::new(settings.metrics, QpcClock));
+        metrics.record_frame_size(buffer_size.into());
+        let closed_metrics = metrics.clone();
         item.Closed(
             &TypedEventHandler::<GraphicsCaptureItem, IInspectable>::new(move |_item, _| {
                 debug!("Item closed, stopping capture");
+                closed_metrics.record_item_closed();
                 unsafe { PostQuitMessage(0) };
                 Ok(())
             }),
@@ -150,65 +165,182 @@ impl Wgc {
             frame_pool,
             settings,
             direct3d_device,
-            buffer_size,
+            pool_state: FramePoolState::new(buffer_size.into()),
+            counters: FrameCounters::default(),
+            overflow: OverflowEstimator::new(
+                settings.frame_queue_length as u32,
+                settings.min_update_interval,
+            ),
+            waited: false,
+            closed: false,
+            metrics,
             d2d1_context,
         })
     }
+
+    /// Returns the number of frames delivered, dropped for a content size change and
+    /// skipped for a full frame queue so far.
+    ///
+    /// The skipped frames are only estimated, in
+    /// [`estimated_skipped_for_overflow`](FrameCounters::estimated_skipped_for_overflow).
+    pub fn counters(&self) -> FrameCounters {
+        self.counters
+    }
+
+    /// Returns the latency and throughput metrics of the capture session over the window
+    /// of [`WgcSettings::metrics`].
+    ///
+    /// The read durations are those of the frames delivered by this session. The queue
+    /// depth is not reported by Windows Graphics Capture; it is approximated by the number
+    /// of frames taken in a row without waiting.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the collector of the metrics of the capture session, which can be shared
+    /// with other threads, for example to serve it with a
+    /// [`MetricsExporter`](crate::MetricsExporter).
+    pub fn metrics_collector(&self) -> Arc<CaptureMetrics> {
+        self.metrics.clone()
+    }
 }
 
 impl Iterator for Wgc {
     type Item = std::result::Result<Frame, WgcError>;
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_until(Duration::MAX) {
+                Ok(NextFrame::Frame(frame)) => return Some(Ok(frame)),
+                Ok(NextFrame::TimedOut) => continue,
+                Ok(NextFrame::Closed) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Bounded waits pump the messages of the capture thread, like [`Iterator::next`], so they
+/// must be called on the thread that created the [`Wgc`].
+///
+/// # Example
+///
+/// ```ignore
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// let mut wgc = Wgc::new(item, WgcSettings::default())?;
+/// loop {
+///     match wgc.next_timeout(Duration::from_secs(1))? {
+///         NextFrame::Frame(frame) => println!("Frame {}", frame.sequence()),
+///         NextFrame::TimedOut => println!("No frame for a second, the window may be idle"),
+///         NextFrame::Closed => break,
+///     }
+/// }
+/// ```
+impl FrameWaiter for Wgc {
+    type Frame = Frame;
+
+    fn clock(&self) -> &dyn Clock {
+        &QpcClock
+    }
+
+    fn try_take(&mut self) -> std::result::Result<Option<Frame>, WgcError> {
+        while let Ok(frame) = self.frame_pool.TryGetNextFrame() {
+            let frame_size = frame.ContentSize()?;
+            let ticks = frame.SystemRelativeTime()?.Duration;
+            let timestamp = Duration::from_nanos(ticks.max(0) as u64 * 100);
+            let skipped = self.overflow.frame(timestamp, self.waited);
+            if skipped > 0 {
+                trace!(
+                    "{} frames may have been skipped as the frame queue was full",
+                    skipped
+                );
+                self.counters.estimated_skipped_for_overflow += skipped;
+                self.metrics.record_estimated_skipped_for_overflow(skipped);
+            }
+            self.waited = false;
+
+            if self.pool_state.needs_recreate(frame_size.into()) {
+                trace!(
+                    "Frame dropped as buffer size changed from {:?} to {:?}",
+                    self.pool_state.buffer_size(),
+                    frame_size
+                );
+                self.frame_pool.Recreate(
+                    &self.direct3d_device,
+                    self.settings.pixel_format.surface_format().into(),
+                    self.settings.frame_queue_length,
+                    frame_size,
+                )?;
+                self.pool_state.recreated(frame_size.into());
+                self.counters.dropped_for_resize += 1;
+                self.metrics.record_dropped_for_resize();
+                self.metrics.record_frame_size(frame_size.into());
+                continue;
+            }
+            trace!("Got frame");
+            let frame = Frame::new(
+                frame,
+                self.d2d1_context.clone(),
+                self.settings.pixel_format,
+                self.settings,
+            )
+            .with_sequence(self.counters.delivered);
+            self.counters.delivered += 1;
+            self.metrics
+                .record_delivery(timestamp, self.overflow.backlog());
+            return Ok(Some(
+                frame
+                    .with_counters(self.counters)
+                    .with_metrics(self.metrics.clone()),
+            ));
+        }
+        Ok(None)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> std::result::Result<bool, WgcError> {
+        self.waited = true;
+        if self.closed {
+            return Ok(false);
+        }
         let mut msg = MSG::default();
+        let mut dispatched = false;
         unsafe {
-            loop {
-                let frame = self.frame_pool.TryGetNextFrame();
-                if let Ok(frame) = frame {
-                    let frame_size = match frame.ContentSize() {
-                        Ok(size) => size,
-                        Err(err) => return Some(Err(err.into())),
-                    };
-
-                    if frame_size != self.buffer_size {
-                        trace!(
-                            "Frame dropped as buffer size changed from {:?} to {:?}",
-                            self.buffer_size, frame_size
-                        );
-                        match self.frame_pool.Recreate(
-                            &self.direct3d_device,
-                            self.settings.pixel_format.into(),
-                            self.settings.frame_queue_length,
-                            frame_size,
-                        ) {
-                            Ok(_) => {
-                                self.buffer_size = frame_size;
-                            }
-                            Err(err) => return Some(Err(err.into())),
-                        }
-                    } else {
-                        trace!("Got frame");
-                        let frame = Frame::new(
-                            frame,
-                            self.d2d1_context.clone(),
-                            self.settings.pixel_format,
-                            self.settings,
-                        );
-                        return Some(Ok(frame));
-                    }
-                }
-                match GetMessageW(&mut msg, None, 0, 0).0 {
-                    -1 => {
-                        let e: windows::core::Error = GetLastError().to_hresult().into();
-                        return Some(Err(e.into()));
-                    }
-                    0 => return None,
-                    _ => {
-                        let _ = TranslateMessage(&msg);
-                        let _ = DispatchMessageW(&msg);
-                    }
+            // Pending messages are dispatched first, as the wait below only wakes up for
+            // messages that arrive after it starts
+            while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                if msg.message == WM_QUIT {
+                    self.closed = true;
+                    return Ok(false);
                 }
+                let _ = TranslateMessage(&msg);
+                let _ = DispatchMessageW(&msg);
+                dispatched = true;
+            }
+            if dispatched || timeout.is_zero() {
+                return Ok(true);
+            }
+            // Rounded up, so that a sub-millisecond remainder does not spin
+            let millis = timeout
+                .as_nanos()
+                .div_ceil(1_000_000)
+                .min(INFINITE as u128 - 1) as u32;
+            if MsgWaitForMultipleObjectsEx(None, millis, QS_ALLINPUT, MWMO_INPUTAVAILABLE)
+                == WAIT_FAILED
+            {
+                let e: windows::core::Error = GetLastError().to_hresult().into();
+                return Err(e.into());
             }
         }
+        Ok(true)
+    }
+}
+
+impl CaptureSource for Wgc {
+    type Frame = Frame;
+
+    fn counters(&self) -> FrameCounters {
+        Wgc::counters(self)
     }
 }
 
@@ -247,3 +379,66 @@ fn create_dispatcher_queue_controller() -> std::result::Result<DispatcherQueueCo
     let control = unsafe { CreateDispatcherQueueController(options)? };
     Ok(control)
 }
+
+/// Estimates the frames a frame pool skipped while its queue was full, for
+/// [`FrameCounters::estimated_skipped_for_overflow`].
+///
+/// A full pool does not capture new frames until one is released, and nothing reports
+/// them. When the consumer has taken as many frames in a row as the queue holds without
+/// waiting, the queue may have been full, and a gap of several frame intervals before the
+/// next frame is counted as skipped frames. The frame interval is the minimum update
+/// interval of the session if set, and otherwise the shortest gap seen so far.
+#[derive(Debug, Clone, Copy)]
+struct OverflowEstimator {
+    queue_length: u32,
+    backlog: u32,
+    interval: Option<Duration>,
+    fixed_interval: bool,
+    last_timestamp: Option<Duration>,
+}
+
+impl OverflowEstimator {
+    fn new(queue_length: u32, min_update_interval: Option<Duration>) -> Self {
+        Self {
+            queue_length,
+            backlog: 0,
+            interval: min_update_interval.filter(|interval| !interval.is_zero()),
+            fixed_interval: min_update_interval.is_some_and(|interval| !interval.is_zero()),
+            last_timestamp: None,
+        }
+    }
+
+    /// Returns the number of frames taken in a row without waiting, which approximates the
+    /// depth of the queue when the last one was taken.
+    fn backlog(&self) -> u32 {
+        self.backlog.min(self.queue_length.max(1))
+    }
+
+    /// Records a frame taken from the pool, and returns the number of frames estimated to
+    /// have been skipped before it. `waited` tells whether the consumer had to wait for the
+    /// frame, that is, whether the queue was empty.
+    fn frame(&mut self, timestamp: Duration, waited: bool) -> u64 {
+        let was_full = self.backlog >= self.queue_length;
+        self.backlog = if waited { 1 } else { self.backlog + 1 };
+        let Some(last_timestamp) = self.last_timestamp.replace(timestamp) else {
+            return 0;
+        };
+        let gap = timestamp.saturating_sub(last_timestamp);
+        if gap.is_zero() {
+            return 0;
+        }
+        match self.interval {
+            Some(interval) if was_full => {
+                // Round to the nearest number of intervals, less the frame itself.
+                let intervals = (gap.as_nanos() + interval.as_nanos() / 2) / interval.as_nanos();
+                intervals.saturating_sub(1) as u64
+            }
+            _ => {
+                if !self.fixed_interval {
+                    self.interval = Some(self.interval.map_or(gap, |interval| interval.min(gap)));
+                }
+                0
+            }
+        }
+    }
+}
@@ -0,0 +1,483 @@
+//! Platform-neutral types shared by every capture source.
+//!
+//! Nothing in this module depends on the Windows APIs, so code written against these
+//! types compiles and runs on any platform.
+
+/// The size of a frame, in pixels.
+#[derive(Debug, smart_default::SmartDefault, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameSize {
+    #[default(0)]
+    pub width: u32,
+    #[default(0)]
+    pub height: u32,
+}
+
+impl FrameSize {
+    /// Creates a new frame size.
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Returns the number of pixels in a frame of this size.
+    pub const fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Returns `true` if either dimension is zero.
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::Graphics::SizeInt32> for FrameSize {
+    fn from(size: windows::Graphics::SizeInt32) -> Self {
+        let windows::Graphics::SizeInt32 {
+            Width: width,
+            Height: height,
+        } = size;
+        let width = width.max(0) as u32;
+        let height = height.max(0) as u32;
+        Self { width, height }
+    }
+}
+
+/// An RGBA color with 8 bits per channel, not premultiplied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    /// Creates an opaque color.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    /// Creates a color with the given opacity.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Creates an opaque gray with all color channels set to `value`.
+    ///
+    /// For example, YOLO-family models expect letterbox padding of `Color::gray(114)`.
+    pub const fn gray(value: u8) -> Self {
+        Self::rgb(value, value, value)
+    }
+
+    /// Opaque black.
+    pub const BLACK: Self = Self::rgb(0, 0, 0);
+
+    /// Opaque white.
+    pub const WHITE: Self = Self::rgb(255, 255, 255);
+
+    /// Opaque 50% gray, the default letterbox color.
+    pub const GRAY: Self = Self::gray(128);
+
+    /// Fully transparent black.
+    pub const TRANSPARENT: Self = Self::rgba(0, 0, 0, 0);
+
+    /// Returns the color with its color channels multiplied by its alpha, as stored in
+    /// premultiplied buffers.
+    pub fn premultiplied(&self) -> Self {
+        let multiply = |channel: u8| ((channel as u32 * self.a as u32 + 127) / 255) as u8;
+        Self::rgba(multiply(self.r), multiply(self.g), multiply(self.b), self.a)
+    }
+}
+
+/// The coefficients relating RGB to luma (and, for YCbCr formats, chroma).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorMatrix {
+    /// ITU-R BT.601, used by standard-definition video and most image libraries.
+    #[default]
+    Bt601,
+    /// ITU-R BT.709, used by high-definition video and sRGB displays.
+    Bt709,
+    /// ITU-R BT.2020, used by ultra-high-definition and HDR video.
+    Bt2020,
+}
+
+impl ColorMatrix {
+    /// Returns the weights of red, green and blue in luma, which sum to 1.
+    pub fn luma_weights(&self) -> [f32; 3] {
+        match self {
+            ColorMatrix::Bt601 => [0.299, 0.587, 0.114],
+            ColorMatrix::Bt709 => [0.2126, 0.7152, 0.0722],
+            ColorMatrix::Bt2020 => [0.2627, 0.6780, 0.0593],
+        }
+    }
+
+    /// Returns the luma of an RGB color.
+    pub fn luma(&self, r: f32, g: f32, b: f32) -> f32 {
+        let [kr, kg, kb] = self.luma_weights();
+        kr * r + kg * g + kb * b
+    }
+}
+
+/// The range of values used by YCbCr samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorRange {
+    /// Luma from 16 to 235 and chroma from 16 to 240, as used by most video.
+    #[default]
+    Limited,
+    /// Luma and chroma from 0 to 255, as used by JPEG.
+    Full,
+}
+
+/// The arrangement of samples in a YCbCr [`PixelFormat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum YuvLayout {
+    /// A full-resolution Y plane followed by a half-resolution plane of interleaved U and
+    /// V samples (4:2:0).
+    Nv12,
+    /// A full-resolution Y plane followed by half-resolution U and V planes (4:2:0).
+    I420,
+    /// Interleaved `Y0 U Y1 V` samples, one group for every two pixels of a row (4:2:2).
+    Yuy2,
+}
+
+/// How the color values of a [`PixelFormat`] relate to light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// sRGB-encoded BT.709 primaries, as used by SDR displays.
+    Srgb,
+    /// Linear BT.709 primaries where 1.0 is 80 nits, as used by Windows for HDR
+    /// composition. Values above 1.0 are brighter than SDR white, and values may be negative
+    /// for colors outside the BT.709 gamut.
+    ScRgb,
+    /// PQ-encoded (SMPTE ST 2084) BT.2020 primaries, as used by HDR10 video and displays.
+    Hdr10,
+}
+
+/// How pixels of a [`PixelFormat`] are produced from the captured surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Encoding {
+    /// The pixels are read from the surface unchanged.
+    Native,
+    Rgb24,
+    Bgr24,
+    Gray8(ColorMatrix),
+    Gray16(ColorMatrix),
+    Yuv(YuvLayout, ColorMatrix, ColorRange),
+    Hdr10,
+}
+
+/// Specifies the pixel format for capture output.
+///
+/// Contains the underlying DirectX pixel format and the number of bytes per pixel.
+/// Use the predefined constants [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`]
+/// for common formats, or create a custom format with [`PixelFormat::new`].
+///
+/// The packed 3-channel formats [`PixelFormat::RGB24`] and [`PixelFormat::BGR24`] and the
+/// luma formats [`PixelFormat::GRAY8`] and [`PixelFormat::GRAY16`] have no DirectX
+/// equivalent: frames are captured as [`PixelFormat::BGRA8`] and converted on the CPU, as
+/// by [`CpuFrame::convert`](crate::CpuFrame::convert). The same applies to the YCbCr
+/// formats [`PixelFormat::NV12`], [`PixelFormat::I420`] and [`PixelFormat::YUY2`], which are
+/// held by [`YuvFrame`](crate::YuvFrame) rather than [`CpuFrame`](crate::CpuFrame).
+///
+/// For HDR content, capture in [`PixelFormat::RGBA16F`] or [`PixelFormat::R10G10B10A2`] and
+/// tone-map the frames to SDR with [`CpuFrame::tone_map`](crate::CpuFrame::tone_map).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelFormat {
+    /// The underlying DirectX pixel format of the captured surface, stored as its raw
+    /// `DXGI_FORMAT` value so that this type is available on every platform.
+    format: i32,
+    /// The number of bytes required to store a single pixel.
+    bytes_per_pixel: u32,
+    encoding: Encoding,
+}
+
+impl PixelFormat {
+    /// Creates a new custom pixel format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The underlying DirectX pixel format.
+    /// * `bytes_per_pixel` - The number of bytes required to store a single pixel.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::settings::PixelFormat;
+    /// use windows::Graphics::DirectX::DirectXPixelFormat;
+    ///
+    /// let custom_format = PixelFormat::new(
+    ///     DirectXPixelFormat::R8G8B8A8UIntNormalized,
+    ///     4,
+    /// );
+    /// ```
+    #[cfg(windows)]
+    pub fn new(
+        format: windows::Graphics::DirectX::DirectXPixelFormat,
+        bytes_per_pixel: u32,
+    ) -> Self {
+        Self {
+            format: format.0,
+            bytes_per_pixel,
+            encoding: Encoding::Native,
+        }
+    }
+
+    /// RGBA 8-bit per channel format (32 bits per pixel).
+    ///
+    /// Each pixel consists of 4 bytes: Red, Green, Blue, and Alpha, in that order.
+    /// This is a common format for capture output.
+    pub const RGBA8: Self = Self {
+        // DXGI_FORMAT_R8G8B8A8_UNORM
+        format: 28,
+        bytes_per_pixel: 4,
+        encoding: Encoding::Native,
+    };
+
+    /// BGRA 8-bit per channel format (32 bits per pixel).
+    ///
+    /// Each pixel consists of 4 bytes: Blue, Green, Red, and Alpha, in that order.
+    /// This format is commonly used in Windows GDI and DirectComposition.
+    pub const BGRA8: Self = Self {
+        // DXGI_FORMAT_B8G8R8A8_UNORM
+        format: 87,
+        bytes_per_pixel: 4,
+        encoding: Encoding::Native,
+    };
+
+    /// RGBA 16-bit float per channel format (64 bits per pixel).
+    ///
+    /// Each pixel consists of 4 little-endian half-precision floats: Red, Green, Blue, and
+    /// Alpha, in that order, in the [`ColorSpace::ScRgb`] color space. This is the format
+    /// to capture HDR content without clipping.
+    ///
+    /// Frames in this format are only resized on the GPU, when a capture reads them with a
+    /// desired size. The CPU resizing of [`CpuFrame::resize`](crate::CpuFrame::resize),
+    /// [`CpuFrame::output`](crate::CpuFrame::output) and the default
+    /// [`CaptureFrame::read_outputs`](crate::CaptureFrame::read_outputs) returns
+    /// [`WgcError::UnsupportedPixelFormat`](crate::WgcError::UnsupportedPixelFormat); tone-map
+    /// the frames to SDR first. Cropping and reorienting work on the CPU.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let data = [2.0, 1.0, 0.5, 1.0].map(|v| f16::from_f32(v).to_le_bytes()).concat().repeat(4);
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(2, 2), PixelFormat::RGBA16F)?;
+    /// let size = FrameSize::new(1, 1);
+    /// let linear = FrameInterpolationMode::Linear;
+    /// assert!(matches!(
+    ///     frame.resize(size, ResizeMode::Stretch, linear),
+    ///     Err(WgcError::UnsupportedPixelFormat(PixelFormat::RGBA16F))
+    /// ));
+    ///
+    /// let sdr = frame.tone_map(&ToneMapOptions::default())?;
+    /// assert_eq!(sdr.resize(size, ResizeMode::Stretch, linear)?.size(), size);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const RGBA16F: Self = Self {
+        // DXGI_FORMAT_R16G16B16A16_FLOAT
+        format: 10,
+        bytes_per_pixel: 8,
+        encoding: Encoding::Native,
+    };
+
+    /// RGB 10-bit per channel format with 2-bit alpha (32 bits per pixel).
+    ///
+    /// Each pixel is a little-endian 32-bit value holding Red in bits 0-9, Green in bits
+    /// 10-19, Blue in bits 20-29 and Alpha in bits 30-31, in the [`ColorSpace::Hdr10`]
+    /// color space. Frames are captured as [`PixelFormat::RGBA16F`] and encoded on the CPU.
+    ///
+    /// As with [`PixelFormat::RGBA16F`], frames in this format are not resized on the CPU.
+    pub const R10G10B10A2: Self = Self {
+        // DXGI_FORMAT_R10G10B10A2_UNORM
+        format: 24,
+        bytes_per_pixel: 4,
+        encoding: Encoding::Hdr10,
+    };
+
+    /// RGB 8-bit per channel format without alpha (24 bits per pixel).
+    ///
+    /// Each pixel consists of 3 bytes: Red, Green and Blue, in that order.
+    pub const RGB24: Self = Self::converted(3, Encoding::Rgb24);
+
+    /// BGR 8-bit per channel format without alpha (24 bits per pixel).
+    ///
+    /// Each pixel consists of 3 bytes: Blue, Green and Red, in that order.
+    pub const BGR24: Self = Self::converted(3, Encoding::Bgr24);
+
+    /// 8-bit luma format with BT.601 weights.
+    ///
+    /// Use [`PixelFormat::gray8`] to select other weights.
+    pub const GRAY8: Self = Self::gray8(ColorMatrix::Bt601);
+
+    /// 16-bit luma format with BT.601 weights, stored little-endian.
+    ///
+    /// Use [`PixelFormat::gray16`] to select other weights.
+    pub const GRAY16: Self = Self::gray16(ColorMatrix::Bt601);
+
+    /// Returns an 8-bit luma format computed with the weights of `matrix`.
+    pub const fn gray8(matrix: ColorMatrix) -> Self {
+        Self::converted(1, Encoding::Gray8(matrix))
+    }
+
+    /// Returns a 16-bit little-endian luma format computed with the weights of `matrix`.
+    pub const fn gray16(matrix: ColorMatrix) -> Self {
+        Self::converted(2, Encoding::Gray16(matrix))
+    }
+
+    /// NV12 format with BT.601 coefficients and limited range.
+    ///
+    /// Use [`PixelFormat::yuv`] to select other coefficients or the full range.
+    pub const NV12: Self = Self::yuv(YuvLayout::Nv12, ColorMatrix::Bt601, ColorRange::Limited);
+
+    /// I420 format with BT.601 coefficients and limited range.
+    ///
+    /// Use [`PixelFormat::yuv`] to select other coefficients or the full range.
+    pub const I420: Self = Self::yuv(YuvLayout::I420, ColorMatrix::Bt601, ColorRange::Limited);
+
+    /// YUY2 format with BT.601 coefficients and limited range.
+    ///
+    /// Use [`PixelFormat::yuv`] to select other coefficients or the full range.
+    pub const YUY2: Self = Self::yuv(YuvLayout::Yuy2, ColorMatrix::Bt601, ColorRange::Limited);
+
+    /// Returns a YCbCr format with the given layout, coefficients and range.
+    ///
+    /// The [`bytes_per_pixel`](Self::bytes_per_pixel) of the format is the size of a Y
+    /// sample in the planar layouts and 2 in [`YuvLayout::Yuy2`]; use
+    /// [`buffer_len`](Self::buffer_len) for the size of a whole frame.
+    pub const fn yuv(layout: YuvLayout, matrix: ColorMatrix, range: ColorRange) -> Self {
+        let bytes_per_pixel = match layout {
+            YuvLayout::Nv12 | YuvLayout::I420 => 1,
+            YuvLayout::Yuy2 => 2,
+        };
+        Self::converted(bytes_per_pixel, Encoding::Yuv(layout, matrix, range))
+    }
+
+    const fn converted(bytes_per_pixel: u32, encoding: Encoding) -> Self {
+        Self {
+            format: Self::BGRA8.format,
+            bytes_per_pixel,
+            encoding,
+        }
+    }
+
+    /// Returns the DirectX pixel format of the pixels.
+    ///
+    /// Formats converted on the CPU that have no DirectX equivalent, such as
+    /// [`PixelFormat::RGB24`] and the YCbCr formats, return the format of
+    /// [`surface_format`](Self::surface_format). The surface is always captured in the
+    /// format of `surface_format`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use windows::Graphics::DirectX::DirectXPixelFormat;
+    /// use wgc::*;
+    ///
+    /// let hdr10 = PixelFormat::R10G10B10A2;
+    /// assert_eq!(hdr10.format(), DirectXPixelFormat::R10G10B10A2UIntNormalized);
+    /// assert_eq!(hdr10.surface_format().format(), DirectXPixelFormat::R16G16B16A16Float);
+    /// ```
+    #[cfg(windows)]
+    pub fn format(&self) -> windows::Graphics::DirectX::DirectXPixelFormat {
+        windows::Graphics::DirectX::DirectXPixelFormat(self.format)
+    }
+    pub fn bytes_per_pixel(&self) -> u32 {
+        self.bytes_per_pixel
+    }
+
+    /// Returns the format in which frames are captured before being converted to this
+    /// format on the CPU, which is `self` for formats without conversion.
+    pub fn surface_format(&self) -> PixelFormat {
+        match self.encoding {
+            Encoding::Native => *self,
+            Encoding::Hdr10 => Self::RGBA16F,
+            _ => Self::BGRA8,
+        }
+    }
+
+    /// Returns the color space of the format.
+    ///
+    /// Formats created with [`PixelFormat::new`] are assumed to be [`ColorSpace::Srgb`]
+    /// unless they are equal to [`PixelFormat::RGBA16F`].
+    pub fn color_space(&self) -> ColorSpace {
+        match self.encoding {
+            Encoding::Native if *self == Self::RGBA16F => ColorSpace::ScRgb,
+            Encoding::Hdr10 => ColorSpace::Hdr10,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    pub(crate) fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns the matrix used to compute luma and chroma, for the luma and YCbCr formats.
+    pub fn color_matrix(&self) -> Option<ColorMatrix> {
+        match self.encoding {
+            Encoding::Gray8(matrix) | Encoding::Gray16(matrix) => Some(matrix),
+            Encoding::Yuv(_, matrix, _) => Some(matrix),
+            _ => None,
+        }
+    }
+
+    /// Returns the layout of the YCbCr formats.
+    pub fn yuv_layout(&self) -> Option<YuvLayout> {
+        match self.encoding {
+            Encoding::Yuv(layout, _, _) => Some(layout),
+            _ => None,
+        }
+    }
+
+    /// Returns the range of the YCbCr formats.
+    pub fn color_range(&self) -> Option<ColorRange> {
+        match self.encoding {
+            Encoding::Yuv(_, _, range) => Some(range),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes of a tightly packed frame of the given size.
+    ///
+    /// This is `width * height * bytes_per_pixel`, except for the YCbCr formats, whose
+    /// chroma is subsampled: odd dimensions are rounded up for the chroma samples.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// let size = FrameSize::new(5, 3);
+    /// assert_eq!(PixelFormat::RGBA8.buffer_len(size), 60);
+    /// assert_eq!(PixelFormat::NV12.buffer_len(size), 15 + 2 * 3 * 2);
+    /// assert_eq!(PixelFormat::YUY2.buffer_len(size), 3 * 4 * 3);
+    /// ```
+    pub fn buffer_len(&self, size: FrameSize) -> usize {
+        let chroma_width = size.width.div_ceil(2) as usize;
+        let chroma_height = size.height.div_ceil(2) as usize;
+        match self.yuv_layout() {
+            Some(YuvLayout::Nv12 | YuvLayout::I420) => {
+                size.pixel_count() + 2 * chroma_width * chroma_height
+            }
+            Some(YuvLayout::Yuy2) => chroma_width * 4 * size.height as usize,
+            None => size.pixel_count() * self.bytes_per_pixel as usize,
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<PixelFormat> for windows::Graphics::DirectX::DirectXPixelFormat {
+    fn from(pixel_format: PixelFormat) -> Self {
+        Self(pixel_format.format)
+    }
+}
+
+#[cfg(windows)]
+impl From<PixelFormat> for windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT {
+    fn from(pixel_format: PixelFormat) -> Self {
+        Self(pixel_format.format)
+    }
+}
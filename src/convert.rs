@@ -0,0 +1,180 @@
+//! Pixel format conversions performed on the CPU.
+//!
+//! Formats without a DirectX equivalent, such as [`PixelFormat::RGB24`] or
+//! [`PixelFormat::GRAY8`], are captured as [`PixelFormat::BGRA8`] and converted by
+//! [`CpuFrame::convert`] after the pixels are read back. The conversions between 8-bit
+//! formats run through the vectorized row kernels of [`kernels`](crate::kernels).
+
+use crate::*;
+
+impl CpuFrame {
+    /// Converts the frame to another pixel format.
+    ///
+    /// Frames in [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`] can be converted to either
+    /// of them, to [`PixelFormat::RGB24`] and [`PixelFormat::BGR24`], which drop alpha, and to
+    /// the luma formats created by [`PixelFormat::gray8`] and [`PixelFormat::gray16`]. Luma is
+    /// computed from the stored color values, ignoring alpha.
+    ///
+    /// Frames in [`PixelFormat::RGBA8`], [`PixelFormat::BGRA8`] or [`PixelFormat::RGBA16F`]
+    /// can also be converted to [`PixelFormat::R10G10B10A2`], which is PQ-encoded. Use
+    /// [`CpuFrame::tone_map`] to convert HDR frames to 8-bit formats.
+    ///
+    /// Use [`CpuFrame::to_yuv`] for the YCbCr formats.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if either format is not supported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // A green and a blue BGRA pixel.
+    /// let data = vec![0, 255, 0, 255, 255, 0, 0, 255];
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(2, 1), PixelFormat::BGRA8)?;
+    ///
+    /// let rgb = frame.convert(PixelFormat::RGB24)?;
+    /// assert_eq!(rgb.stride(), 6);
+    /// assert_eq!(rgb.data(), &[0, 255, 0, 0, 0, 255]);
+    ///
+    /// assert_eq!(frame.convert(PixelFormat::GRAY8)?.data(), &[150, 29]);
+    /// let bt709 = frame.convert(PixelFormat::gray8(ColorMatrix::Bt709))?;
+    /// assert_eq!(bt709.data(), &[182, 18]);
+    ///
+    /// let gray16 = frame.convert(PixelFormat::GRAY16)?;
+    /// assert_eq!(gray16.pixel(0, 0), &38469u16.to_le_bytes());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert(&self, pixel_format: PixelFormat) -> std::result::Result<CpuFrame, WgcError> {
+        if pixel_format.encoding() == Encoding::Hdr10 {
+            return self.to_hdr10();
+        }
+        let Some(kernel) = kernels::RowKernel::new(self.pixel_format(), pixel_format) else {
+            let unsupported = match self.pixel_format() {
+                PixelFormat::RGBA8 | PixelFormat::BGRA8 => pixel_format,
+                source_format => source_format,
+            };
+            return Err(WgcError::UnsupportedPixelFormat(unsupported));
+        };
+
+        let row_bytes = self.size().width as usize * pixel_format.bytes_per_pixel() as usize;
+        let mut data = vec![0; row_bytes * self.size().height as usize];
+        // Frames without columns have no bytes to convert.
+        for (row, output) in self.rows().zip(data.chunks_exact_mut(row_bytes.max(1))) {
+            kernel.run(row, output);
+        }
+        Ok(CpuFrame::from_packed(data, self.size(), pixel_format)?.with_metadata_of(self))
+    }
+}
+
+/// Converts a frame, usually read in the [`surface_format`](PixelFormat::surface_format)
+/// of `pixel_format`, to `pixel_format` and `alpha_mode`, and returns its pixel data.
+pub(crate) fn into_pixels(
+    mut frame: CpuFrame,
+    pixel_format: PixelFormat,
+    alpha_mode: AlphaMode,
+) -> std::result::Result<Vec<u8>, WgcError> {
+    if frame.alpha_mode() != alpha_mode {
+        frame = frame.convert_alpha(alpha_mode)?;
+    }
+    if frame.pixel_format() == pixel_format {
+        Ok(frame.into_packed().into_data())
+    } else if pixel_format.yuv_layout().is_some() {
+        Ok(frame.to_yuv(pixel_format)?.into_data())
+    } else {
+        Ok(frame.convert(pixel_format)?.into_data())
+    }
+}
+
+impl CpuFrame {
+    /// Converts the pixel data to another [`AlphaMode`].
+    ///
+    /// Un-premultiplying divides the color channels by alpha, so the colors of fully
+    /// transparent pixels become black and those of nearly transparent pixels lose
+    /// precision. Converting to [`AlphaMode::Opaque`] composites the frame over black.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame is not [`PixelFormat::RGBA8`],
+    /// [`PixelFormat::BGRA8`] or [`PixelFormat::RGBA16F`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // Half-transparent red, as read by Direct2D.
+    /// let data = vec![128, 0, 0, 128];
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(1, 1), PixelFormat::RGBA8)?;
+    /// assert_eq!(frame.alpha_mode(), AlphaMode::Premultiplied);
+    ///
+    /// let straight = frame.convert_alpha(AlphaMode::Straight)?;
+    /// assert_eq!(straight.alpha_mode(), AlphaMode::Straight);
+    /// assert_eq!(straight.data(), &[255, 0, 0, 128]);
+    ///
+    /// let opaque = straight.convert_alpha(AlphaMode::Opaque)?;
+    /// assert_eq!(opaque.data(), &[128, 0, 0, 255]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn convert_alpha(&self, alpha_mode: AlphaMode) -> std::result::Result<CpuFrame, WgcError> {
+        let from = self.alpha_mode();
+        let mut frame = self.clone().into_packed().with_alpha_mode(alpha_mode);
+        if from == alpha_mode || from == AlphaMode::Opaque {
+            return Ok(frame);
+        }
+        match self.pixel_format() {
+            PixelFormat::RGBA8 | PixelFormat::BGRA8 => {
+                for pixel in frame.data_mut().chunks_exact_mut(4) {
+                    let alpha = pixel[3] as u32;
+                    let [color @ .., a] = pixel else {
+                        unreachable!()
+                    };
+                    for channel in color {
+                        *channel = match (from, alpha_mode) {
+                            (_, AlphaMode::Straight) if alpha == 0 => 0,
+                            (_, AlphaMode::Straight) => {
+                                ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8
+                            }
+                            (AlphaMode::Straight, _) => {
+                                ((*channel as u32 * alpha + 127) / 255) as u8
+                            }
+                            _ => *channel,
+                        };
+                    }
+                    if alpha_mode == AlphaMode::Opaque {
+                        *a = 255;
+                    }
+                }
+            }
+            PixelFormat::RGBA16F => {
+                for pixel in frame.data_mut().chunks_exact_mut(8) {
+                    let mut values =
+                        [0, 1, 2, 3].map(|i| f16::from_le_bytes([pixel[2 * i], pixel[2 * i + 1]]));
+                    let alpha = values[3].to_f32();
+                    for value in &mut values[..3] {
+                        let value_f32 = value.to_f32();
+                        *value = f16::from_f32(match (from, alpha_mode) {
+                            (_, AlphaMode::Straight) if alpha <= 0.0 => 0.0,
+                            (_, AlphaMode::Straight) => value_f32 / alpha,
+                            (AlphaMode::Straight, _) => value_f32 * alpha,
+                            _ => value_f32,
+                        });
+                    }
+                    if alpha_mode == AlphaMode::Opaque {
+                        values[3] = f16::ONE;
+                    }
+                    for (i, value) in values.iter().enumerate() {
+                        pixel[2 * i..2 * i + 2].copy_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+            pixel_format => return Err(WgcError::UnsupportedPixelFormat(pixel_format)),
+        }
+        Ok(frame)
+    }
+}
@@ -0,0 +1,278 @@
+//! An owned, platform-neutral copy of a frame's pixels.
+
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// An owned frame in CPU memory.
+///
+/// A `CpuFrame` bundles a pixel buffer with everything needed to interpret it: its size,
+/// pixel format, row stride and [`AlphaMode`], plus the render time, sequence number and
+/// [`LetterboxTransform`] of the frame it was read from. It is `Send + Sync` and does not
+/// depend on any Windows API, so frame processing built on it can be written and tested on
+/// any platform.
+///
+/// Rows are stored top to bottom, `stride` bytes apart. A row may be followed by padding,
+/// which the row and pixel accessors skip.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// // A 2x2 RGBA frame whose rows are padded to 12 bytes.
+/// let data = vec![
+///     1, 1, 1, 255, 2, 2, 2, 255, 0, 0, 0, 0, //
+///     3, 3, 3, 255, 4, 4, 4, 255,
+/// ];
+/// let frame = CpuFrame::from_raw(data, FrameSize::new(2, 2), PixelFormat::RGBA8, 12)?;
+/// assert_eq!(frame.pixel(1, 1), &[4, 4, 4, 255]);
+/// assert_eq!(frame.row(1), &[3, 3, 3, 255, 4, 4, 4, 255]);
+/// assert_eq!(frame.into_packed().stride(), 8);
+///
+/// fn assert_send_sync<T: Send + Sync>() {}
+/// assert_send_sync::<CpuFrame>();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuFrame {
+    data: Vec<u8>,
+    size: FrameSize,
+    pixel_format: PixelFormat,
+    stride: usize,
+    render_time: Option<Instant>,
+    timestamp: Option<Duration>,
+    sequence: u64,
+    transform: LetterboxTransform,
+    alpha_mode: AlphaMode,
+}
+
+impl CpuFrame {
+    /// Creates a frame from raw pixel data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The pixel data, with rows stored top to bottom.
+    /// * `size` - The size of the frame.
+    /// * `pixel_format` - The pixel format of the data.
+    /// * `stride` - The number of bytes between the starts of two consecutive rows.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidStride`] if `stride` is smaller than a row of pixels,
+    /// [`WgcError::BufferTooSmall`] if `data` cannot hold `size.height` rows, and
+    /// [`WgcError::UnsupportedPixelFormat`] for the YCbCr formats, which are held by
+    /// [`YuvFrame`].
+    pub fn from_raw(
+        data: Vec<u8>,
+        size: FrameSize,
+        pixel_format: PixelFormat,
+        stride: usize,
+    ) -> std::result::Result<Self, WgcError> {
+        check_buffer(pixel_format, size, data.len(), stride)?;
+        Ok(Self {
+            data,
+            size,
+            pixel_format,
+            stride,
+            render_time: None,
+            timestamp: None,
+            sequence: 0,
+            transform: LetterboxTransform::identity(size),
+            alpha_mode: AlphaMode::Premultiplied,
+        })
+    }
+
+    /// Creates a frame from tightly packed pixel data, whose stride is
+    /// `width * bytes_per_pixel`.
+    pub fn from_packed(
+        data: Vec<u8>,
+        size: FrameSize,
+        pixel_format: PixelFormat,
+    ) -> std::result::Result<Self, WgcError> {
+        let stride = size.width as usize * pixel_format.bytes_per_pixel() as usize;
+        Self::from_raw(data, size, pixel_format, stride)
+    }
+
+    /// Sets the time at which the frame was rendered.
+    pub fn with_render_time(mut self, render_time: Instant) -> Self {
+        self.render_time = Some(render_time);
+        self
+    }
+
+    /// Sets the time at which the frame was rendered, as the duration since the system
+    /// booted on the performance-counter [`Clock`].
+    pub fn with_timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets the sequence number of the frame.
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Sets the transform from the source frame to this frame.
+    pub fn with_transform(mut self, transform: LetterboxTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Sets how the alpha channel of the pixel data is interpreted, without changing the
+    /// data. Use [`convert_alpha`](Self::convert_alpha) to convert the data instead.
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Copies the render time, timestamp, sequence number, transform and alpha mode of
+    /// `other`, for frames derived from it.
+    pub(crate) fn with_metadata_of(self, other: &CpuFrame) -> Self {
+        Self {
+            render_time: other.render_time,
+            timestamp: other.timestamp,
+            sequence: other.sequence,
+            transform: other.transform,
+            alpha_mode: other.alpha_mode,
+            ..self
+        }
+    }
+
+    /// Returns the size of the frame.
+    pub fn size(&self) -> FrameSize {
+        self.size
+    }
+
+    /// Returns the pixel format of the frame.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Returns the number of bytes between the starts of two consecutive rows.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the number of bytes of pixel data in a row, excluding padding.
+    pub fn row_bytes(&self) -> usize {
+        self.size.width as usize * self.pixel_format.bytes_per_pixel() as usize
+    }
+
+    /// Returns the time at which the frame was rendered, if known.
+    pub fn render_time(&self) -> Option<Instant> {
+        self.render_time
+    }
+
+    /// Returns the time at which the frame was rendered as the duration since the system
+    /// booted, if known. See [`CaptureFrame::timestamp`].
+    pub fn timestamp(&self) -> Option<Duration> {
+        self.timestamp
+    }
+
+    /// Returns the sequence number of the frame.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the transform from the frame it was read from to this frame.
+    ///
+    /// Use it to map coordinates on this frame back to the source frame. Frames created
+    /// from raw data have an identity transform.
+    pub fn transform(&self) -> LetterboxTransform {
+        self.transform
+    }
+
+    /// Returns how the alpha channel of the pixel data is interpreted.
+    ///
+    /// Frames created from raw data are [`AlphaMode::Premultiplied`], like captured frames,
+    /// unless set otherwise with [`with_alpha_mode`](Self::with_alpha_mode).
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Returns the raw pixel data, including any row padding.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the raw pixel data mutably, including any row padding.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+
+    /// Consumes the frame and returns its raw pixel data, including any row padding.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the pixels of row `y`, excluding padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: u32) -> &[u8] {
+        assert!(y < self.size.height, "Row {} is out of bounds", y);
+        let start = y as usize * self.stride;
+        &self.data[start..start + self.row_bytes()]
+    }
+
+    /// Returns the pixels of row `y` mutably, excluding padding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is out of bounds.
+    pub fn row_mut(&mut self, y: u32) -> &mut [u8] {
+        assert!(y < self.size.height, "Row {} is out of bounds", y);
+        let start = y as usize * self.stride;
+        let row_bytes = self.row_bytes();
+        &mut self.data[start..start + row_bytes]
+    }
+
+    /// Returns an iterator over the rows of the frame, top to bottom, excluding padding.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let row_bytes = self.row_bytes();
+        (0..self.size.height as usize)
+            .map(move |y| &self.data[y * self.stride..y * self.stride + row_bytes])
+    }
+
+    /// Returns the bytes of the pixel at `(x, y)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is out of bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> &[u8] {
+        assert!(x < self.size.width, "Column {} is out of bounds", x);
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel() as usize;
+        let start = x as usize * bytes_per_pixel;
+        &self.row(y)[start..start + bytes_per_pixel]
+    }
+
+    /// Returns the bytes of the pixel at `(x, y)` mutably.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `(x, y)` is out of bounds.
+    pub fn pixel_mut(&mut self, x: u32, y: u32) -> &mut [u8] {
+        assert!(x < self.size.width, "Column {} is out of bounds", x);
+        let bytes_per_pixel = self.pixel_format.bytes_per_pixel() as usize;
+        let start = x as usize * bytes_per_pixel;
+        &mut self.row_mut(y)[start..start + bytes_per_pixel]
+    }
+
+    /// Returns the frame with its rows tightly packed, removing any padding.
+    pub fn into_packed(self) -> Self {
+        let row_bytes = self.row_bytes();
+        if self.stride == row_bytes {
+            return self;
+        }
+        let data = self.rows().flatten().copied().collect();
+        Self {
+            data,
+            stride: row_bytes,
+            ..self
+        }
+    }
+}
@@ -1,7 +1,28 @@
+use crate::*;
+
 #[derive(Debug, thiserror::Error, Clone)]
 pub enum WgcError {
+    #[cfg(windows)]
     #[error("Wgc: {0}")]
     WindowsError(#[from] windows::core::Error),
     #[error("No item selected")]
     NoItemSelected,
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedPixelFormat(PixelFormat),
+    #[error("Buffer too small: expected at least {expected} bytes, got {actual}")]
+    BufferTooSmall { expected: usize, actual: usize },
+    #[error("Invalid stride: {stride} is smaller than the row size {row_bytes}")]
+    InvalidStride { stride: usize, row_bytes: usize },
+    #[error("Crop region {crop:?} does not overlap the frame of size {size:?}")]
+    InvalidCrop { crop: Rect, size: FrameSize },
+    #[error("Invalid tiles of size {tile_size:?} with an overlap of {overlap} pixels")]
+    InvalidTileOptions { tile_size: FrameSize, overlap: u32 },
+    #[error("I/O error: {0}")]
+    Io(std::sync::Arc<std::io::Error>),
+}
+
+impl From<std::io::Error> for WgcError {
+    fn from(err: std::io::Error) -> Self {
+        WgcError::Io(std::sync::Arc::new(err))
+    }
 }
@@ -0,0 +1,328 @@
+//! A Prometheus/OpenMetrics endpoint for capture metrics.
+//!
+//! Available with the `prometheus` feature. [`MetricsExporter`] serves the metrics of a
+//! [`CaptureMetrics`] collector in the OpenMetrics text format on `GET /metrics`, from a
+//! background thread, so that unattended capture agents can be scraped. It is a minimal
+//! HTTP/1.1 server meant for a local or lab network, not for exposure to the internet.
+
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::*;
+
+/// The content type of the OpenMetrics text format.
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The quantiles of the latency and read-duration summaries.
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// The interval at which the server checks whether it was shut down.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The time a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The largest request accepted, headers included.
+const MAX_REQUEST_LEN: usize = 8192;
+
+/// Writes the header of a metric family.
+fn write_family(out: &mut String, name: &str, kind: &str, unit: Option<&str>, help: &str) {
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    if let Some(unit) = unit {
+        let _ = writeln!(out, "# UNIT {name} {unit}");
+    }
+    let _ = writeln!(out, "# HELP {name} {help}");
+}
+
+/// Writes a summary of the durations of `histogram`, in seconds, by quantile.
+fn write_summary(out: &mut String, name: &str, help: &str, histogram: &DurationHistogram) {
+    write_family(out, name, "summary", Some("seconds"), help);
+    for quantile in QUANTILES {
+        if let Some(value) = histogram.percentile(quantile * 100.0) {
+            let _ = writeln!(
+                out,
+                "{name}{{quantile=\"{quantile}\"}} {}",
+                value.as_secs_f64()
+            );
+        }
+    }
+}
+
+impl CaptureMetrics {
+    /// Renders the metrics in the OpenMetrics text format, as served by
+    /// [`MetricsExporter`].
+    ///
+    /// The frame counters are totals since the collector was created, while the frame
+    /// rate, queue depth and the quantiles of the latency and read duration cover the
+    /// rolling window.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use wgc::*;
+    ///
+    /// let clock = FakeClock::new(Duration::from_secs(10));
+    /// let metrics = CaptureMetrics::new(MetricsOptions::default(), clock.clone());
+    /// metrics.record_frame_size(FrameSize::new(1920, 1080));
+    /// metrics.record_delivery(Duration::from_millis(9_990), 1);
+    ///
+    /// let text = metrics.to_openmetrics();
+    /// assert!(text.contains("wgc_frames_delivered_total 1\n"));
+    /// assert!(text.contains("wgc_frame_width_pixels 1920\n"));
+    /// assert!(text.contains("wgc_capture_latency_seconds{quantile=\"0.5\"} 0.01\n"));
+    /// assert!(text.ends_with("# EOF\n"));
+    /// ```
+    pub fn to_openmetrics(&self) -> String {
+        let snapshot = self.snapshot();
+        let totals = self.totals();
+        let mut out = String::new();
+        let counters = [
+            (
+                "wgc_frames_delivered",
+                "Frames delivered by the capture source.",
+                totals.delivered,
+            ),
+            (
+                "wgc_frames_dropped_for_resize",
+                "Frames dropped while the frame pool was recreated for a new content size.",
+                totals.dropped_for_resize,
+            ),
+            (
+                "wgc_frames_skipped_for_overflow",
+                "Frames known to be skipped because the frame queue was full.",
+                totals.skipped_for_overflow,
+            ),
+            (
+                "wgc_frames_estimated_skipped_for_overflow",
+                "Frames estimated from timestamp gaps to be skipped because the frame queue was full.",
+                totals.estimated_skipped_for_overflow,
+            ),
+            (
+                "wgc_item_closed",
+                "Times the captured item was closed.",
+                self.item_closed(),
+            ),
+        ];
+        for (name, help, value) in counters {
+            write_family(&mut out, name, "counter", None, help);
+            let _ = writeln!(out, "{name}_total {value}");
+        }
+
+        write_family(
+            &mut out,
+            "wgc_frame_rate",
+            "gauge",
+            None,
+            "Delivered frames per second over the metrics window.",
+        );
+        let _ = writeln!(out, "wgc_frame_rate {}", snapshot.frame_rate);
+        write_family(
+            &mut out,
+            "wgc_queue_depth",
+            "gauge",
+            None,
+            "Mean number of queued frames when a frame was taken, over the metrics window.",
+        );
+        let _ = writeln!(out, "wgc_queue_depth {}", snapshot.mean_queue_depth);
+
+        let frame_size = self.frame_size();
+        for (name, help, value) in [
+            (
+                "wgc_frame_width_pixels",
+                "Width of the frames currently delivered.",
+                frame_size.map(|size| size.width),
+            ),
+            (
+                "wgc_frame_height_pixels",
+                "Height of the frames currently delivered.",
+                frame_size.map(|size| size.height),
+            ),
+        ] {
+            write_family(&mut out, name, "gauge", Some("pixels"), help);
+            if let Some(value) = value {
+                let _ = writeln!(out, "{name} {value}");
+            }
+        }
+
+        write_summary(
+            &mut out,
+            "wgc_capture_latency_seconds",
+            "Latency from the timestamp of a frame to its delivery, over the metrics window.",
+            &snapshot.latency,
+        );
+        write_summary(
+            &mut out,
+            "wgc_read_duration_seconds",
+            "Duration of the reads of the pixels of a frame, over the metrics window.",
+            &snapshot.read_duration,
+        );
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+/// Serves the metrics of a capture source on `GET /metrics`, in the OpenMetrics text
+/// format.
+///
+/// The server runs on a background thread until the exporter is dropped. Connections are
+/// handled one at a time, which is enough for periodic scrapes.
+///
+/// # Example
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let mut source = SyntheticSource::new(SyntheticItem::default(), WgcSettings::default())?;
+/// let exporter = MetricsExporter::serve("127.0.0.1:0", source.metrics_collector())?;
+/// source.next().unwrap()?;
+///
+/// let mut stream = TcpStream::connect(exporter.local_addr())?;
+/// stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+/// assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+/// assert!(response.contains("wgc_frames_delivered_total 1\n"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MetricsExporter {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsExporter {
+    /// Binds `addr` and starts serving `metrics`.
+    ///
+    /// Pass port 0 to let the system pick a free port, and read it back with
+    /// [`local_addr`](Self::local_addr).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::Io`] if the address cannot be bound.
+    pub fn serve(
+        addr: impl ToSocketAddrs,
+        metrics: Arc<CaptureMetrics>,
+    ) -> std::result::Result<Self, WgcError> {
+        let listener = TcpListener::bind(addr)?;
+        // Accepting without blocking lets the thread notice the shutdown.
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("wgc-metrics-exporter".into())
+            .spawn({
+                let shutdown = shutdown.clone();
+                move || serve_connections(listener, &metrics, &shutdown)
+            })?;
+        debug!("Serving capture metrics on http://{}/metrics", local_addr);
+        Ok(Self {
+            local_addr,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the address the exporter listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsExporter {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn serve_connections(listener: TcpListener, metrics: &CaptureMetrics, shutdown: &AtomicBool) {
+    while !shutdown.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(_err) = handle_connection(stream, metrics) {
+                    debug!("Metrics request failed: {}", _err);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            Err(_err) => {
+                debug!("Failed to accept a metrics connection: {}", _err);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+/// Reads one request and writes its response, then closes the connection.
+fn handle_connection(mut stream: TcpStream, metrics: &CaptureMetrics) -> std::io::Result<()> {
+    // Accepted sockets may inherit the non-blocking mode of the listener
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut chunk = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&chunk[..read]);
+        if request.len() > MAX_REQUEST_LEN {
+            return respond(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                "text/plain",
+                "",
+            );
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    match (method, path) {
+        ("GET", "/metrics") => respond(
+            &mut stream,
+            "200 OK",
+            OPENMETRICS_CONTENT_TYPE,
+            &metrics.to_openmetrics(),
+        ),
+        (_, "/metrics") => respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET is allowed\n",
+        ),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found\n"),
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
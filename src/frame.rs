@@ -1,8 +1,10 @@
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+pub use crate::common::FrameSize;
 use crate::*;
 use windows::{
-    Graphics::{Capture::Direct3D11CaptureFrame, SizeInt32},
+    Graphics::Capture::Direct3D11CaptureFrame,
     Win32::{
         Graphics::{
             Direct2D::{
@@ -15,12 +17,16 @@ use windows::{
     },
     core::Interface,
 };
+use windows_numerics::Matrix3x2;
 #[derive(Debug)]
 pub struct Frame {
     frame: Direct3D11CaptureFrame,
     d2d1_context: ID2D1DeviceContext,
     pixel_format: PixelFormat,
     wgc_settings: WgcSettings,
+    sequence: u64,
+    counters: FrameCounters,
+    metrics: Option<Arc<CaptureMetrics>>,
 }
 impl Frame {
     pub fn new(
@@ -34,15 +40,62 @@ impl Frame {
             d2d1_context,
             pixel_format,
             wgc_settings,
+            sequence: 0,
+            counters: FrameCounters::default(),
+            metrics: None,
         }
     }
 
+    pub(crate) fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub(crate) fn with_counters(mut self, counters: FrameCounters) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Arc<CaptureMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Runs a read of the frame, recording its duration in the metrics of the session
+    fn timed<T>(
+        &self,
+        read: impl FnOnce() -> std::result::Result<T, WgcError>,
+    ) -> std::result::Result<T, WgcError> {
+        match &self.metrics {
+            Some(metrics) => metrics.time_read(read),
+            None => read(),
+        }
+    }
+
+    /// Returns the sequence number of the frame, that is, the number of frames delivered
+    /// by the capture session before this one.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the counters of the capture session as of the delivery of this frame, see
+    /// [`Wgc::counters`].
+    pub fn counters(&self) -> FrameCounters {
+        self.counters
+    }
+
     /// Returns the time at which the frame was rendered
     pub fn render_time(&self) -> std::result::Result<Instant, WgcError> {
-        let frame_delay_since_boot =
-            Duration::from_nanos(self.frame.SystemRelativeTime()?.Duration as u64 * 100);
-        let system_uptime = elapsed_since_system_boot();
-        Ok(Instant::now() - system_uptime + frame_delay_since_boot)
+        Ok(QpcClock.instant_at(self.timestamp()?))
+    }
+
+    /// Returns the time at which the frame was rendered, as the duration since the system
+    /// booted on the performance-counter clock
+    ///
+    /// This is the `SystemRelativeTime` of the frame, without conversion.
+    pub fn timestamp(&self) -> std::result::Result<Duration, WgcError> {
+        let ticks = self.frame.SystemRelativeTime()?.Duration;
+        Ok(Duration::from_nanos(ticks.max(0) as u64 * 100))
     }
 
     /// Returns the size of the frame
@@ -51,90 +104,255 @@ impl Frame {
         Ok(size.into())
     }
 
+    /// Returns the transform applied by [`read_pixels`](Self::read_pixels) for the given
+    /// desired size, according to [`WgcSettings::crop`], [`WgcSettings::orientation`] and
+    /// [`WgcSettings::resize_mode`].
+    ///
+    /// Its [`output_size`](LetterboxTransform::output_size) is the size of the pixel data.
+    pub fn output_transform(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<LetterboxTransform, WgcError> {
+        read_transform(self.size()?, desired_size, &self.wgc_settings)
+    }
+
     /// Reads the pixel data from the capture frame and returns it as a byte vector.
     ///
     /// If a `desired_size` is provided and differs from the frame's native size,
-    /// the image will be scaled to fit within the desired dimensions while maintaining
-    /// aspect ratio. The scaled image will be letterboxed (centered with gray borders)
-    /// to fill the desired size.
+    /// the image will be resized according to [`WgcSettings::resize_mode`]. By default,
+    /// it is scaled to fit within the desired dimensions while maintaining
+    /// aspect ratio, and letterboxed (centered with 50% gray borders) to fill the desired size.
     ///
     /// If `desired_size` is `None`, the frame's native size will be used.
     ///
+    /// If [`WgcSettings::crop`] is set, the frame is cropped to that region before it is
+    /// resized, and the region's size takes the place of the frame's native size. The
+    /// cropped frame is reoriented by [`WgcSettings::orientation`] before it is resized.
+    ///
     /// # Arguments
     ///
-    /// * `desired_size` - Optional target size for the output image. If specified, the frame will be scaled to fit within these dimensions.
+    /// * `desired_size` - Optional target size for the output image. If specified, the frame will be resized to these dimensions.
     ///
     /// # Returns
     ///
-    /// A `Vec<u8>` containing the raw pixel data in the frame's pixel format.
-    /// The buffer size is `width * height * bytes_per_pixel`.
+    /// A `Vec<u8>` containing the raw pixel data in the frame's pixel format, with the
+    /// alpha mode of [`WgcSettings::alpha_mode`].
+    /// The buffer size is the [`buffer_len`](PixelFormat::buffer_len) of the
+    /// [`output_size`](LetterboxTransform::output_size) of
+    /// [`output_transform`](Self::output_transform), which is
+    /// `width * height * bytes_per_pixel` for formats other than YCbCr.
     ///
     pub fn read_pixels(
         &self,
         desired_size: Option<FrameSize>,
     ) -> std::result::Result<Vec<u8>, WgcError> {
-        let frame_size = self.size()?;
-        let desired_size = desired_size.unwrap_or(frame_size);
-        let mut buffer = vec![
-            0;
-            (desired_size.width * desired_size.height * self.pixel_format.bytes_per_pixel())
-                as usize
-        ];
+        self.timed(|| {
+            let spec = OutputSpec::from_settings(&self.wgc_settings, desired_size);
+            let frame_bitmap = self.create_bitmap_from_frame()?;
+            Ok(self.read_output(&frame_bitmap, &spec)?.pixels)
+        })
+    }
+
+    /// Reads several outputs from the frame, one for each spec, in order.
+    ///
+    /// The frame bitmap is created once and every output is drawn from it. Each output is
+    /// cropped, reoriented, resized and converted as described by its [`OutputSpec`],
+    /// regardless of the settings of the capture session; the formats are converted from
+    /// the surface format of [`WgcSettings::pixel_format`].
+    ///
+    /// # Errors
+    ///
+    /// Fails as a whole if any output fails, for example with [`WgcError::InvalidCrop`] or
+    /// [`WgcError::UnsupportedPixelFormat`].
+    pub fn read_outputs(
+        &self,
+        specs: &[OutputSpec],
+    ) -> std::result::Result<Vec<FrameOutput>, WgcError> {
+        self.timed(|| {
+            let frame_bitmap = self.create_bitmap_from_frame()?;
+            specs
+                .iter()
+                .map(|spec| self.read_output(&frame_bitmap, spec))
+                .collect()
+        })
+    }
+
+    /// Reads one output from the bitmap of the frame.
+    fn read_output(
+        &self,
+        frame_bitmap: &ID2D1Bitmap1,
+        spec: &OutputSpec,
+    ) -> std::result::Result<FrameOutput, WgcError> {
+        let transform = spec.transform(self.size()?)?;
+        let output_size = transform.output_size;
+        let pixels = if self.needs_conversion(spec) {
+            // Read a copy of the surface and convert the whole frame on the CPU
+            let surface_format = self.pixel_format.surface_format();
+            let stride = (output_size.width * surface_format.bytes_per_pixel()) as usize;
+            let mut buffer = vec![0; stride * output_size.height as usize];
+            self.read_surface_into(
+                frame_bitmap,
+                spec,
+                &transform,
+                &mut buffer,
+                stride,
+                surface_format,
+            )?;
+            let surface = CpuFrame::from_packed(buffer, output_size, surface_format)?;
+            convert::into_pixels(surface, spec.pixel_format, spec.alpha_mode)?
+        } else {
+            let stride = (output_size.width * spec.pixel_format.bytes_per_pixel()) as usize;
+            let mut buffer = vec![0; stride * output_size.height as usize];
+            self.read_surface_into(
+                frame_bitmap,
+                spec,
+                &transform,
+                &mut buffer,
+                stride,
+                spec.pixel_format,
+            )?;
+            buffer
+        };
+        Ok(FrameOutput {
+            pixels,
+            pixel_format: spec.pixel_format,
+            alpha_mode: spec.alpha_mode,
+            transform,
+        })
+    }
+
+    /// Reads the pixel data from the capture frame into `buffer`, writing rows `stride`
+    /// bytes apart.
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels).
+    /// With [`AlphaMode::Premultiplied`], the formats that have a
+    /// [`RowKernel`](crate::kernels::RowKernel) from their surface format (all but
+    /// [`PixelFormat::R10G10B10A2`] and the YCbCr formats) are converted row by row
+    /// straight from the mapped bitmap into the buffer, without allocating on the CPU.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidStride`] if `stride` is smaller than a row of pixels,
+    /// [`WgcError::BufferTooSmall`] if `buffer` cannot hold all rows of the output, and
+    /// [`WgcError::UnsupportedPixelFormat`] for the YCbCr formats.
+    pub fn read_pixels_into(
+        &self,
+        desired_size: Option<FrameSize>,
+        buffer: &mut [u8],
+        stride: usize,
+    ) -> std::result::Result<(), WgcError> {
+        self.timed(|| {
+            let spec = OutputSpec::from_settings(&self.wgc_settings, desired_size);
+            let transform = spec.transform(self.size()?)?;
+            let output_size = transform.output_size;
+            check_buffer(self.pixel_format, output_size, buffer.len(), stride)?;
+            let frame_bitmap = self.create_bitmap_from_frame()?;
+            if !self.needs_conversion(&spec) {
+                return self.read_surface_into(
+                    &frame_bitmap,
+                    &spec,
+                    &transform,
+                    buffer,
+                    stride,
+                    self.pixel_format,
+                );
+            }
+            let data = self.read_output(&frame_bitmap, &spec)?.pixels;
+            let row_bytes = (output_size.width * self.pixel_format.bytes_per_pixel()) as usize;
+            copy_rows(
+                &data,
+                row_bytes,
+                buffer,
+                stride,
+                row_bytes,
+                output_size.height as usize,
+            );
+            Ok(())
+        })
+    }
+
+    /// Returns `true` if an output is read in the surface format and then converted as a
+    /// whole, rather than converted row by row as it is copied out of the GPU.
+    fn needs_conversion(&self, spec: &OutputSpec) -> bool {
+        // Direct2D reads premultiplied alpha; other modes are converted on the CPU as well
+        spec.alpha_mode != AlphaMode::Premultiplied
+            || kernels::RowKernel::new(self.pixel_format.surface_format(), spec.pixel_format)
+                .is_none()
+    }
 
-        let frame_bitmap = self.create_bitmap_from_frame()?;
+    /// Reads the frame into `buffer`, applying `transform`, and converts its rows from the
+    /// surface format to `pixel_format`.
+    fn read_surface_into(
+        &self,
+        frame_bitmap: &ID2D1Bitmap1,
+        spec: &OutputSpec,
+        transform: &LetterboxTransform,
+        buffer: &mut [u8],
+        stride: usize,
+        pixel_format: PixelFormat,
+    ) -> std::result::Result<(), WgcError> {
+        let kernel = kernels::RowKernel::new(self.pixel_format.surface_format(), pixel_format)
+            .ok_or(WgcError::UnsupportedPixelFormat(pixel_format))?;
+        let frame_size = transform.source_size;
+        let output_size = transform.output_size;
 
-        if frame_size != desired_size {
-            let canvas_bitmap = self.create_canvas_bitmap(desired_size)?;
+        if transform != LetterboxTransform::identity(frame_size) {
+            let canvas_bitmap = self.create_canvas_bitmap(output_size)?;
 
             unsafe {
                 self.d2d1_context.BeginDraw();
                 self.d2d1_context.SetTarget(&canvas_bitmap);
+                // Modes without padding cover the whole canvas, so the color only
+                // matters for `ResizeMode::Fit`
+                let pad_color = spec.resize_mode.pad_color().unwrap_or(Color::TRANSPARENT);
                 let letterbox_color = D2D1_COLOR_F {
-                    r: 0.5,
-                    g: 0.5,
-                    b: 0.5,
-                    a: 1.0,
+                    r: pad_color.r as f32 / 255.0,
+                    g: pad_color.g as f32 / 255.0,
+                    b: pad_color.b as f32 / 255.0,
+                    a: pad_color.a as f32 / 255.0,
                 };
                 self.d2d1_context.Clear(Some(&letterbox_color));
 
-                // Calculate the scale factor to maintain aspect ratio
-                let src_w = frame_size.width as f32;
-                let src_h = frame_size.height as f32;
-                let dst_w = desired_size.width as f32;
-                let dst_h = desired_size.height as f32;
-                let scale = (dst_w / src_w).min(dst_h / src_h);
-
-                // Determine the new dimensions
-                let final_w = src_w * scale;
-                let final_h = src_h * scale;
-
-                let x = (dst_w - final_w) / 2.0;
-                let y = (dst_h - final_h) / 2.0;
-
-                // Create the destination rectangle
-                let dest_rect = D2D_RECT_F {
-                    left: x,
-                    top: y,
-                    right: x + final_w,
-                    bottom: y + final_h,
+                // The region of the frame that is read, drawn in frame coordinates through
+                // the transform, which reorients it and may overflow the canvas in
+                // `ResizeMode::Fill`
+                let region = match spec.crop {
+                    Some(crop) => crop.clamp_to(frame_size)?,
+                    None => frame_size.into(),
+                };
+                let region_rect = D2D_RECT_F {
+                    left: region.x,
+                    top: region.y,
+                    right: region.right(),
+                    bottom: region.bottom(),
                 };
+                let [m11, m12, m21, m22, dx, dy] = transform.matrix();
+                self.d2d1_context.SetTransform(&Matrix3x2 {
+                    M11: m11,
+                    M12: m12,
+                    M21: m21,
+                    M22: m22,
+                    M31: dx,
+                    M32: dy,
+                });
 
                 self.d2d1_context.DrawBitmap(
-                    &frame_bitmap,
-                    Some(&dest_rect),
+                    frame_bitmap,
+                    Some(&region_rect),
                     1.0, // Opacity
-                    self.wgc_settings.frame_interpolation_mode.into(),
-                    None,
+                    spec.frame_interpolation_mode.into(),
+                    Some(&region_rect),
                     None,
                 );
+                self.d2d1_context.SetTransform(&Matrix3x2::identity());
 
                 self.d2d1_context.EndDraw(None, None)?;
             }
-            self.read_pixels_from_bitmap(&mut buffer, desired_size, canvas_bitmap)?;
+            self.read_pixels_from_bitmap(buffer, stride, output_size, &canvas_bitmap, &kernel)?;
         } else {
-            self.read_pixels_from_bitmap(&mut buffer, frame_size, frame_bitmap)?;
+            self.read_pixels_from_bitmap(buffer, stride, frame_size, frame_bitmap, &kernel)?;
         }
-        Ok(buffer)
+        Ok(())
     }
 
     fn create_canvas_bitmap(&self, size: FrameSize) -> std::result::Result<ID2D1Bitmap1, WgcError> {
@@ -144,7 +362,7 @@ impl Frame {
         };
         let bitmap_properties = D2D1_BITMAP_PROPERTIES1 {
             pixelFormat: D2D1_PIXEL_FORMAT {
-                format: self.pixel_format.into(),
+                format: self.pixel_format.surface_format().into(),
                 alphaMode:
                     windows::Win32::Graphics::Direct2D::Common::D2D1_ALPHA_MODE_PREMULTIPLIED,
             },
@@ -161,8 +379,10 @@ impl Frame {
     fn read_pixels_from_bitmap(
         &self,
         buffer: &mut [u8],
+        stride: usize,
         desired_size: FrameSize,
-        bitmap: ID2D1Bitmap1,
+        bitmap: &ID2D1Bitmap1,
+        kernel: &kernels::RowKernel,
     ) -> std::result::Result<(), WgcError> {
         let size = D2D_SIZE_U {
             width: desired_size.width,
@@ -170,7 +390,7 @@ impl Frame {
         };
         let bitmap_properties = D2D1_BITMAP_PROPERTIES1 {
             pixelFormat: D2D1_PIXEL_FORMAT {
-                format: self.pixel_format.into(),
+                format: self.pixel_format.surface_format().into(),
                 alphaMode:
                     windows::Win32::Graphics::Direct2D::Common::D2D1_ALPHA_MODE_PREMULTIPLIED,
             },
@@ -182,19 +402,23 @@ impl Frame {
             self.d2d1_context
                 .CreateBitmap(size, None, 0, &bitmap_properties)
         }?;
-        unsafe { bitmap_cpu_read.CopyFromBitmap(None, &bitmap, None) }?;
+        unsafe { bitmap_cpu_read.CopyFromBitmap(None, bitmap, None) }?;
         let mapped_rect = unsafe {
             bitmap_cpu_read.Map(windows::Win32::Graphics::Direct2D::D2D1_MAP_OPTIONS_READ)
         }?;
         let pitch = mapped_rect.pitch as usize;
         let data_ptr = mapped_rect.bits;
 
-        let row_bytes = (desired_size.width * self.pixel_format.bytes_per_pixel()) as usize;
+        let src_row_bytes =
+            (desired_size.width * kernel.source_format().bytes_per_pixel()) as usize;
+        let row_bytes = (desired_size.width * kernel.target_format().bytes_per_pixel()) as usize;
 
-        for (i, dst_row) in buffer.chunks_exact_mut(row_bytes).enumerate() {
+        // Converting each row as it is copied out of the mapped bitmap saves a pass over
+        // the frame
+        for i in 0..desired_size.height as usize {
             let src_ptr = unsafe { data_ptr.add(i * pitch) };
-            let src_row = unsafe { std::slice::from_raw_parts(src_ptr, row_bytes) };
-            dst_row.copy_from_slice(src_row);
+            let src_row = unsafe { std::slice::from_raw_parts(src_ptr, src_row_bytes) };
+            kernel.run(src_row, &mut buffer[i * stride..i * stride + row_bytes]);
         }
         unsafe { bitmap_cpu_read.Unmap() }?;
         Ok(())
@@ -212,27 +436,77 @@ impl Frame {
     }
 }
 
-impl From<Frame> for Direct3D11CaptureFrame {
-    fn from(frame: Frame) -> Self {
-        frame.frame
+impl CaptureFrame for Frame {
+    fn size(&self) -> std::result::Result<FrameSize, WgcError> {
+        Frame::size(self)
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.wgc_settings.alpha_mode
+    }
+
+    fn render_time(&self) -> std::result::Result<Instant, WgcError> {
+        Frame::render_time(self)
+    }
+
+    fn timestamp(&self) -> std::result::Result<Duration, WgcError> {
+        Frame::timestamp(self)
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn counters(&self) -> FrameCounters {
+        self.counters
+    }
+
+    fn output_transform(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<LetterboxTransform, WgcError> {
+        Frame::output_transform(self, desired_size)
+    }
+
+    fn read_pixels(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<Vec<u8>, WgcError> {
+        Frame::read_pixels(self, desired_size)
+    }
+
+    fn read_outputs(
+        &self,
+        specs: &[OutputSpec],
+    ) -> std::result::Result<Vec<FrameOutput>, WgcError> {
+        Frame::read_outputs(self, specs)
+    }
+
+    fn read_pixels_into(
+        &self,
+        desired_size: Option<FrameSize>,
+        buffer: &mut [u8],
+        stride: usize,
+    ) -> std::result::Result<(), WgcError> {
+        Frame::read_pixels_into(self, desired_size, buffer, stride)
     }
 }
-#[derive(Debug, smart_default::SmartDefault, Clone, Copy, PartialEq, Eq)]
-pub struct FrameSize {
-    #[default(0)]
-    pub width: u32,
-    #[default(0)]
-    pub height: u32,
+
+impl TryFrom<&Frame> for CpuFrame {
+    type Error = WgcError;
+
+    /// Reads the frame at its native size.
+    fn try_from(frame: &Frame) -> std::result::Result<Self, Self::Error> {
+        frame.to_cpu_frame(None)
+    }
 }
 
-impl From<SizeInt32> for FrameSize {
-    fn from(size: SizeInt32) -> Self {
-        let SizeInt32 {
-            Width: width,
-            Height: height,
-        } = size;
-        let width = width.max(0) as u32;
-        let height = height.max(0) as u32;
-        Self { width, height }
+impl From<Frame> for Direct3D11CaptureFrame {
+    fn from(frame: Frame) -> Self {
+        frame.frame
     }
 }
@@ -0,0 +1,241 @@
+//! Constant-frame-rate resampling of capture streams.
+//!
+//! Windows Graphics Capture only delivers a frame when the content changes: a static screen
+//! produces no frames, while a playing video produces one per refresh. Recordings and
+//! training loops usually need a steady rate instead. [`FrameRateResampler`] turns any
+//! stream of frames into one frame per output interval, according to the frame
+//! [`timestamp`](CaptureFrame::timestamp)s, by repeating the last frame across gaps and
+//! dropping surplus frames within an interval.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::*;
+
+/// A frame of a [`FrameRateResampler`] output.
+#[derive(Debug)]
+pub struct ResampledFrame<F> {
+    /// The frame shown in this slot. Duplicates share the frame of the slot they repeat.
+    pub frame: Arc<F>,
+    /// The index of the slot, counting from 0 at the first frame.
+    pub slot: u64,
+    /// The time of the slot, as the duration since the system booted on the clock of the
+    /// frame timestamps.
+    pub timestamp: Duration,
+    /// Whether the frame was already shown in an earlier slot.
+    pub duplicate: bool,
+}
+
+impl<F> Clone for ResampledFrame<F> {
+    fn clone(&self) -> Self {
+        Self {
+            frame: self.frame.clone(),
+            slot: self.slot,
+            timestamp: self.timestamp,
+            duplicate: self.duplicate,
+        }
+    }
+}
+
+/// Resamples a stream of frames to a constant frame rate.
+///
+/// The output slots are spaced by a fixed interval, starting at the timestamp of the first
+/// frame. Each slot shows the latest frame rendered before the midpoint to the next slot,
+/// so that frames delivered slightly late still land in their slot. A slot without a new
+/// frame repeats the previous one, and a frame replaced by a later one in the same slot is
+/// dropped.
+///
+/// A slot is only emitted once a frame past it arrives, or the input ends, so the output
+/// lags the input by up to one frame. The output ends with the slot of the last frame.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// // Ten frames at 10 fps, with deterministic timestamps.
+/// let clock = FakeClock::new(Duration::from_secs(60));
+/// let item = SyntheticItem {
+///     frame_interval: Duration::from_millis(100),
+///     frame_count: Some(10),
+///     ..Default::default()
+/// };
+/// let source = SyntheticSource::new(item.clone(), WgcSettings::default())?.with_clock(clock.clone());
+///
+/// // Upsampled to 20 fps, every frame is shown twice, except the last.
+/// let mut resampler = FrameRateResampler::new(source, 20.0);
+/// let slots = resampler.by_ref().collect::<Result<Vec<_>, _>>()?;
+/// let indices: Vec<u64> = slots.iter().map(|slot| slot.frame.index()).collect();
+/// assert_eq!(indices[..5], [0, 0, 1, 1, 2]);
+/// assert_eq!(slots.len(), 19);
+/// assert_eq!(slots[3].timestamp, Duration::from_millis(60_150));
+/// assert!(slots[3].duplicate);
+/// assert_eq!((resampler.duplicated(), resampler.dropped()), (9, 0));
+///
+/// // Downsampled to 4 fps, the surplus frames are dropped.
+/// let source = SyntheticSource::new(item.clone(), WgcSettings::default())?.with_clock(clock.clone());
+/// let mut resampler = FrameRateResampler::new(source, 4.0);
+/// let slots = resampler.by_ref().collect::<Result<Vec<_>, _>>()?;
+/// let indices: Vec<u64> = slots.iter().map(|slot| slot.frame.index()).collect();
+/// assert_eq!(indices, [1, 3, 6, 8, 9]);
+/// assert_eq!((resampler.emitted(), resampler.duplicated(), resampler.dropped()), (5, 0, 5));
+///
+/// // At the same rate, the gap left by a frame dropped on resize is filled.
+/// let item = SyntheticItem { size_changes: vec![(2, FrameSize::new(320, 240))], ..item };
+/// let source = SyntheticSource::new(item, WgcSettings::default())?.with_clock(clock);
+/// let mut resampler = FrameRateResampler::new(source, 10.0);
+/// let slots = resampler.by_ref().collect::<Result<Vec<_>, _>>()?;
+/// let indices: Vec<u64> = slots.iter().map(|slot| slot.frame.index()).collect();
+/// assert_eq!(indices[..4], [0, 1, 1, 3]);
+/// assert_eq!((resampler.duplicated(), resampler.dropped()), (1, 0));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FrameRateResampler<I, F> {
+    frames: I,
+    interval: Duration,
+    start: Option<Duration>,
+    slot: u64,
+    current: Option<Arc<F>>,
+    current_shown: bool,
+    pending: Option<(F, Duration)>,
+    exhausted: bool,
+    emitted: u64,
+    duplicated: u64,
+    dropped: u64,
+}
+
+impl<I, F> FrameRateResampler<I, F>
+where
+    I: Iterator<Item = std::result::Result<F, WgcError>>,
+    F: CaptureFrame,
+{
+    /// Resamples `frames` to `frame_rate` frames per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_rate` is not positive and finite.
+    pub fn new(frames: I, frame_rate: f64) -> Self {
+        assert!(
+            frame_rate.is_finite() && frame_rate > 0.0,
+            "Frame rate must be positive"
+        );
+        Self::with_interval(frames, Duration::from_secs_f64(1.0 / frame_rate))
+    }
+
+    /// Resamples `frames` to one frame every `interval`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_interval(frames: I, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "Frame interval must be greater than 0");
+        Self {
+            frames,
+            interval,
+            start: None,
+            slot: 0,
+            current: None,
+            current_shown: false,
+            pending: None,
+            exhausted: false,
+            emitted: 0,
+            duplicated: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the interval between two output frames.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the number of frames emitted so far, duplicates included.
+    pub fn emitted(&self) -> u64 {
+        self.emitted
+    }
+
+    /// Returns the number of emitted frames that repeat an earlier frame.
+    pub fn duplicated(&self) -> u64 {
+        self.duplicated
+    }
+
+    /// Returns the number of input frames that were never emitted, because a later frame
+    /// replaced them in their slot.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Returns the underlying iterator, for example to read the counters of its source.
+    pub fn get_ref(&self) -> &I {
+        &self.frames
+    }
+
+    /// Returns the time of the given slot.
+    fn slot_time(&self, start: Duration, slot: u64) -> Duration {
+        start + Duration::from_nanos((self.interval.as_nanos() * slot as u128) as u64)
+    }
+}
+
+impl<I, F> Iterator for FrameRateResampler<I, F>
+where
+    I: Iterator<Item = std::result::Result<F, WgcError>>,
+    F: CaptureFrame,
+{
+    type Item = std::result::Result<ResampledFrame<F>, WgcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Takes every frame due for this slot, and reads ahead the first frame past it.
+        loop {
+            if self.pending.is_none() && !self.exhausted {
+                match self.frames.next() {
+                    Some(Ok(frame)) => match frame.timestamp() {
+                        Ok(timestamp) => self.pending = Some((frame, timestamp)),
+                        Err(err) => return Some(Err(err)),
+                    },
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => self.exhausted = true,
+                }
+            }
+            let Some((_, timestamp)) = &self.pending else {
+                break;
+            };
+            let start = *self.start.get_or_insert(*timestamp);
+            let limit = self.slot_time(start, self.slot) + self.interval / 2;
+            if *timestamp >= limit {
+                break;
+            }
+            let (frame, _) = self.pending.take().unwrap();
+            if self.current.is_some() && !self.current_shown {
+                trace!("Frame dropped by the resampler in slot {}", self.slot);
+                self.dropped += 1;
+            }
+            self.current = Some(Arc::new(frame));
+            self.current_shown = false;
+        }
+
+        let frame = self.current.clone()?;
+        if self.current_shown && self.pending.is_none() {
+            // The input has ended and its last frame was shown.
+            return None;
+        }
+        let duplicate = self.current_shown;
+        if duplicate {
+            self.duplicated += 1;
+        }
+        let slot = self.slot;
+        let timestamp = self.slot_time(self.start?, slot);
+        self.current_shown = true;
+        self.slot += 1;
+        self.emitted += 1;
+        Some(Ok(ResampledFrame {
+            frame,
+            slot,
+            timestamp,
+            duplicate,
+        }))
+    }
+}
@@ -0,0 +1,735 @@
+//! Geometry shared by the GPU and CPU read paths.
+//!
+//! When a frame is scaled and letterboxed, [`LetterboxTransform`] records how pixels of the
+//! capture item map to pixels of the output, so that results computed on the output (for
+//! example, object detections) can be mapped back to screen coordinates.
+
+use crate::*;
+
+/// A point, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    /// Creates a new point.
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// An axis-aligned rectangle, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    /// The left edge.
+    pub x: f32,
+    /// The top edge.
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    /// Creates a new rectangle.
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the right edge of the rectangle.
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    /// Returns the bottom edge of the rectangle.
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Returns `true` if the rectangle has no area.
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    /// Returns the overlapping area of two rectangles, or `None` if they do not overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let rect = Rect::new(
+            x,
+            y,
+            self.right().min(other.right()) - x,
+            self.bottom().min(other.bottom()) - y,
+        );
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// Returns the whole pixels of a frame of `size` covered by the rectangle.
+    ///
+    /// The edges are rounded outwards to whole pixels, and the parts of the rectangle
+    /// outside the frame are dropped, so a region stays usable when the capture item
+    /// shrinks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidCrop`] if the rectangle is not finite or does not overlap
+    /// the frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// let size = FrameSize::new(100, 50);
+    /// let region = Rect::new(10.5, -5.0, 20.0, 100.0).clamp_to(size).unwrap();
+    /// assert_eq!(region, Rect::new(10.0, 0.0, 21.0, 50.0));
+    ///
+    /// assert!(Rect::new(100.0, 0.0, 10.0, 10.0).clamp_to(size).is_err());
+    /// assert!(Rect::new(0.0, 0.0, f32::NAN, 10.0).clamp_to(size).is_err());
+    /// ```
+    pub fn clamp_to(&self, size: FrameSize) -> std::result::Result<Rect, WgcError> {
+        let invalid = WgcError::InvalidCrop { crop: *self, size };
+        if ![self.x, self.y, self.width, self.height]
+            .iter()
+            .all(|value| value.is_finite())
+        {
+            return Err(invalid);
+        }
+        let left = self.x.floor().clamp(0.0, size.width as f32);
+        let top = self.y.floor().clamp(0.0, size.height as f32);
+        let right = self.right().ceil().clamp(0.0, size.width as f32);
+        let bottom = self.bottom().ceil().clamp(0.0, size.height as f32);
+        let region = Rect::new(left, top, right - left, bottom - top);
+        if region.is_empty() {
+            return Err(invalid);
+        }
+        Ok(region)
+    }
+}
+
+impl From<FrameSize> for Rect {
+    /// Returns a rectangle of the given size at the origin.
+    fn from(size: FrameSize) -> Self {
+        Self::new(0.0, 0.0, size.width as f32, size.height as f32)
+    }
+}
+
+/// Specifies where content is placed within a larger area.
+///
+/// Used by [`ResizeMode::Fit`] to position the scaled frame within the padding, and by
+/// [`ResizeMode::Fill`] to choose which part of the frame is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Alignment {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Alignment {
+    /// Returns the horizontal and vertical position of the content within the free space,
+    /// from `0.0` (left or top) to `1.0` (right or bottom).
+    pub fn factors(&self) -> (f32, f32) {
+        match self {
+            Alignment::TopLeft => (0.0, 0.0),
+            Alignment::Top => (0.5, 0.0),
+            Alignment::TopRight => (1.0, 0.0),
+            Alignment::Left => (0.0, 0.5),
+            Alignment::Center => (0.5, 0.5),
+            Alignment::Right => (1.0, 0.5),
+            Alignment::BottomLeft => (0.0, 1.0),
+            Alignment::Bottom => (0.5, 1.0),
+            Alignment::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// A rotation or reflection of a frame, in steps of 90 degrees.
+///
+/// Pixels are moved without resampling. Rotations by 90 and 270 degrees, and the two
+/// transpositions, swap the width and height of the frame.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// // A landscape monitor mounted in portrait.
+/// let size = FrameSize::new(1920, 1080);
+/// let orientation = Orientation::Rotate90;
+/// assert_eq!(orientation.output_size(size), FrameSize::new(1080, 1920));
+///
+/// // The top-left corner ends up at the top right, and maps back.
+/// let corner = orientation.map_point(Point::new(0.0, 0.0), size);
+/// assert_eq!(corner, Point::new(1080.0, 0.0));
+/// assert_eq!(orientation.unmap_point(corner, size), Point::new(0.0, 0.0));
+///
+/// assert_eq!(orientation.then(Orientation::Rotate270), Orientation::Identity);
+/// assert_eq!(orientation.then(Orientation::FlipHorizontal), Orientation::Transpose);
+/// assert_eq!(orientation.inverse(), Orientation::Rotate270);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    /// The frame is unchanged.
+    #[default]
+    Identity,
+    /// Rotated 90 degrees clockwise.
+    Rotate90,
+    /// Rotated 180 degrees.
+    Rotate180,
+    /// Rotated 90 degrees counterclockwise.
+    Rotate270,
+    /// Mirrored left to right.
+    FlipHorizontal,
+    /// Mirrored top to bottom.
+    FlipVertical,
+    /// Mirrored across the diagonal through the top-left corner, so that rows become
+    /// columns.
+    Transpose,
+    /// Mirrored across the diagonal through the top-right corner.
+    Transverse,
+}
+
+impl Orientation {
+    /// Returns whether the axes are swapped, then whether the horizontal and vertical axes
+    /// of the result are reversed.
+    pub(crate) fn flags(&self) -> (bool, bool, bool) {
+        match self {
+            Orientation::Identity => (false, false, false),
+            Orientation::Rotate90 => (true, true, false),
+            Orientation::Rotate180 => (false, true, true),
+            Orientation::Rotate270 => (true, false, true),
+            Orientation::FlipHorizontal => (false, true, false),
+            Orientation::FlipVertical => (false, false, true),
+            Orientation::Transpose => (true, false, false),
+            Orientation::Transverse => (true, true, true),
+        }
+    }
+
+    fn from_flags(flags: (bool, bool, bool)) -> Self {
+        match flags {
+            (false, false, false) => Orientation::Identity,
+            (true, true, false) => Orientation::Rotate90,
+            (false, true, true) => Orientation::Rotate180,
+            (true, false, true) => Orientation::Rotate270,
+            (false, true, false) => Orientation::FlipHorizontal,
+            (false, false, true) => Orientation::FlipVertical,
+            (true, false, false) => Orientation::Transpose,
+            (true, true, true) => Orientation::Transverse,
+        }
+    }
+
+    /// Returns `true` if the orientation swaps the width and height of a frame.
+    pub fn swaps_axes(&self) -> bool {
+        self.flags().0
+    }
+
+    /// Returns the size of a frame of `size` after it is reoriented.
+    pub fn output_size(&self, size: FrameSize) -> FrameSize {
+        if self.swaps_axes() {
+            FrameSize::new(size.height, size.width)
+        } else {
+            size
+        }
+    }
+
+    /// Maps a point of a frame of `size` to the reoriented frame.
+    pub fn map_point(&self, point: Point, size: FrameSize) -> Point {
+        let (swap, flip_x, flip_y) = self.flags();
+        let output = self.output_size(size);
+        let (x, y) = if swap {
+            (point.y, point.x)
+        } else {
+            (point.x, point.y)
+        };
+        Point::new(
+            if flip_x { output.width as f32 - x } else { x },
+            if flip_y { output.height as f32 - y } else { y },
+        )
+    }
+
+    /// Maps a point of the reoriented frame back to the frame of `size`.
+    pub fn unmap_point(&self, point: Point, size: FrameSize) -> Point {
+        self.inverse().map_point(point, self.output_size(size))
+    }
+
+    /// Returns the orientation that undoes this one.
+    pub fn inverse(&self) -> Self {
+        let (swap, flip_x, flip_y) = self.flags();
+        if swap {
+            Self::from_flags((swap, flip_y, flip_x))
+        } else {
+            *self
+        }
+    }
+
+    /// Returns the orientation that applies `self`, then `next`.
+    pub fn then(&self, next: Orientation) -> Self {
+        let (swap, flip_x, flip_y) = self.flags();
+        let (next_swap, next_flip_x, next_flip_y) = next.flags();
+        // Reversing an axis before swapping reverses the other axis after it.
+        let (flip_x, flip_y) = if next_swap {
+            (flip_y, flip_x)
+        } else {
+            (flip_x, flip_y)
+        };
+        Self::from_flags((
+            swap != next_swap,
+            flip_x != next_flip_x,
+            flip_y != next_flip_y,
+        ))
+    }
+}
+
+/// Maps coordinates of a source frame to coordinates of a scaled output frame.
+///
+/// A source point is first reoriented within the source frame by
+/// [`orientation`](#structfield.orientation), then the reoriented point `(x, y)` maps to
+/// `(x * scale_x + offset_x, y * scale_y + offset_y)` in the output. The source content
+/// covers [`content_rect`](#structfield.content_rect) of the output; the rest of the output
+/// is padding.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// // A 1920x1080 frame letterboxed into a 640x640 model input.
+/// let transform = LetterboxTransform::fit(
+///     FrameSize::new(1920, 1080),
+///     FrameSize::new(640, 640),
+///     Alignment::Center,
+/// );
+/// assert_eq!(transform.scale_x, 1.0 / 3.0);
+/// assert_eq!(transform.offset_y, 140.0);
+/// assert_eq!(transform.content_rect, Rect::new(0.0, 140.0, 640.0, 360.0));
+///
+/// // Map a detection on the model input back to screen pixels.
+/// let detection = Rect::new(100.0, 200.0, 30.0, 60.0);
+/// assert_eq!(transform.unmap_rect(detection), Rect::new(300.0, 180.0, 90.0, 180.0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LetterboxTransform {
+    /// The horizontal scale factor from source to output pixels.
+    pub scale_x: f32,
+    /// The vertical scale factor from source to output pixels. Equal to
+    /// [`scale_x`](#structfield.scale_x) when the aspect ratio is preserved.
+    pub scale_y: f32,
+    /// The horizontal position of the source origin in the output.
+    pub offset_x: f32,
+    /// The vertical position of the source origin in the output.
+    pub offset_y: f32,
+    /// The area of the output covered by source content, excluding padding and any parts of
+    /// the source cropped away.
+    pub content_rect: Rect,
+    /// The size of the source frame.
+    pub source_size: FrameSize,
+    /// The size of the output frame.
+    pub output_size: FrameSize,
+    /// The rotation or reflection of the source frame, applied before it is scaled. The
+    /// scale factors and offsets apply to the axes of the reoriented frame.
+    pub orientation: Orientation,
+}
+
+impl LetterboxTransform {
+    /// Returns the transform of a frame that is not scaled.
+    pub fn identity(size: FrameSize) -> Self {
+        Self {
+            scale_x: 1.0,
+            scale_y: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            content_rect: size.into(),
+            source_size: size,
+            output_size: size,
+            orientation: Orientation::Identity,
+        }
+    }
+
+    /// Returns the transform of `source` resized to `desired` with the given mode.
+    ///
+    /// The [`output_size`](#structfield.output_size) of the transform is the size of the
+    /// resulting frame. It equals `desired`, except for [`ResizeMode::ScaleOnly`], where it
+    /// is derived from the scaled source size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// let source = FrameSize::new(1920, 1080);
+    /// let desired = FrameSize::new(640, 640);
+    ///
+    /// let fill = LetterboxTransform::new(source, desired, ResizeMode::Fill { alignment: Alignment::Center });
+    /// assert_eq!(fill.scale_x, 640.0 / 1080.0);
+    /// assert_eq!(fill.content_rect, Rect::new(0.0, 0.0, 640.0, 640.0));
+    /// assert!((fill.unmap_point(Point::new(320.0, 320.0)).x - 960.0).abs() < 1e-3);
+    ///
+    /// let stretch = LetterboxTransform::new(source, desired, ResizeMode::Stretch);
+    /// assert_eq!((stretch.scale_x, stretch.scale_y), (1.0 / 3.0, 640.0 / 1080.0));
+    ///
+    /// let scale_only = LetterboxTransform::new(source, desired, ResizeMode::ScaleOnly);
+    /// assert_eq!(scale_only.output_size, FrameSize::new(640, 360));
+    /// assert_eq!(scale_only.offset_y, 0.0);
+    /// ```
+    pub fn new(source: FrameSize, desired: FrameSize, resize_mode: ResizeMode) -> Self {
+        match resize_mode {
+            ResizeMode::Fit { alignment, .. } => Self::fit(source, desired, alignment),
+            ResizeMode::Fill { alignment } => Self::fill(source, desired, alignment),
+            ResizeMode::Stretch => Self::stretch(source, desired),
+            ResizeMode::ScaleOnly => Self::scale_only(source, desired),
+        }
+    }
+
+    /// Returns the transform of `source` cropped to `region`, with the edges of `region`
+    /// clamped to the frame as by [`Rect::clamp_to`].
+    ///
+    /// The output is the cropped area, unscaled, so mapping a point subtracts the origin
+    /// of the region.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidCrop`] if `region` does not overlap the frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // Crop a 1280x720 game viewport from a 2560x1440 monitor, then letterbox it.
+    /// let monitor = FrameSize::new(2560, 1440);
+    /// let crop = LetterboxTransform::crop(monitor, Rect::new(640.0, 360.0, 1280.0, 720.0))?;
+    /// assert_eq!(crop.output_size, FrameSize::new(1280, 720));
+    ///
+    /// let letterbox = LetterboxTransform::new(crop.output_size, FrameSize::new(640, 640), ResizeMode::default());
+    /// let transform = crop.then(&letterbox);
+    /// assert_eq!(transform.content_rect, Rect::new(0.0, 140.0, 640.0, 360.0));
+    /// assert_eq!(transform.unmap_point(Point::new(0.0, 140.0)), Point::new(640.0, 360.0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn crop(source: FrameSize, region: Rect) -> std::result::Result<Self, WgcError> {
+        let region = region.clamp_to(source)?;
+        let output = FrameSize::new(region.width as u32, region.height as u32);
+        Ok(Self {
+            scale_x: 1.0,
+            scale_y: 1.0,
+            offset_x: -region.x,
+            offset_y: -region.y,
+            content_rect: output.into(),
+            source_size: source,
+            output_size: output,
+            orientation: Orientation::Identity,
+        })
+    }
+
+    /// Returns the transform of `source` reoriented by `orientation`, unscaled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // Crop the left half of a 1920x1080 monitor, turn it upright and letterbox it.
+    /// let monitor = FrameSize::new(1920, 1080);
+    /// let crop = LetterboxTransform::crop(monitor, Rect::new(0.0, 0.0, 960.0, 1080.0))?;
+    /// let orient = LetterboxTransform::orient(crop.output_size, Orientation::Rotate270);
+    /// assert_eq!(orient.output_size, FrameSize::new(1080, 960));
+    /// let letterbox = LetterboxTransform::new(orient.output_size, FrameSize::new(540, 540), ResizeMode::default());
+    /// let transform = crop.then(&orient).then(&letterbox);
+    /// assert_eq!(transform.orientation, Orientation::Rotate270);
+    /// assert_eq!(transform.content_rect, Rect::new(0.0, 30.0, 540.0, 480.0));
+    ///
+    /// // The top-right corner of the crop is the top-left corner of the content.
+    /// assert_eq!(transform.map_point(Point::new(960.0, 0.0)), Point::new(0.0, 30.0));
+    /// assert_eq!(transform.unmap_point(Point::new(540.0, 510.0)), Point::new(0.0, 1080.0));
+    /// assert_eq!(
+    ///     transform.unmap_rect(Rect::new(0.0, 30.0, 270.0, 240.0)),
+    ///     Rect::new(480.0, 0.0, 480.0, 540.0)
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn orient(source: FrameSize, orientation: Orientation) -> Self {
+        let output = orientation.output_size(source);
+        Self {
+            content_rect: output.into(),
+            output_size: output,
+            orientation,
+            ..Self::identity(source)
+        }
+    }
+
+    /// Returns the transform of [`ResizeMode::Fit`]: `source` is scaled to fit within
+    /// `output` while maintaining its aspect ratio, and placed according to `alignment`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// // Odd sizes produce fractional offsets, which are kept exact.
+    /// let transform = LetterboxTransform::fit(FrameSize::new(7, 3), FrameSize::new(5, 5), Alignment::Center);
+    /// assert_eq!(transform.scale_x, 5.0 / 7.0);
+    /// assert_eq!(transform.content_rect.width, 5.0);
+    /// assert!((transform.offset_y - (5.0 - 3.0 * 5.0 / 7.0) / 2.0).abs() < 1e-6);
+    ///
+    /// // A portrait frame is pillarboxed.
+    /// let transform = LetterboxTransform::fit(FrameSize::new(1080, 1920), FrameSize::new(640, 480), Alignment::Center);
+    /// assert_eq!(transform.scale_y, 0.25);
+    /// assert_eq!(transform.content_rect, Rect::new(185.0, 0.0, 270.0, 480.0));
+    ///
+    /// // Top-left alignment makes the transform a pure scale.
+    /// let transform = LetterboxTransform::fit(FrameSize::new(1080, 1920), FrameSize::new(640, 480), Alignment::TopLeft);
+    /// assert_eq!((transform.offset_x, transform.offset_y), (0.0, 0.0));
+    /// assert_eq!(transform.content_rect, Rect::new(0.0, 0.0, 270.0, 480.0));
+    ///
+    /// // Points round-trip through the transform.
+    /// let transform = LetterboxTransform::fit(FrameSize::new(1366, 768), FrameSize::new(640, 640), Alignment::Bottom);
+    /// let point = Point::new(1000.0, 123.0);
+    /// let round_trip = transform.unmap_point(transform.map_point(point));
+    /// assert!((round_trip.x - point.x).abs() < 1e-3 && (round_trip.y - point.y).abs() < 1e-3);
+    /// ```
+    pub fn fit(source: FrameSize, output: FrameSize, alignment: Alignment) -> Self {
+        if source == output || source.is_empty() {
+            return Self {
+                output_size: output,
+                ..Self::identity(source)
+            };
+        }
+        let src_w = source.width as f32;
+        let src_h = source.height as f32;
+        let dst_w = output.width as f32;
+        let dst_h = output.height as f32;
+        let scale = (dst_w / src_w).min(dst_h / src_h);
+
+        let final_w = src_w * scale;
+        let final_h = src_h * scale;
+
+        let (align_x, align_y) = alignment.factors();
+        let x = (dst_w - final_w) * align_x;
+        let y = (dst_h - final_h) * align_y;
+        Self {
+            scale_x: scale,
+            scale_y: scale,
+            offset_x: x,
+            offset_y: y,
+            content_rect: Rect::new(x, y, final_w, final_h),
+            source_size: source,
+            output_size: output,
+            orientation: Orientation::Identity,
+        }
+    }
+
+    /// Returns the transform of [`ResizeMode::Fill`]: `source` is scaled to cover `output`
+    /// while maintaining its aspect ratio, and placed according to `alignment`.
+    pub fn fill(source: FrameSize, output: FrameSize, alignment: Alignment) -> Self {
+        if source == output || source.is_empty() {
+            return Self {
+                output_size: output,
+                ..Self::identity(source)
+            };
+        }
+        let src_w = source.width as f32;
+        let src_h = source.height as f32;
+        let dst_w = output.width as f32;
+        let dst_h = output.height as f32;
+        let scale = (dst_w / src_w).max(dst_h / src_h);
+
+        let (align_x, align_y) = alignment.factors();
+        let x = (dst_w - src_w * scale) * align_x;
+        let y = (dst_h - src_h * scale) * align_y;
+        Self {
+            scale_x: scale,
+            scale_y: scale,
+            offset_x: x,
+            offset_y: y,
+            content_rect: output.into(),
+            source_size: source,
+            output_size: output,
+            orientation: Orientation::Identity,
+        }
+    }
+
+    /// Returns the transform of [`ResizeMode::ScaleOnly`]: `source` is scaled to fit within
+    /// `desired` while maintaining its aspect ratio, and the output has the scaled size,
+    /// rounded to whole pixels.
+    pub fn scale_only(source: FrameSize, desired: FrameSize) -> Self {
+        if source.is_empty() {
+            return Self::identity(source);
+        }
+        let scale = (desired.width as f32 / source.width as f32)
+            .min(desired.height as f32 / source.height as f32);
+        let output = FrameSize::new(
+            ((source.width as f32 * scale).round() as u32).clamp(1, desired.width.max(1)),
+            ((source.height as f32 * scale).round() as u32).clamp(1, desired.height.max(1)),
+        );
+        Self::stretch(source, output)
+    }
+
+    /// Returns the transform of [`ResizeMode::Stretch`]: `source` is scaled to exactly
+    /// `output`, without maintaining its aspect ratio.
+    pub fn stretch(source: FrameSize, output: FrameSize) -> Self {
+        if source.is_empty() {
+            return Self {
+                output_size: output,
+                ..Self::identity(source)
+            };
+        }
+        Self {
+            scale_x: output.width as f32 / source.width as f32,
+            scale_y: output.height as f32 / source.height as f32,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            content_rect: output.into(),
+            source_size: source,
+            output_size: output,
+            orientation: Orientation::Identity,
+        }
+    }
+
+    /// Returns the area of the output covered by the whole source frame, which may extend
+    /// beyond the output.
+    pub fn source_rect(&self) -> Rect {
+        self.map_rect(self.source_size.into())
+    }
+
+    /// Maps a point from source to output coordinates.
+    pub fn map_point(&self, point: Point) -> Point {
+        let point = self.orientation.map_point(point, self.source_size);
+        Point::new(
+            point.x * self.scale_x + self.offset_x,
+            point.y * self.scale_y + self.offset_y,
+        )
+    }
+
+    /// Maps a point from output to source coordinates.
+    pub fn unmap_point(&self, point: Point) -> Point {
+        let point = Point::new(
+            (point.x - self.offset_x) / self.scale_x,
+            (point.y - self.offset_y) / self.scale_y,
+        );
+        self.orientation.unmap_point(point, self.source_size)
+    }
+
+    /// Maps a rectangle from source to output coordinates.
+    pub fn map_rect(&self, rect: Rect) -> Rect {
+        let (width, height) = if self.orientation.swaps_axes() {
+            (rect.height, rect.width)
+        } else {
+            (rect.width, rect.height)
+        };
+        // The corners swap places when the orientation reverses an axis.
+        let top_left = self.map_point(Point::new(rect.x, rect.y));
+        let bottom_right = self.map_point(Point::new(rect.right(), rect.bottom()));
+        Rect::new(
+            top_left.x.min(bottom_right.x),
+            top_left.y.min(bottom_right.y),
+            width * self.scale_x,
+            height * self.scale_y,
+        )
+    }
+
+    /// Maps a rectangle from output to source coordinates.
+    ///
+    /// Parts of `rect` lying in the padding map outside the source frame; intersect `rect`
+    /// with [`content_rect`](#structfield.content_rect) first to discard them.
+    pub fn unmap_rect(&self, rect: Rect) -> Rect {
+        let (width, height) = (rect.width / self.scale_x, rect.height / self.scale_y);
+        let (width, height) = if self.orientation.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let top_left = self.unmap_point(Point::new(rect.x, rect.y));
+        let bottom_right = self.unmap_point(Point::new(rect.right(), rect.bottom()));
+        Rect::new(
+            top_left.x.min(bottom_right.x),
+            top_left.y.min(bottom_right.y),
+            width,
+            height,
+        )
+    }
+
+    /// Returns the transform as an affine matrix `[m11, m12, m21, m22, dx, dy]`, which maps
+    /// a source point `(x, y)` to `(x * m11 + y * m21 + dx, x * m12 + y * m22 + dy)`, in the
+    /// order used by Direct2D.
+    pub fn matrix(&self) -> [f32; 6] {
+        let (swap, flip_x, flip_y) = self.orientation.flags();
+        let sign = |flip: bool| if flip { -1.0 } else { 1.0 };
+        let scale_x = sign(flip_x) * self.scale_x;
+        let scale_y = sign(flip_y) * self.scale_y;
+        let origin = self.map_point(Point::new(0.0, 0.0));
+        if swap {
+            [0.0, scale_y, scale_x, 0.0, origin.x, origin.y]
+        } else {
+            [scale_x, 0.0, 0.0, scale_y, origin.x, origin.y]
+        }
+    }
+
+    /// Returns the transform that applies `self`, then `next`.
+    ///
+    /// `next` must map from the output of `self`.
+    pub fn then(&self, next: &LetterboxTransform) -> LetterboxTransform {
+        let content_rect = next
+            .map_rect(self.content_rect)
+            .intersection(&next.content_rect)
+            .unwrap_or_default();
+        // Reorient the scaled frame of `self` by reorienting its source frame instead: the
+        // axes of `self` follow the orientation of `next`, and a reversed axis measures
+        // its offset from the far edge.
+        let (swap, flip_x, flip_y) = next.orientation.flags();
+        let oriented = self.orientation.output_size(self.source_size);
+        let axes = [
+            (
+                self.scale_x,
+                self.offset_x,
+                next.source_size.width,
+                oriented.width,
+            ),
+            (
+                self.scale_y,
+                self.offset_y,
+                next.source_size.height,
+                oriented.height,
+            ),
+        ];
+        let [x, y] = if swap { [axes[1], axes[0]] } else { axes };
+        let axis = |(scale, offset, extent, oriented_extent): (f32, f32, u32, u32), flip| {
+            if flip {
+                (
+                    scale,
+                    extent as f32 - offset - scale * oriented_extent as f32,
+                )
+            } else {
+                (scale, offset)
+            }
+        };
+        let (scale_x, offset_x) = axis(x, flip_x);
+        let (scale_y, offset_y) = axis(y, flip_y);
+        LetterboxTransform {
+            scale_x: scale_x * next.scale_x,
+            scale_y: scale_y * next.scale_y,
+            offset_x: offset_x * next.scale_x + next.offset_x,
+            offset_y: offset_y * next.scale_y + next.offset_y,
+            content_rect,
+            source_size: self.source_size,
+            output_size: next.output_size,
+            orientation: self.orientation.then(next.orientation),
+        }
+    }
+}
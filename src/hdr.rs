@@ -0,0 +1,285 @@
+//! Decoding of HDR frames and tone-mapping to SDR.
+//!
+//! On an HDR display, Windows composes the desktop in [`ColorSpace::ScRgb`]. Frames captured
+//! in an 8-bit format are clipped, so HDR content looks washed out. Capture in
+//! [`PixelFormat::RGBA16F`] or [`PixelFormat::R10G10B10A2`] instead, and use
+//! [`CpuFrame::tone_map`] to produce 8-bit sRGB frames.
+
+use crate::*;
+
+/// The luminance of scRGB 1.0, in nits.
+const SCRGB_WHITE_NITS: f32 = 80.0;
+
+/// The luminance of PQ 1.0, in nits.
+const PQ_MAX_NITS: f32 = 10000.0;
+
+/// An operator compressing HDR luminance into the SDR range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToneMapOperator {
+    /// Clips values brighter than SDR white. SDR content is unchanged, and highlights lose
+    /// all detail.
+    Clip,
+    /// The extended Reinhard operator, which maps the peak luminance to white. Soft, but
+    /// darkens SDR content.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve. Contrasty and saturated.
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2, with its white point at the peak
+    /// luminance.
+    Hable,
+}
+
+impl ToneMapOperator {
+    /// Maps a linear value relative to SDR white to `[0, 1]`, with `peak` the relative
+    /// luminance of the brightest content.
+    fn apply(&self, value: f32, peak: f32) -> f32 {
+        let value = value.max(0.0);
+        let mapped = match self {
+            ToneMapOperator::Clip => value,
+            ToneMapOperator::Reinhard => value * (1.0 + value / (peak * peak)) / (1.0 + value),
+            ToneMapOperator::Aces => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+            ToneMapOperator::Hable => {
+                fn curve(x: f32) -> f32 {
+                    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+                    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+                }
+                const EXPOSURE_BIAS: f32 = 2.0;
+                curve(value * EXPOSURE_BIAS) / curve(peak)
+            }
+        };
+        mapped.clamp(0.0, 1.0)
+    }
+}
+
+/// Options for [`CpuFrame::tone_map`].
+#[derive(Debug, smart_default::SmartDefault, Clone, Copy, PartialEq)]
+pub struct ToneMapOptions {
+    /// The tone-mapping operator.
+    ///
+    /// Defaults to [`ToneMapOperator::Aces`].
+    #[default(ToneMapOperator::Aces)]
+    pub operator: ToneMapOperator,
+    /// The luminance of SDR white, in nits.
+    ///
+    /// Windows renders SDR content at the "SDR content brightness" of the display settings,
+    /// so set this to the same value to keep SDR content at its original brightness.
+    ///
+    /// Defaults to `80.0`, the luminance of scRGB 1.0.
+    #[default(SCRGB_WHITE_NITS)]
+    pub sdr_white_nits: f32,
+    /// The luminance of the brightest content, in nits, which the
+    /// [`Reinhard`](ToneMapOperator::Reinhard) and [`Hable`](ToneMapOperator::Hable) operators
+    /// map to white.
+    ///
+    /// Defaults to `1000.0`.
+    #[default(1000.0)]
+    pub peak_nits: f32,
+    /// The pixel format of the result, [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`].
+    ///
+    /// Defaults to [`PixelFormat::RGBA8`].
+    #[default(PixelFormat::RGBA8)]
+    pub pixel_format: PixelFormat,
+}
+
+impl CpuFrame {
+    /// Decodes the frame to linear [`ColorSpace::ScRgb`] values, as `[r, g, b, a]` for each
+    /// pixel from top to bottom.
+    ///
+    /// Half-float [`PixelFormat::RGBA16F`] values are returned as is, PQ-encoded
+    /// [`PixelFormat::R10G10B10A2`] values are linearized and converted to BT.709 primaries,
+    /// and 8-bit [`PixelFormat::RGBA8`] and [`PixelFormat::BGRA8`] values are linearized from
+    /// sRGB. Alpha is in `[0, 1]`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] for other formats.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // An scRGB pixel four times as bright as SDR white (320 nits).
+    /// let data = [4.0, 4.0, 4.0, 1.0].map(|v| f16::from_f32(v).to_le_bytes()).concat();
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(1, 1), PixelFormat::RGBA16F)?;
+    /// assert_eq!(frame.to_scrgb()?, [[4.0, 4.0, 4.0, 1.0]]);
+    ///
+    /// // PQ encoding round-trips within the precision of 10 bits.
+    /// let hdr10 = frame.convert(PixelFormat::R10G10B10A2)?;
+    /// assert_eq!(hdr10.pixel_format().color_space(), ColorSpace::Hdr10);
+    /// let [r, g, b, a] = hdr10.to_scrgb()?[0];
+    /// assert!((r - 4.0).abs() < 0.02 && (g - 4.0).abs() < 0.02 && (b - 4.0).abs() < 0.02);
+    /// assert_eq!(a, 1.0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_scrgb(&self) -> std::result::Result<Vec<[f32; 4]>, WgcError> {
+        let pixel_format = self.pixel_format();
+        let decode: fn(&[u8]) -> [f32; 4] = match pixel_format {
+            PixelFormat::RGBA16F => |pixel| {
+                let channel = |i: usize| f16::from_le_bytes([pixel[2 * i], pixel[2 * i + 1]]);
+                [0, 1, 2, 3].map(|i| channel(i).to_f32())
+            },
+            PixelFormat::R10G10B10A2 => |pixel| {
+                let bits = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                let channel = |shift: u32| pq_to_nits((bits >> shift & 0x3ff) as f32 / 1023.0);
+                let rgb = multiply(&BT2020_TO_BT709, [channel(0), channel(10), channel(20)])
+                    .map(|nits| nits / SCRGB_WHITE_NITS);
+                [rgb[0], rgb[1], rgb[2], (bits >> 30) as f32 / 3.0]
+            },
+            PixelFormat::RGBA8 => |pixel| {
+                let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(srgb_to_linear);
+                [r, g, b, pixel[3] as f32 / 255.0]
+            },
+            PixelFormat::BGRA8 => |pixel| {
+                let [r, g, b] = [pixel[2], pixel[1], pixel[0]].map(srgb_to_linear);
+                [r, g, b, pixel[3] as f32 / 255.0]
+            },
+            _ => return Err(WgcError::UnsupportedPixelFormat(pixel_format)),
+        };
+        let bytes_per_pixel = pixel_format.bytes_per_pixel() as usize;
+        Ok(self
+            .rows()
+            .flat_map(|row| row.chunks_exact(bytes_per_pixel).map(decode))
+            .collect())
+    }
+
+    /// Tone-maps the frame to 8-bit sRGB.
+    ///
+    /// The frame is decoded as by [`to_scrgb`](Self::to_scrgb), scaled so that
+    /// [`sdr_white_nits`](ToneMapOptions::sdr_white_nits) becomes 1.0, compressed into the
+    /// SDR range by the [`operator`](ToneMapOptions::operator) and encoded to sRGB.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame cannot be decoded or
+    /// [`pixel_format`](ToneMapOptions::pixel_format) is not [`PixelFormat::RGBA8`] or
+    /// [`PixelFormat::BGRA8`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // Black, SDR white and a 1000-nit highlight.
+    /// let data = [0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 12.5, 12.5, 12.5, 1.0]
+    ///     .map(|v| f16::from_f32(v).to_le_bytes())
+    ///     .concat();
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(3, 1), PixelFormat::RGBA16F)?;
+    ///
+    /// let clipped = frame.tone_map(&ToneMapOptions {
+    ///     operator: ToneMapOperator::Clip,
+    ///     ..Default::default()
+    /// })?;
+    /// assert_eq!(clipped.data(), &[0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255]);
+    ///
+    /// for operator in [ToneMapOperator::Reinhard, ToneMapOperator::Aces, ToneMapOperator::Hable] {
+    ///     let mapped = frame.tone_map(&ToneMapOptions { operator, ..Default::default() })?;
+    ///     // Black stays black, and the highlight is brighter than SDR white.
+    ///     assert_eq!(mapped.pixel(0, 0), &[0, 0, 0, 255]);
+    ///     assert!(mapped.pixel(1, 0)[0] < mapped.pixel(2, 0)[0]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tone_map(&self, options: &ToneMapOptions) -> std::result::Result<CpuFrame, WgcError> {
+        let (red, blue) = match options.pixel_format {
+            PixelFormat::RGBA8 => (0, 2),
+            PixelFormat::BGRA8 => (2, 0),
+            pixel_format => return Err(WgcError::UnsupportedPixelFormat(pixel_format)),
+        };
+        let exposure = SCRGB_WHITE_NITS / options.sdr_white_nits;
+        let peak = options.peak_nits / options.sdr_white_nits;
+        let mut data = Vec::with_capacity(self.size().pixel_count() * 4);
+        for [r, g, b, a] in self.to_scrgb()? {
+            let [r, g, b] =
+                [r, g, b].map(|v| linear_to_srgb(options.operator.apply(v * exposure, peak)));
+            let mut pixel = [0, 0, 0, (a.clamp(0.0, 1.0) * 255.0).round() as u8];
+            pixel[red] = r;
+            pixel[1] = g;
+            pixel[blue] = b;
+            data.extend_from_slice(&pixel);
+        }
+        Ok(CpuFrame::from_packed(data, self.size(), options.pixel_format)?.with_metadata_of(self))
+    }
+
+    /// Encodes the frame to [`PixelFormat::R10G10B10A2`].
+    pub(crate) fn to_hdr10(&self) -> std::result::Result<CpuFrame, WgcError> {
+        let mut data = Vec::with_capacity(self.size().pixel_count() * 4);
+        for [r, g, b, a] in self.to_scrgb()? {
+            let rgb = multiply(&BT709_TO_BT2020, [r, g, b]);
+            let [r, g, b] = rgb.map(|v| {
+                (nits_to_pq(v * SCRGB_WHITE_NITS) * 1023.0)
+                    .round()
+                    .clamp(0.0, 1023.0) as u32
+            });
+            let a = (a.clamp(0.0, 1.0) * 3.0).round() as u32;
+            data.extend_from_slice(&(r | g << 10 | b << 20 | a << 30).to_le_bytes());
+        }
+        Ok(
+            CpuFrame::from_packed(data, self.size(), PixelFormat::R10G10B10A2)?
+                .with_metadata_of(self),
+        )
+    }
+}
+
+/// Converts linear BT.2020 RGB to linear BT.709 RGB.
+const BT2020_TO_BT709: [[f32; 3]; 3] = [
+    [1.6605, -0.5876, -0.0728],
+    [-0.1246, 1.1329, -0.0083],
+    [-0.0182, -0.1006, 1.1187],
+];
+
+/// Converts linear BT.709 RGB to linear BT.2020 RGB.
+const BT709_TO_BT2020: [[f32; 3]; 3] = [
+    [0.6274, 0.3293, 0.0433],
+    [0.0691, 0.9195, 0.0114],
+    [0.0164, 0.0880, 0.8956],
+];
+
+fn multiply(matrix: &[[f32; 3]; 3], [r, g, b]: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * r + row[1] * g + row[2] * b)
+}
+
+// SMPTE ST 2084 constants.
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+/// The PQ EOTF: converts a signal in `[0, 1]` to nits.
+fn pq_to_nits(signal: f32) -> f32 {
+    let power = signal.max(0.0).powf(1.0 / PQ_M2);
+    let linear = ((power - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * power)).powf(1.0 / PQ_M1);
+    linear * PQ_MAX_NITS
+}
+
+/// The inverse PQ EOTF: converts nits to a signal in `[0, 1]`.
+fn nits_to_pq(nits: f32) -> f32 {
+    let power = (nits / PQ_MAX_NITS).clamp(0.0, 1.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * power) / (1.0 + PQ_C3 * power)).powf(PQ_M2)
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear value in `[0, 1]` to an 8-bit sRGB value.
+fn linear_to_srgb(value: f32) -> u8 {
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round().clamp(0.0, 255.0) as u8
+}
@@ -0,0 +1,1553 @@
+//! Row conversion kernels with runtime SIMD dispatch.
+//!
+//! The CPU pixel conversions of the crate run through the kernels of this module: the
+//! swizzles and luma conversions of [`CpuFrame::convert`], the YCbCr conversions of
+//! [`CpuFrame::to_yuv`] and [`YuvFrame::to_rgb`], the float normalization of
+//! [`CpuFrame::to_tensor`] and, on Windows, the copy out of the mapped Direct2D bitmap that
+//! ends every read.
+//!
+//! Each kernel has a portable scalar implementation, which defines its results, and SSE2,
+//! AVX2 or NEON implementations that produce identical output. The best [`SimdLevel`]
+//! supported by the CPU is detected once and used unless a kernel is given another level
+//! with `with_level`, which is how the vector paths are compared to the scalar one.
+//!
+//! The HDR conversions are not vectorized.
+//!
+//! # Example
+//!
+//! ```
+//! use wgc::kernels::{RowKernel, SimdLevel};
+//! use wgc::*;
+//!
+//! let kernel = RowKernel::new(PixelFormat::BGRA8, PixelFormat::RGB24).unwrap();
+//! assert!(kernel.level().is_supported());
+//!
+//! let mut rgb = [0; 6];
+//! kernel.run(&[0, 0, 255, 255, 255, 0, 0, 255], &mut rgb);
+//! assert_eq!(rgb, [255, 0, 0, 0, 0, 255]);
+//!
+//! let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+//! let mut expected = [0; 6];
+//! scalar.run(&[0, 0, 255, 255, 255, 0, 0, 255], &mut expected);
+//! assert_eq!(rgb, expected);
+//! ```
+
+use std::sync::OnceLock;
+
+use crate::*;
+
+/// A set of vector instructions the kernels can be run with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimdLevel {
+    /// Portable code without explicit vector instructions, available everywhere.
+    Scalar,
+    /// SSE2 on x86 and x86-64.
+    Sse2,
+    /// AVX2 on x86 and x86-64.
+    Avx2,
+    /// NEON on little-endian AArch64.
+    Neon,
+}
+
+impl SimdLevel {
+    /// Returns the most capable level supported by the CPU, detected on the first call.
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<SimdLevel> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            let level = Self::supported()
+                .last()
+                .copied()
+                .unwrap_or(SimdLevel::Scalar);
+            debug!("Detected SIMD level {:?}", level);
+            level
+        })
+    }
+
+    /// Returns the levels supported by the CPU, from the least to the most capable.
+    pub fn supported() -> Vec<Self> {
+        [
+            SimdLevel::Scalar,
+            SimdLevel::Sse2,
+            SimdLevel::Avx2,
+            SimdLevel::Neon,
+        ]
+        .into_iter()
+        .filter(SimdLevel::is_supported)
+        .collect()
+    }
+
+    /// Returns `true` if the CPU supports the level.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            SimdLevel::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => std::arch::is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            SimdLevel::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// A conversion between two pixel formats, applied to each pixel independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Operation {
+    Copy,
+    SwapRedBlue,
+    /// Drops alpha, swapping red and blue if `swap` is set.
+    DropAlpha {
+        swap: bool,
+    },
+    /// Computes 8-bit luma, reading red from the third byte of a pixel if `swap` is set.
+    Gray8 {
+        matrix: ColorMatrix,
+        swap: bool,
+    },
+    /// Computes 16-bit luma, reading red from the third byte of a pixel if `swap` is set.
+    Gray16 {
+        matrix: ColorMatrix,
+        swap: bool,
+    },
+}
+
+/// Converts rows of pixels from one pixel format to another.
+///
+/// Kernels exist between any format and itself, except for the YCbCr formats, and from
+/// [`PixelFormat::RGBA8`] and [`PixelFormat::BGRA8`] to either of them, to
+/// [`PixelFormat::RGB24`] and [`PixelFormat::BGR24`], and to the luma formats created by
+/// [`PixelFormat::gray8`] and [`PixelFormat::gray16`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RowKernel {
+    operation: Operation,
+    source: PixelFormat,
+    target: PixelFormat,
+    level: SimdLevel,
+}
+
+impl RowKernel {
+    /// Returns the kernel converting `source` pixels to `target`, run with the level
+    /// returned by [`SimdLevel::detect`], or `None` if there is no such kernel.
+    pub fn new(source: PixelFormat, target: PixelFormat) -> Option<Self> {
+        let operation = if source == target {
+            if source.yuv_layout().is_some() {
+                return None;
+            }
+            Operation::Copy
+        } else {
+            let swap = match source {
+                PixelFormat::RGBA8 => false,
+                PixelFormat::BGRA8 => true,
+                _ => return None,
+            };
+            match target.encoding() {
+                Encoding::Native if matches!(target, PixelFormat::RGBA8 | PixelFormat::BGRA8) => {
+                    Operation::SwapRedBlue
+                }
+                Encoding::Rgb24 => Operation::DropAlpha { swap },
+                Encoding::Bgr24 => Operation::DropAlpha { swap: !swap },
+                Encoding::Gray8(matrix) => Operation::Gray8 { matrix, swap },
+                Encoding::Gray16(matrix) => Operation::Gray16 { matrix, swap },
+                _ => return None,
+            }
+        };
+        Some(Self {
+            operation,
+            source,
+            target,
+            level: SimdLevel::detect(),
+        })
+    }
+
+    /// Returns the kernel run with the given level, or `None` if the CPU does not support
+    /// it.
+    pub fn with_level(self, level: SimdLevel) -> Option<Self> {
+        level.is_supported().then_some(Self { level, ..self })
+    }
+
+    /// Returns the level the kernel is run with.
+    pub fn level(&self) -> SimdLevel {
+        self.level
+    }
+
+    /// Returns the pixel format the kernel converts from.
+    pub fn source_format(&self) -> PixelFormat {
+        self.source
+    }
+
+    /// Returns the pixel format the kernel converts to.
+    pub fn target_format(&self) -> PixelFormat {
+        self.target
+    }
+
+    /// Converts the pixels of `source` into `target`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` does not hold a whole number of pixels, or if `target` is not
+    /// exactly the size of as many converted pixels.
+    pub fn run(&self, source: &[u8], target: &mut [u8]) {
+        let source_bytes = self.source.bytes_per_pixel() as usize;
+        let target_bytes = self.target.bytes_per_pixel() as usize;
+        let pixels = source.len() / source_bytes;
+        assert!(
+            source.len().is_multiple_of(source_bytes) && target.len() == pixels * target_bytes,
+            "Cannot convert {} bytes of {:?} into {} bytes of {:?}",
+            source.len(),
+            self.source,
+            target.len(),
+            self.target
+        );
+        // The vector kernels convert a prefix of the pixels and leave the rest to the
+        // scalar kernel.
+        let done = match (self.operation, self.level) {
+            (Operation::Copy, _) | (_, SimdLevel::Scalar) => 0,
+            // SAFETY: kernels only hold levels that the CPU supports.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            (operation, SimdLevel::Sse2) => unsafe { x86::convert_sse2(operation, source, target) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            (operation, SimdLevel::Avx2) => unsafe { x86::convert_avx2(operation, source, target) },
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            (operation, SimdLevel::Neon) => unsafe { neon::convert(operation, source, target) },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not supported", self.level),
+        };
+        scalar::convert(
+            self.operation,
+            &source[done * source_bytes..],
+            &mut target[done * target_bytes..],
+        );
+    }
+}
+
+/// Converts rows of pixels to the interleaved, normalized `f32` values of a tensor.
+///
+/// Each pixel becomes three values, `pixel[c] * multiplier[c] + bias[c]` for each channel
+/// `c` in the order given by [`TensorOptions::channels`], where the multiplier and bias
+/// fold the scale, mean and standard deviation of the options. Kernels exist for
+/// [`PixelFormat::RGBA8`] and [`PixelFormat::BGRA8`] with [`TensorChannels::Rgb`] or
+/// [`TensorChannels::Bgr`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TensorKernel {
+    swap: bool,
+    multiplier: [f32; 3],
+    bias: [f32; 3],
+    level: SimdLevel,
+}
+
+impl TensorKernel {
+    /// Returns the kernel normalizing `source` pixels according to `options`, run with the
+    /// level returned by [`SimdLevel::detect`], or `None` if there is no such kernel.
+    pub fn new(source: PixelFormat, options: &TensorOptions) -> Option<Self> {
+        let swap = match (source, options.channels) {
+            (PixelFormat::RGBA8, TensorChannels::Rgb)
+            | (PixelFormat::BGRA8, TensorChannels::Bgr) => false,
+            (PixelFormat::RGBA8, TensorChannels::Bgr)
+            | (PixelFormat::BGRA8, TensorChannels::Rgb) => true,
+            _ => return None,
+        };
+        Some(Self {
+            swap,
+            multiplier: std::array::from_fn(|c| options.scale / options.std[c]),
+            bias: std::array::from_fn(|c| -options.mean[c] / options.std[c]),
+            level: SimdLevel::detect(),
+        })
+    }
+
+    /// Returns the kernel run with the given level, or `None` if the CPU does not support
+    /// it.
+    pub fn with_level(self, level: SimdLevel) -> Option<Self> {
+        level.is_supported().then_some(Self { level, ..self })
+    }
+
+    /// Returns the level the kernel is run with.
+    pub fn level(&self) -> SimdLevel {
+        self.level
+    }
+
+    /// Converts the pixels of `source` into three values each in `target`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` does not hold a whole number of pixels, or if `target` does not
+    /// hold exactly three values per pixel.
+    pub fn run(&self, source: &[u8], target: &mut [f32]) {
+        let pixels = source.len() / 4;
+        assert!(
+            source.len().is_multiple_of(4) && target.len() == pixels * 3,
+            "Cannot normalize {} bytes into {} values",
+            source.len(),
+            target.len()
+        );
+        let (swap, multiplier, bias) = (self.swap, self.multiplier, self.bias);
+        let done = match self.level {
+            SimdLevel::Scalar => 0,
+            // SAFETY: kernels only hold levels that the CPU supports.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => unsafe {
+                x86::normalize_sse2(swap, multiplier, bias, source, target)
+            },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => unsafe {
+                x86::normalize_avx2(swap, multiplier, bias, source, target)
+            },
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            SimdLevel::Neon => unsafe { neon::normalize(swap, multiplier, bias, source, target) },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not supported", self.level),
+        };
+        scalar::normalize(
+            swap,
+            multiplier,
+            bias,
+            &source[done * 4..],
+            &mut target[done * 3..],
+        );
+    }
+}
+
+/// Converts rows of pixels between RGB and YCbCr.
+///
+/// [`to_yuv`](Self::to_yuv) computes the quantized luma and the unquantized chroma of each
+/// pixel, which is then averaged over the pixels of each chroma sample and quantized with
+/// [`quantize_chroma`](Self::quantize_chroma). [`to_rgb`](Self::to_rgb) converts luma and
+/// horizontally subsampled chroma back, repeating each chroma sample for two pixels.
+/// Kernels exist between [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`] and any YCbCr
+/// format; only the matrix and range of the latter matter.
+///
+/// # Example
+///
+/// ```
+/// use wgc::kernels::YuvKernel;
+/// use wgc::*;
+///
+/// let kernel = YuvKernel::new(PixelFormat::RGBA8, PixelFormat::NV12).unwrap();
+/// let (mut luma, mut u, mut v) = ([0; 2], [0.0; 2], [0.0; 2]);
+/// kernel.to_yuv(&[255, 255, 255, 255, 0, 0, 0, 255], &mut luma, &mut u, &mut v);
+/// assert_eq!(luma, [235, 16]);
+/// assert_eq!(kernel.quantize_chroma((u[0] + u[1]) / 2.0), 128);
+///
+/// let mut rgba = [0; 8];
+/// kernel.to_rgb(&luma, &[128], &[128], &mut rgba);
+/// assert_eq!(rgba, [255, 255, 255, 255, 0, 0, 0, 255]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YuvKernel {
+    coefficients: Coefficients,
+    swap: bool,
+    level: SimdLevel,
+}
+
+impl YuvKernel {
+    /// Returns the kernel converting between `rgb` pixels and the YCbCr format `yuv`, run
+    /// with the level returned by [`SimdLevel::detect`], or `None` if there is no such
+    /// kernel.
+    pub fn new(rgb: PixelFormat, yuv: PixelFormat) -> Option<Self> {
+        let swap = match rgb {
+            PixelFormat::RGBA8 => false,
+            PixelFormat::BGRA8 => true,
+            _ => return None,
+        };
+        let Encoding::Yuv(_, matrix, range) = yuv.encoding() else {
+            return None;
+        };
+        Some(Self {
+            coefficients: Coefficients::new(matrix, range),
+            swap,
+            level: SimdLevel::detect(),
+        })
+    }
+
+    /// Returns the kernel run with the given level, or `None` if the CPU does not support
+    /// it.
+    pub fn with_level(self, level: SimdLevel) -> Option<Self> {
+        level.is_supported().then_some(Self { level, ..self })
+    }
+
+    /// Returns the level the kernel is run with.
+    pub fn level(&self) -> SimdLevel {
+        self.level
+    }
+
+    /// Converts the pixels of `source` into their quantized luma in `luma` and their
+    /// chroma, from -127.5 to 127.5, in `u` and `v`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source` does not hold a whole number of pixels, or if `luma`, `u` or `v`
+    /// does not hold exactly one value per pixel.
+    pub fn to_yuv(&self, source: &[u8], luma: &mut [u8], u: &mut [f32], v: &mut [f32]) {
+        let pixels = source.len() / 4;
+        assert!(
+            source.len().is_multiple_of(4)
+                && luma.len() == pixels
+                && u.len() == pixels
+                && v.len() == pixels,
+            "Cannot convert {} bytes into {}, {} and {} samples",
+            source.len(),
+            luma.len(),
+            u.len(),
+            v.len()
+        );
+        let (coefficients, swap) = (self.coefficients, self.swap);
+        let done = match self.level {
+            SimdLevel::Scalar => 0,
+            // SAFETY: kernels only hold levels that the CPU supports.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => unsafe { x86::to_yuv_sse2(coefficients, swap, source, luma, u, v) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => unsafe { x86::to_yuv_avx2(coefficients, swap, source, luma, u, v) },
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            SimdLevel::Neon => unsafe { neon::to_yuv(coefficients, swap, source, luma, u, v) },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not supported", self.level),
+        };
+        scalar::to_yuv(
+            coefficients,
+            swap,
+            &source[done * 4..],
+            &mut luma[done..],
+            &mut u[done..],
+            &mut v[done..],
+        );
+    }
+
+    /// Quantizes chroma computed by [`to_yuv`](Self::to_yuv), usually the average of the
+    /// pixels a chroma sample covers.
+    pub fn quantize_chroma(&self, chroma: f32) -> u8 {
+        self.coefficients.quantize_chroma(chroma)
+    }
+
+    /// Converts the samples of `luma`, `u` and `v` into opaque pixels in `target`, where
+    /// each chroma sample covers two pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `u` and `v` do not hold exactly one sample per two luma samples, rounded
+    /// up, or if `target` is not exactly the size of as many pixels as `luma` has samples.
+    pub fn to_rgb(&self, luma: &[u8], u: &[u8], v: &[u8], target: &mut [u8]) {
+        let pixels = luma.len();
+        assert!(
+            u.len() == pixels.div_ceil(2) && v.len() == u.len() && target.len() == pixels * 4,
+            "Cannot convert {}, {} and {} samples into {} bytes",
+            pixels,
+            u.len(),
+            v.len(),
+            target.len()
+        );
+        let (coefficients, swap) = (self.coefficients, self.swap);
+        // The vector kernels convert an even number of pixels, so that the scalar kernel
+        // starts on a chroma sample.
+        let done = match self.level {
+            SimdLevel::Scalar => 0,
+            // SAFETY: kernels only hold levels that the CPU supports.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => unsafe { x86::to_rgb_sse2(coefficients, swap, luma, u, v, target) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => unsafe { x86::to_rgb_avx2(coefficients, swap, luma, u, v, target) },
+            #[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+            SimdLevel::Neon => unsafe { neon::to_rgb(coefficients, swap, luma, u, v, target) },
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not supported", self.level),
+        };
+        scalar::to_rgb(
+            coefficients,
+            swap,
+            &luma[done..],
+            &u[done / 2..],
+            &v[done / 2..],
+            &mut target[done * 4..],
+        );
+    }
+}
+
+/// The coefficients of a color matrix and range, in 8-bit units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Coefficients {
+    kr: f32,
+    kg: f32,
+    kb: f32,
+    /// The divisors of the blue and red differences, `2 * (1 - kb)` and `2 * (1 - kr)`.
+    u_scale: f32,
+    v_scale: f32,
+    limited: bool,
+}
+
+impl Coefficients {
+    fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        let [kr, kg, kb] = matrix.luma_weights();
+        Self {
+            kr,
+            kg,
+            kb,
+            u_scale: 2.0 * (1.0 - kb),
+            v_scale: 2.0 * (1.0 - kr),
+            limited: range == ColorRange::Limited,
+        }
+    }
+
+    fn quantize_chroma(&self, chroma: f32) -> u8 {
+        let value = if self.limited {
+            128.0 + chroma * 224.0 / 255.0
+        } else {
+            128.0 + chroma
+        };
+        (value + 0.5) as u8
+    }
+}
+
+/// The reference implementations of the kernels.
+mod scalar {
+    use super::*;
+
+    pub(super) fn convert(operation: Operation, source: &[u8], target: &mut [u8]) {
+        match operation {
+            Operation::Copy => target.copy_from_slice(source),
+            Operation::SwapRedBlue => {
+                for (pixel, output) in source.chunks_exact(4).zip(target.chunks_exact_mut(4)) {
+                    output.copy_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+                }
+            }
+            Operation::DropAlpha { swap } => {
+                for (pixel, output) in source.chunks_exact(4).zip(target.chunks_exact_mut(3)) {
+                    let (first, third) = if swap { (2, 0) } else { (0, 2) };
+                    output.copy_from_slice(&[pixel[first], pixel[1], pixel[third]]);
+                }
+            }
+            Operation::Gray8 { matrix, swap } => {
+                for (pixel, output) in source.chunks_exact(4).zip(target.iter_mut()) {
+                    *output = (luma(matrix, swap, pixel) + 0.5) as u8;
+                }
+            }
+            Operation::Gray16 { matrix, swap } => {
+                for (pixel, output) in source.chunks_exact(4).zip(target.chunks_exact_mut(2)) {
+                    let value = (luma(matrix, swap, pixel) * 257.0 + 0.5) as u16;
+                    output.copy_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    fn luma(matrix: ColorMatrix, swap: bool, pixel: &[u8]) -> f32 {
+        let (red, blue) = if swap { (2, 0) } else { (0, 2) };
+        matrix.luma(pixel[red] as f32, pixel[1] as f32, pixel[blue] as f32)
+    }
+
+    pub(super) fn normalize(
+        swap: bool,
+        multiplier: [f32; 3],
+        bias: [f32; 3],
+        source: &[u8],
+        target: &mut [f32],
+    ) {
+        for (pixel, output) in source.chunks_exact(4).zip(target.chunks_exact_mut(3)) {
+            for (c, value) in output.iter_mut().enumerate() {
+                let channel = if swap { 2 - c } else { c };
+                *value = pixel[channel] as f32 * multiplier[c] + bias[c];
+            }
+        }
+    }
+
+    pub(super) fn to_yuv(
+        coefficients: Coefficients,
+        swap: bool,
+        source: &[u8],
+        luma: &mut [u8],
+        u: &mut [f32],
+        v: &mut [f32],
+    ) {
+        let Coefficients { kr, kg, kb, .. } = coefficients;
+        let (red, blue) = if swap { (2, 0) } else { (0, 2) };
+        for (i, pixel) in source.chunks_exact(4).enumerate() {
+            let (r, g, b) = (pixel[red] as f32, pixel[1] as f32, pixel[blue] as f32);
+            let y = kr * r + kg * g + kb * b;
+            let quantized = if coefficients.limited {
+                16.0 + y * 219.0 / 255.0
+            } else {
+                y
+            };
+            luma[i] = (quantized + 0.5) as u8;
+            u[i] = (b - y) / coefficients.u_scale;
+            v[i] = (r - y) / coefficients.v_scale;
+        }
+    }
+
+    pub(super) fn to_rgb(
+        coefficients: Coefficients,
+        swap: bool,
+        luma: &[u8],
+        u: &[u8],
+        v: &[u8],
+        target: &mut [u8],
+    ) {
+        let Coefficients { kr, kg, kb, .. } = coefficients;
+        let (red, blue) = if swap { (2, 0) } else { (0, 2) };
+        for (i, output) in target.chunks_exact_mut(4).enumerate() {
+            let (y, u, v) = (
+                luma[i] as f32,
+                u[i / 2] as f32 - 128.0,
+                v[i / 2] as f32 - 128.0,
+            );
+            let (y, u, v) = if coefficients.limited {
+                (
+                    (y - 16.0) * 255.0 / 219.0,
+                    u * 255.0 / 224.0,
+                    v * 255.0 / 224.0,
+                )
+            } else {
+                (y, u, v)
+            };
+            let r = y + coefficients.v_scale * v;
+            let b = y + coefficients.u_scale * u;
+            let g = (y - kr * r - kb * b) / kg;
+            output[red] = (r + 0.5) as u8;
+            output[1] = (g + 0.5) as u8;
+            output[blue] = (b + 0.5) as u8;
+            output[3] = 255;
+        }
+    }
+}
+
+/// SSE2 and AVX2 kernels. They perform the same floating-point operations in the same
+/// order as the scalar kernels, without fused multiply-adds, so their results are
+/// identical.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::{Coefficients, Operation};
+    use crate::ColorMatrix;
+
+    /// Converts a prefix of the pixels with SSE2 and returns its length in pixels.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn convert_sse2(operation: Operation, source: &[u8], target: &mut [u8]) -> usize {
+        match operation {
+            Operation::SwapRedBlue => swap_red_blue_sse2(source, target),
+            Operation::Gray8 { matrix, swap } => gray_sse2(matrix, swap, false, source, target),
+            Operation::Gray16 { matrix, swap } => gray_sse2(matrix, swap, true, source, target),
+            // SSE2 has no byte shuffle to pack three-byte pixels with.
+            Operation::Copy | Operation::DropAlpha { .. } => 0,
+        }
+    }
+
+    /// Converts a prefix of the pixels with AVX2 and returns its length in pixels.
+    #[target_feature(enable = "avx2")]
+    pub(super) fn convert_avx2(operation: Operation, source: &[u8], target: &mut [u8]) -> usize {
+        match operation {
+            Operation::SwapRedBlue => swap_red_blue_avx2(source, target),
+            Operation::DropAlpha { swap } => drop_alpha_avx2(swap, source, target),
+            Operation::Gray8 { matrix, swap } => gray_avx2(matrix, swap, false, source, target),
+            Operation::Gray16 { matrix, swap } => gray_avx2(matrix, swap, true, source, target),
+            Operation::Copy => 0,
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn swap_red_blue_sse2(source: &[u8], target: &mut [u8]) -> usize {
+        let green_alpha = _mm_set1_epi32(0xFF00_FF00_u32 as i32);
+        let low_byte = _mm_set1_epi32(0xFF);
+        let blocks = source.len() / 16;
+        for i in 0..blocks {
+            // SAFETY: both slices hold `blocks` blocks of 16 bytes.
+            let pixels = unsafe { _mm_loadu_si128(source.as_ptr().add(i * 16).cast()) };
+            let first = _mm_and_si128(pixels, low_byte);
+            let third = _mm_and_si128(_mm_srli_epi32(pixels, 16), low_byte);
+            let swapped = _mm_or_si128(
+                _mm_and_si128(pixels, green_alpha),
+                _mm_or_si128(_mm_slli_epi32(first, 16), third),
+            );
+            unsafe { _mm_storeu_si128(target.as_mut_ptr().add(i * 16).cast(), swapped) };
+        }
+        blocks * 4
+    }
+
+    /// Returns the red, green and blue channels of 4 pixels as floats.
+    #[target_feature(enable = "sse2")]
+    fn channels_sse2(pixels: __m128i, swap: bool) -> [__m128; 3] {
+        let low_byte = _mm_set1_epi32(0xFF);
+        let first = _mm_cvtepi32_ps(_mm_and_si128(pixels, low_byte));
+        let green = _mm_cvtepi32_ps(_mm_and_si128(_mm_srli_epi32(pixels, 8), low_byte));
+        let third = _mm_cvtepi32_ps(_mm_and_si128(_mm_srli_epi32(pixels, 16), low_byte));
+        if swap {
+            [third, green, first]
+        } else {
+            [first, green, third]
+        }
+    }
+
+    /// Returns the rounded luma of 4 pixels, as in the scalar kernels.
+    #[target_feature(enable = "sse2")]
+    fn luma_sse2(pixels: __m128i, matrix: ColorMatrix, swap: bool, wide: bool) -> __m128i {
+        let [kr, kg, kb] = matrix.luma_weights();
+        let [red, green, blue] = channels_sse2(pixels, swap);
+        let luma = _mm_add_ps(
+            _mm_add_ps(
+                _mm_mul_ps(_mm_set1_ps(kr), red),
+                _mm_mul_ps(_mm_set1_ps(kg), green),
+            ),
+            _mm_mul_ps(_mm_set1_ps(kb), blue),
+        );
+        let luma = if wide {
+            _mm_mul_ps(luma, _mm_set1_ps(257.0))
+        } else {
+            luma
+        };
+        _mm_cvttps_epi32(_mm_add_ps(luma, _mm_set1_ps(0.5)))
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn gray_sse2(
+        matrix: ColorMatrix,
+        swap: bool,
+        wide: bool,
+        source: &[u8],
+        target: &mut [u8],
+    ) -> usize {
+        let blocks = source.len() / 32;
+        for i in 0..blocks {
+            // SAFETY: `source` holds `blocks` blocks of 8 pixels and `target` as many
+            // blocks of 8 luma values.
+            let (low, high) = unsafe {
+                (
+                    _mm_loadu_si128(source.as_ptr().add(i * 32).cast()),
+                    _mm_loadu_si128(source.as_ptr().add(i * 32 + 16).cast()),
+                )
+            };
+            let low = luma_sse2(low, matrix, swap, wide);
+            let high = luma_sse2(high, matrix, swap, wide);
+            if wide {
+                // SSE2 only packs with signed saturation, so move the values into the
+                // signed range and back.
+                let offset = _mm_set1_epi32(0x8000);
+                let packed =
+                    _mm_packs_epi32(_mm_sub_epi32(low, offset), _mm_sub_epi32(high, offset));
+                let values = _mm_xor_si128(packed, _mm_set1_epi16(0x8000_u16 as i16));
+                unsafe { _mm_storeu_si128(target.as_mut_ptr().add(i * 16).cast(), values) };
+            } else {
+                let words = _mm_packs_epi32(low, high);
+                let bytes = _mm_packus_epi16(words, words);
+                unsafe { _mm_storel_epi64(target.as_mut_ptr().add(i * 8).cast(), bytes) };
+            }
+        }
+        blocks * 8
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn swap_red_blue_avx2(source: &[u8], target: &mut [u8]) -> usize {
+        let shuffle = _mm256_setr_epi8(
+            2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15, //
+            2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15,
+        );
+        let blocks = source.len() / 32;
+        for i in 0..blocks {
+            // SAFETY: both slices hold `blocks` blocks of 32 bytes.
+            let pixels = unsafe { _mm256_loadu_si256(source.as_ptr().add(i * 32).cast()) };
+            let swapped = _mm256_shuffle_epi8(pixels, shuffle);
+            unsafe { _mm256_storeu_si256(target.as_mut_ptr().add(i * 32).cast(), swapped) };
+        }
+        blocks * 8
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn drop_alpha_avx2(swap: bool, source: &[u8], target: &mut [u8]) -> usize {
+        // Packs the 4 pixels of each 128-bit lane into its first 12 bytes, then the two
+        // lanes into the first 24 bytes.
+        let shuffle = if swap {
+            _mm256_setr_epi8(
+                2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1, //
+                2, 1, 0, 6, 5, 4, 10, 9, 8, 14, 13, 12, -1, -1, -1, -1,
+            )
+        } else {
+            _mm256_setr_epi8(
+                0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13, 14, -1, -1, -1, -1, //
+                0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13, 14, -1, -1, -1, -1,
+            )
+        };
+        let compact = _mm256_setr_epi32(0, 1, 2, 4, 5, 6, 7, 7);
+        let pixels = source.len() / 4;
+        let mut done = 0;
+        // Each store writes 32 bytes, of which only the first 24 are pixels, so it needs 8
+        // more bytes of `target` past the 8 pixels it converts.
+        while done + 11 <= pixels {
+            // SAFETY: `source` holds 8 pixels from `done` on and `target` 32 bytes from
+            // `done * 3` on.
+            let block = unsafe { _mm256_loadu_si256(source.as_ptr().add(done * 4).cast()) };
+            let packed = _mm256_permutevar8x32_epi32(_mm256_shuffle_epi8(block, shuffle), compact);
+            unsafe { _mm256_storeu_si256(target.as_mut_ptr().add(done * 3).cast(), packed) };
+            done += 8;
+        }
+        done
+    }
+
+    /// Returns the red, green and blue channels of 8 pixels as floats.
+    #[target_feature(enable = "avx2")]
+    fn channels_avx2(pixels: __m256i, swap: bool) -> [__m256; 3] {
+        let low_byte = _mm256_set1_epi32(0xFF);
+        let first = _mm256_cvtepi32_ps(_mm256_and_si256(pixels, low_byte));
+        let green = _mm256_cvtepi32_ps(_mm256_and_si256(_mm256_srli_epi32(pixels, 8), low_byte));
+        let third = _mm256_cvtepi32_ps(_mm256_and_si256(_mm256_srli_epi32(pixels, 16), low_byte));
+        if swap {
+            [third, green, first]
+        } else {
+            [first, green, third]
+        }
+    }
+
+    /// Returns the rounded luma of 8 pixels, as in the scalar kernels.
+    #[target_feature(enable = "avx2")]
+    fn luma_avx2(pixels: __m256i, matrix: ColorMatrix, swap: bool, wide: bool) -> __m256i {
+        let [kr, kg, kb] = matrix.luma_weights();
+        let [red, green, blue] = channels_avx2(pixels, swap);
+        let luma = _mm256_add_ps(
+            _mm256_add_ps(
+                _mm256_mul_ps(_mm256_set1_ps(kr), red),
+                _mm256_mul_ps(_mm256_set1_ps(kg), green),
+            ),
+            _mm256_mul_ps(_mm256_set1_ps(kb), blue),
+        );
+        let luma = if wide {
+            _mm256_mul_ps(luma, _mm256_set1_ps(257.0))
+        } else {
+            luma
+        };
+        _mm256_cvttps_epi32(_mm256_add_ps(luma, _mm256_set1_ps(0.5)))
+    }
+
+    #[target_feature(enable = "avx2")]
+    fn gray_avx2(
+        matrix: ColorMatrix,
+        swap: bool,
+        wide: bool,
+        source: &[u8],
+        target: &mut [u8],
+    ) -> usize {
+        let blocks = source.len() / 64;
+        for i in 0..blocks {
+            // SAFETY: `source` holds `blocks` blocks of 16 pixels and `target` as many
+            // blocks of 16 luma values.
+            let (low, high) = unsafe {
+                (
+                    _mm256_loadu_si256(source.as_ptr().add(i * 64).cast()),
+                    _mm256_loadu_si256(source.as_ptr().add(i * 64 + 32).cast()),
+                )
+            };
+            let low = luma_avx2(low, matrix, swap, wide);
+            let high = luma_avx2(high, matrix, swap, wide);
+            // Packing works within 128-bit lanes, so restore the order of the pixels.
+            let words = _mm256_permute4x64_epi64(_mm256_packus_epi32(low, high), 0b11_01_10_00);
+            if wide {
+                unsafe { _mm256_storeu_si256(target.as_mut_ptr().add(i * 32).cast(), words) };
+            } else {
+                let bytes = _mm_packus_epi16(
+                    _mm256_castsi256_si128(words),
+                    _mm256_extracti128_si256(words, 1),
+                );
+                unsafe { _mm_storeu_si128(target.as_mut_ptr().add(i * 16).cast(), bytes) };
+            }
+        }
+        blocks * 16
+    }
+
+    /// Normalizes a prefix of the pixels with SSE2 and returns its length in pixels.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn normalize_sse2(
+        swap: bool,
+        multiplier: [f32; 3],
+        bias: [f32; 3],
+        source: &[u8],
+        target: &mut [f32],
+    ) -> usize {
+        let multiplier = _mm_setr_ps(multiplier[0], multiplier[1], multiplier[2], 0.0);
+        let bias = _mm_setr_ps(bias[0], bias[1], bias[2], 0.0);
+        let zero = _mm_setzero_si128();
+        let pixels = source.len() / 4;
+        let mut done = 0;
+        // Each pixel is stored as 4 values, the last of which is overwritten by the next
+        // pixel, so the last pixel converted needs a value of `target` past its own.
+        while done + 5 <= pixels {
+            // SAFETY: `source` holds 4 pixels from `done` on and `target` 4 values from
+            // each of their starts on.
+            let bytes = unsafe { _mm_loadu_si128(source.as_ptr().add(done * 4).cast()) };
+            let halves = [
+                _mm_unpacklo_epi8(bytes, zero),
+                _mm_unpackhi_epi8(bytes, zero),
+            ];
+            for (half, words) in halves.into_iter().enumerate() {
+                let quarters = [
+                    _mm_unpacklo_epi16(words, zero),
+                    _mm_unpackhi_epi16(words, zero),
+                ];
+                for (quarter, pixel) in quarters.into_iter().enumerate() {
+                    let mut values = _mm_cvtepi32_ps(pixel);
+                    if swap {
+                        values = _mm_shuffle_ps(values, values, 0b11_00_01_10);
+                    }
+                    let values = _mm_add_ps(_mm_mul_ps(values, multiplier), bias);
+                    let start = (done + half * 2 + quarter) * 3;
+                    unsafe { _mm_storeu_ps(target.as_mut_ptr().add(start), values) };
+                }
+            }
+            done += 4;
+        }
+        done
+    }
+
+    /// Normalizes a prefix of the pixels with AVX2 and returns its length in pixels.
+    #[target_feature(enable = "avx2")]
+    pub(super) fn normalize_avx2(
+        swap: bool,
+        multiplier: [f32; 3],
+        bias: [f32; 3],
+        source: &[u8],
+        target: &mut [f32],
+    ) -> usize {
+        let [m0, m1, m2] = multiplier;
+        let [b0, b1, b2] = bias;
+        let multiplier = _mm256_setr_ps(m0, m1, m2, m0, m1, m2, 0.0, 0.0);
+        let bias = _mm256_setr_ps(b0, b1, b2, b0, b1, b2, 0.0, 0.0);
+        // Moves the color channels of 2 pixels into the first 6 values.
+        let compact = if swap {
+            _mm256_setr_epi32(2, 1, 0, 6, 5, 4, 7, 7)
+        } else {
+            _mm256_setr_epi32(0, 1, 2, 4, 5, 6, 7, 7)
+        };
+        let pixels = source.len() / 4;
+        let mut done = 0;
+        // Each store writes 8 values, of which only the first 6 are pixels, so it needs 2
+        // more values of `target` past the 2 pixels it converts.
+        while done + 3 <= pixels {
+            // SAFETY: `source` holds 2 pixels from `done` on and `target` 8 values from
+            // `done * 3` on.
+            let bytes = unsafe { _mm_loadl_epi64(source.as_ptr().add(done * 4).cast()) };
+            let values = _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(bytes));
+            let values = _mm256_permutevar8x32_ps(values, compact);
+            let values = _mm256_add_ps(_mm256_mul_ps(values, multiplier), bias);
+            unsafe { _mm256_storeu_ps(target.as_mut_ptr().add(done * 3), values) };
+            done += 2;
+        }
+        done
+    }
+
+    /// Converts a prefix of the pixels to YCbCr with SSE2 and returns its length in pixels.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn to_yuv_sse2(
+        coefficients: Coefficients,
+        swap: bool,
+        source: &[u8],
+        luma: &mut [u8],
+        u: &mut [f32],
+        v: &mut [f32],
+    ) -> usize {
+        let (kr, kg, kb) = (
+            _mm_set1_ps(coefficients.kr),
+            _mm_set1_ps(coefficients.kg),
+            _mm_set1_ps(coefficients.kb),
+        );
+        let (u_scale, v_scale) = (
+            _mm_set1_ps(coefficients.u_scale),
+            _mm_set1_ps(coefficients.v_scale),
+        );
+        let blocks = source.len() / 32;
+        for i in 0..blocks {
+            let mut quantized = [_mm_setzero_si128(); 2];
+            for (half, value) in quantized.iter_mut().enumerate() {
+                let start = i * 8 + half * 4;
+                // SAFETY: `source` holds `blocks` blocks of 8 pixels and `luma`, `u` and `v`
+                // as many blocks of 8 samples.
+                let pixels = unsafe { _mm_loadu_si128(source.as_ptr().add(start * 4).cast()) };
+                let [red, green, blue] = channels_sse2(pixels, swap);
+                let y = _mm_add_ps(
+                    _mm_add_ps(_mm_mul_ps(kr, red), _mm_mul_ps(kg, green)),
+                    _mm_mul_ps(kb, blue),
+                );
+                let scaled = if coefficients.limited {
+                    _mm_add_ps(
+                        _mm_set1_ps(16.0),
+                        _mm_div_ps(_mm_mul_ps(y, _mm_set1_ps(219.0)), _mm_set1_ps(255.0)),
+                    )
+                } else {
+                    y
+                };
+                *value = _mm_cvttps_epi32(_mm_add_ps(scaled, _mm_set1_ps(0.5)));
+                unsafe {
+                    let blue_difference = _mm_div_ps(_mm_sub_ps(blue, y), u_scale);
+                    let red_difference = _mm_div_ps(_mm_sub_ps(red, y), v_scale);
+                    _mm_storeu_ps(u.as_mut_ptr().add(start), blue_difference);
+                    _mm_storeu_ps(v.as_mut_ptr().add(start), red_difference);
+                }
+            }
+            let words = _mm_packs_epi32(quantized[0], quantized[1]);
+            let bytes = _mm_packus_epi16(words, words);
+            unsafe { _mm_storel_epi64(luma.as_mut_ptr().add(i * 8).cast(), bytes) };
+        }
+        blocks * 8
+    }
+
+    /// Converts a prefix of the pixels to YCbCr with AVX2 and returns its length in pixels.
+    #[target_feature(enable = "avx2")]
+    pub(super) fn to_yuv_avx2(
+        coefficients: Coefficients,
+        swap: bool,
+        source: &[u8],
+        luma: &mut [u8],
+        u: &mut [f32],
+        v: &mut [f32],
+    ) -> usize {
+        let (kr, kg, kb) = (
+            _mm256_set1_ps(coefficients.kr),
+            _mm256_set1_ps(coefficients.kg),
+            _mm256_set1_ps(coefficients.kb),
+        );
+        let (u_scale, v_scale) = (
+            _mm256_set1_ps(coefficients.u_scale),
+            _mm256_set1_ps(coefficients.v_scale),
+        );
+        let blocks = source.len() / 64;
+        for i in 0..blocks {
+            let mut quantized = [_mm256_setzero_si256(); 2];
+            for (half, value) in quantized.iter_mut().enumerate() {
+                let start = i * 16 + half * 8;
+                // SAFETY: `source` holds `blocks` blocks of 16 pixels and `luma`, `u` and `v`
+                // as many blocks of 16 samples.
+                let pixels = unsafe { _mm256_loadu_si256(source.as_ptr().add(start * 4).cast()) };
+                let [red, green, blue] = channels_avx2(pixels, swap);
+                let y = _mm256_add_ps(
+                    _mm256_add_ps(_mm256_mul_ps(kr, red), _mm256_mul_ps(kg, green)),
+                    _mm256_mul_ps(kb, blue),
+                );
+                let scaled = if coefficients.limited {
+                    _mm256_add_ps(
+                        _mm256_set1_ps(16.0),
+                        _mm256_div_ps(
+                            _mm256_mul_ps(y, _mm256_set1_ps(219.0)),
+                            _mm256_set1_ps(255.0),
+                        ),
+                    )
+                } else {
+                    y
+                };
+                *value = _mm256_cvttps_epi32(_mm256_add_ps(scaled, _mm256_set1_ps(0.5)));
+                unsafe {
+                    let blue_difference = _mm256_div_ps(_mm256_sub_ps(blue, y), u_scale);
+                    let red_difference = _mm256_div_ps(_mm256_sub_ps(red, y), v_scale);
+                    _mm256_storeu_ps(u.as_mut_ptr().add(start), blue_difference);
+                    _mm256_storeu_ps(v.as_mut_ptr().add(start), red_difference);
+                }
+            }
+            // Packing works within 128-bit lanes, so restore the order of the pixels.
+            let words = _mm256_permute4x64_epi64(
+                _mm256_packus_epi32(quantized[0], quantized[1]),
+                0b11_01_10_00,
+            );
+            let bytes = _mm_packus_epi16(
+                _mm256_castsi256_si128(words),
+                _mm256_extracti128_si256(words, 1),
+            );
+            unsafe { _mm_storeu_si128(luma.as_mut_ptr().add(i * 16).cast(), bytes) };
+        }
+        blocks * 16
+    }
+
+    /// Returns the rounded red, green and blue channels of 4 pixels from their luma and
+    /// chroma samples, as in the scalar kernels.
+    #[target_feature(enable = "sse2")]
+    fn rgb_sse2(coefficients: Coefficients, y: __m128, u: __m128, v: __m128) -> [__m128i; 3] {
+        let offset = _mm_set1_ps(128.0);
+        let (u, v) = (_mm_sub_ps(u, offset), _mm_sub_ps(v, offset));
+        let (y, u, v) = if coefficients.limited {
+            let (full, limited) = (_mm_set1_ps(255.0), _mm_set1_ps(224.0));
+            (
+                _mm_div_ps(
+                    _mm_mul_ps(_mm_sub_ps(y, _mm_set1_ps(16.0)), full),
+                    _mm_set1_ps(219.0),
+                ),
+                _mm_div_ps(_mm_mul_ps(u, full), limited),
+                _mm_div_ps(_mm_mul_ps(v, full), limited),
+            )
+        } else {
+            (y, u, v)
+        };
+        let r = _mm_add_ps(y, _mm_mul_ps(_mm_set1_ps(coefficients.v_scale), v));
+        let b = _mm_add_ps(y, _mm_mul_ps(_mm_set1_ps(coefficients.u_scale), u));
+        let g = _mm_div_ps(
+            _mm_sub_ps(
+                _mm_sub_ps(y, _mm_mul_ps(_mm_set1_ps(coefficients.kr), r)),
+                _mm_mul_ps(_mm_set1_ps(coefficients.kb), b),
+            ),
+            _mm_set1_ps(coefficients.kg),
+        );
+        let half = _mm_set1_ps(0.5);
+        [
+            _mm_cvttps_epi32(_mm_add_ps(r, half)),
+            _mm_cvttps_epi32(_mm_add_ps(g, half)),
+            _mm_cvttps_epi32(_mm_add_ps(b, half)),
+        ]
+    }
+
+    /// Converts 16 bytes to 4 vectors of floats.
+    #[target_feature(enable = "sse2")]
+    fn widen_sse2(bytes: __m128i) -> [__m128; 4] {
+        let zero = _mm_setzero_si128();
+        let low = _mm_unpacklo_epi8(bytes, zero);
+        let high = _mm_unpackhi_epi8(bytes, zero);
+        [
+            _mm_cvtepi32_ps(_mm_unpacklo_epi16(low, zero)),
+            _mm_cvtepi32_ps(_mm_unpackhi_epi16(low, zero)),
+            _mm_cvtepi32_ps(_mm_unpacklo_epi16(high, zero)),
+            _mm_cvtepi32_ps(_mm_unpackhi_epi16(high, zero)),
+        ]
+    }
+
+    /// Interleaves 16 values of each color channel with opaque alpha into the 64 bytes of
+    /// `target`.
+    #[target_feature(enable = "sse2")]
+    fn store_rgba_sse2(first: __m128i, green: __m128i, third: __m128i, target: &mut [u8]) {
+        let target = &mut target[..64];
+        let alpha = _mm_set1_epi8(-1);
+        let (low_first, low_third) = (
+            _mm_unpacklo_epi8(first, green),
+            _mm_unpacklo_epi8(third, alpha),
+        );
+        let (high_first, high_third) = (
+            _mm_unpackhi_epi8(first, green),
+            _mm_unpackhi_epi8(third, alpha),
+        );
+        let pixels = [
+            _mm_unpacklo_epi16(low_first, low_third),
+            _mm_unpackhi_epi16(low_first, low_third),
+            _mm_unpacklo_epi16(high_first, high_third),
+            _mm_unpackhi_epi16(high_first, high_third),
+        ];
+        for (i, pixels) in pixels.into_iter().enumerate() {
+            // SAFETY: `target` holds 4 blocks of 16 bytes.
+            unsafe { _mm_storeu_si128(target.as_mut_ptr().add(i * 16).cast(), pixels) };
+        }
+    }
+
+    /// Converts a prefix of the YCbCr samples to pixels with SSE2 and returns its length in
+    /// pixels.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn to_rgb_sse2(
+        coefficients: Coefficients,
+        swap: bool,
+        luma: &[u8],
+        u: &[u8],
+        v: &[u8],
+        target: &mut [u8],
+    ) -> usize {
+        let blocks = luma.len() / 16;
+        for i in 0..blocks {
+            // SAFETY: `luma` holds `blocks` blocks of 16 samples, `u` and `v` as many blocks
+            // of 8 samples and `target` as many blocks of 16 pixels.
+            let (y, u, v) = unsafe {
+                (
+                    _mm_loadu_si128(luma.as_ptr().add(i * 16).cast()),
+                    _mm_loadl_epi64(u.as_ptr().add(i * 8).cast()),
+                    _mm_loadl_epi64(v.as_ptr().add(i * 8).cast()),
+                )
+            };
+            // Repeat each chroma sample for two pixels.
+            let (y, u, v) = (
+                widen_sse2(y),
+                widen_sse2(_mm_unpacklo_epi8(u, u)),
+                widen_sse2(_mm_unpacklo_epi8(v, v)),
+            );
+            let mut channels = [[_mm_setzero_si128(); 4]; 3];
+            for q in 0..4 {
+                let [r, g, b] = rgb_sse2(coefficients, y[q], u[q], v[q]);
+                (channels[0][q], channels[1][q], channels[2][q]) = (r, g, b);
+            }
+            // Saturating packs clamp the channels to bytes.
+            let mut bytes = [_mm_setzero_si128(); 3];
+            for (c, quarters) in channels.into_iter().enumerate() {
+                bytes[c] = _mm_packus_epi16(
+                    _mm_packs_epi32(quarters[0], quarters[1]),
+                    _mm_packs_epi32(quarters[2], quarters[3]),
+                );
+            }
+            let [red, green, blue] = bytes;
+            let (first, third) = if swap { (blue, red) } else { (red, blue) };
+            store_rgba_sse2(first, green, third, &mut target[i * 64..]);
+        }
+        blocks * 16
+    }
+
+    /// Returns the rounded red, green and blue channels of 8 pixels from their luma and
+    /// chroma samples, as in the scalar kernels.
+    #[target_feature(enable = "avx2")]
+    fn rgb_avx2(coefficients: Coefficients, y: __m256, u: __m256, v: __m256) -> [__m256i; 3] {
+        let offset = _mm256_set1_ps(128.0);
+        let (u, v) = (_mm256_sub_ps(u, offset), _mm256_sub_ps(v, offset));
+        let (y, u, v) = if coefficients.limited {
+            let (full, limited) = (_mm256_set1_ps(255.0), _mm256_set1_ps(224.0));
+            (
+                _mm256_div_ps(
+                    _mm256_mul_ps(_mm256_sub_ps(y, _mm256_set1_ps(16.0)), full),
+                    _mm256_set1_ps(219.0),
+                ),
+                _mm256_div_ps(_mm256_mul_ps(u, full), limited),
+                _mm256_div_ps(_mm256_mul_ps(v, full), limited),
+            )
+        } else {
+            (y, u, v)
+        };
+        let r = _mm256_add_ps(y, _mm256_mul_ps(_mm256_set1_ps(coefficients.v_scale), v));
+        let b = _mm256_add_ps(y, _mm256_mul_ps(_mm256_set1_ps(coefficients.u_scale), u));
+        let g = _mm256_div_ps(
+            _mm256_sub_ps(
+                _mm256_sub_ps(y, _mm256_mul_ps(_mm256_set1_ps(coefficients.kr), r)),
+                _mm256_mul_ps(_mm256_set1_ps(coefficients.kb), b),
+            ),
+            _mm256_set1_ps(coefficients.kg),
+        );
+        let half = _mm256_set1_ps(0.5);
+        [
+            _mm256_cvttps_epi32(_mm256_add_ps(r, half)),
+            _mm256_cvttps_epi32(_mm256_add_ps(g, half)),
+            _mm256_cvttps_epi32(_mm256_add_ps(b, half)),
+        ]
+    }
+
+    /// Converts 16 bytes to 2 vectors of floats.
+    #[target_feature(enable = "avx2")]
+    fn widen_avx2(bytes: __m128i) -> [__m256; 2] {
+        [
+            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(bytes)),
+            _mm256_cvtepi32_ps(_mm256_cvtepu8_epi32(_mm_srli_si128(bytes, 8))),
+        ]
+    }
+
+    /// Converts a prefix of the YCbCr samples to pixels with AVX2 and returns its length in
+    /// pixels.
+    #[target_feature(enable = "avx2")]
+    pub(super) fn to_rgb_avx2(
+        coefficients: Coefficients,
+        swap: bool,
+        luma: &[u8],
+        u: &[u8],
+        v: &[u8],
+        target: &mut [u8],
+    ) -> usize {
+        let blocks = luma.len() / 16;
+        for i in 0..blocks {
+            // SAFETY: `luma` holds `blocks` blocks of 16 samples, `u` and `v` as many blocks
+            // of 8 samples and `target` as many blocks of 16 pixels.
+            let (y, u, v) = unsafe {
+                (
+                    _mm_loadu_si128(luma.as_ptr().add(i * 16).cast()),
+                    _mm_loadl_epi64(u.as_ptr().add(i * 8).cast()),
+                    _mm_loadl_epi64(v.as_ptr().add(i * 8).cast()),
+                )
+            };
+            // Repeat each chroma sample for two pixels.
+            let (u, v) = (_mm_unpacklo_epi8(u, u), _mm_unpacklo_epi8(v, v));
+            let (y, u, v) = (widen_avx2(y), widen_avx2(u), widen_avx2(v));
+            let mut channels = [[_mm256_setzero_si256(); 2]; 3];
+            for half in 0..2 {
+                let [r, g, b] = rgb_avx2(coefficients, y[half], u[half], v[half]);
+                (channels[0][half], channels[1][half], channels[2][half]) = (r, g, b);
+            }
+            // Saturating packs clamp the channels to bytes. Packing works within 128-bit
+            // lanes, so restore the order of the pixels.
+            let mut bytes = [_mm_setzero_si128(); 3];
+            for (c, halves) in channels.into_iter().enumerate() {
+                let words = _mm256_permute4x64_epi64(
+                    _mm256_packs_epi32(halves[0], halves[1]),
+                    0b11_01_10_00,
+                );
+                bytes[c] = _mm_packus_epi16(
+                    _mm256_castsi256_si128(words),
+                    _mm256_extracti128_si256(words, 1),
+                );
+            }
+            let [red, green, blue] = bytes;
+            let (first, third) = if swap { (blue, red) } else { (red, blue) };
+            store_rgba_sse2(first, green, third, &mut target[i * 64..]);
+        }
+        blocks * 16
+    }
+}
+
+/// NEON kernels. They perform the same floating-point operations in the same order as the
+/// scalar kernels, without fused multiply-adds, so their results are identical.
+#[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{Coefficients, Operation};
+    use crate::ColorMatrix;
+
+    /// Converts a prefix of the pixels and returns its length in pixels.
+    #[target_feature(enable = "neon")]
+    pub(super) fn convert(operation: Operation, source: &[u8], target: &mut [u8]) -> usize {
+        let blocks = source.len() / 64;
+        for i in 0..blocks {
+            // SAFETY: `source` holds `blocks` blocks of 16 pixels and `target` as many
+            // blocks of 16 converted pixels.
+            let pixels = unsafe { vld4q_u8(source.as_ptr().add(i * 64)) };
+            match operation {
+                Operation::SwapRedBlue => {
+                    let swapped = uint8x16x4_t(pixels.2, pixels.1, pixels.0, pixels.3);
+                    unsafe { vst4q_u8(target.as_mut_ptr().add(i * 64), swapped) };
+                }
+                Operation::DropAlpha { swap } => {
+                    let colors = if swap {
+                        uint8x16x3_t(pixels.2, pixels.1, pixels.0)
+                    } else {
+                        uint8x16x3_t(pixels.0, pixels.1, pixels.2)
+                    };
+                    unsafe { vst3q_u8(target.as_mut_ptr().add(i * 48), colors) };
+                }
+                Operation::Gray8 { matrix, swap } => {
+                    let [low, high] = luma(pixels, matrix, swap, false);
+                    let bytes = vcombine_u8(vmovn_u16(low), vmovn_u16(high));
+                    unsafe { vst1q_u8(target.as_mut_ptr().add(i * 16), bytes) };
+                }
+                Operation::Gray16 { matrix, swap } => {
+                    let [low, high] = luma(pixels, matrix, swap, true);
+                    unsafe {
+                        vst1q_u8(target.as_mut_ptr().add(i * 32), vreinterpretq_u8_u16(low));
+                        vst1q_u8(
+                            target.as_mut_ptr().add(i * 32 + 16),
+                            vreinterpretq_u8_u16(high),
+                        );
+                    }
+                }
+                Operation::Copy => return 0,
+            }
+        }
+        blocks * 16
+    }
+
+    /// Converts the 16 values of a channel to 4 vectors of floats.
+    #[target_feature(enable = "neon")]
+    fn to_f32(channel: uint8x16_t) -> [float32x4_t; 4] {
+        let low = vmovl_u8(vget_low_u8(channel));
+        let high = vmovl_high_u8(channel);
+        [
+            vcvtq_f32_u32(vmovl_u16(vget_low_u16(low))),
+            vcvtq_f32_u32(vmovl_high_u16(low)),
+            vcvtq_f32_u32(vmovl_u16(vget_low_u16(high))),
+            vcvtq_f32_u32(vmovl_high_u16(high)),
+        ]
+    }
+
+    /// Returns the rounded luma of 16 pixels, as in the scalar kernels.
+    #[target_feature(enable = "neon")]
+    fn luma(pixels: uint8x16x4_t, matrix: ColorMatrix, swap: bool, wide: bool) -> [uint16x8_t; 2] {
+        let [kr, kg, kb] = matrix.luma_weights();
+        let (red, blue) = if swap {
+            (pixels.2, pixels.0)
+        } else {
+            (pixels.0, pixels.2)
+        };
+        let (red, green, blue) = (to_f32(red), to_f32(pixels.1), to_f32(blue));
+        let mut values = [vdupq_n_u32(0); 4];
+        for (q, value) in values.iter_mut().enumerate() {
+            let luma = vaddq_f32(
+                vaddq_f32(
+                    vmulq_f32(vdupq_n_f32(kr), red[q]),
+                    vmulq_f32(vdupq_n_f32(kg), green[q]),
+                ),
+                vmulq_f32(vdupq_n_f32(kb), blue[q]),
+            );
+            let luma = if wide {
+                vmulq_f32(luma, vdupq_n_f32(257.0))
+            } else {
+                luma
+            };
+            *value = vcvtq_u32_f32(vaddq_f32(luma, vdupq_n_f32(0.5)));
+        }
+        [
+            vcombine_u16(vmovn_u32(values[0]), vmovn_u32(values[1])),
+            vcombine_u16(vmovn_u32(values[2]), vmovn_u32(values[3])),
+        ]
+    }
+
+    /// Normalizes a prefix of the pixels and returns its length in pixels.
+    #[target_feature(enable = "neon")]
+    pub(super) fn normalize(
+        swap: bool,
+        multiplier: [f32; 3],
+        bias: [f32; 3],
+        source: &[u8],
+        target: &mut [f32],
+    ) -> usize {
+        let blocks = source.len() / 64;
+        for i in 0..blocks {
+            // SAFETY: `source` holds `blocks` blocks of 16 pixels and `target` as many
+            // blocks of 48 values.
+            let pixels = unsafe { vld4q_u8(source.as_ptr().add(i * 64)) };
+            let (first, third) = if swap {
+                (pixels.2, pixels.0)
+            } else {
+                (pixels.0, pixels.2)
+            };
+            let channels = [to_f32(first), to_f32(pixels.1), to_f32(third)];
+            for q in 0..4 {
+                let mut normalized = [vdupq_n_f32(0.0); 3];
+                for (c, value) in normalized.iter_mut().enumerate() {
+                    *value = vaddq_f32(
+                        vmulq_f32(channels[c][q], vdupq_n_f32(multiplier[c])),
+                        vdupq_n_f32(bias[c]),
+                    );
+                }
+                let values = float32x4x3_t(normalized[0], normalized[1], normalized[2]);
+                unsafe { vst3q_f32(target.as_mut_ptr().add((i * 16 + q * 4) * 3), values) };
+            }
+        }
+        blocks * 16
+    }
+
+    /// Narrows 16 values to bytes, saturating them.
+    #[target_feature(enable = "neon")]
+    fn narrow(values: [uint32x4_t; 4]) -> uint8x16_t {
+        vcombine_u8(
+            vqmovn_u16(vcombine_u16(vqmovn_u32(values[0]), vqmovn_u32(values[1]))),
+            vqmovn_u16(vcombine_u16(vqmovn_u32(values[2]), vqmovn_u32(values[3]))),
+        )
+    }
+
+    /// Converts a prefix of the pixels to YCbCr and returns its length in pixels.
+    #[target_feature(enable = "neon")]
+    pub(super) fn to_yuv(
+        coefficients: Coefficients,
+        swap: bool,
+        source: &[u8],
+        luma: &mut [u8],
+        u: &mut [f32],
+        v: &mut [f32],
+    ) -> usize {
+        let (kr, kg, kb) = (
+            vdupq_n_f32(coefficients.kr),
+            vdupq_n_f32(coefficients.kg),
+            vdupq_n_f32(coefficients.kb),
+        );
+        let (u_scale, v_scale) = (
+            vdupq_n_f32(coefficients.u_scale),
+            vdupq_n_f32(coefficients.v_scale),
+        );
+        let blocks = source.len() / 64;
+        for i in 0..blocks {
+            // SAFETY: `source` holds `blocks` blocks of 16 pixels and `luma`, `u` and `v` as
+            // many blocks of 16 samples.
+            let pixels = unsafe { vld4q_u8(source.as_ptr().add(i * 64)) };
+            let (red, blue) = if swap {
+                (pixels.2, pixels.0)
+            } else {
+                (pixels.0, pixels.2)
+            };
+            let (red, green, blue) = (to_f32(red), to_f32(pixels.1), to_f32(blue));
+            let mut quantized = [vdupq_n_u32(0); 4];
+            for (q, value) in quantized.iter_mut().enumerate() {
+                let y = vaddq_f32(
+                    vaddq_f32(vmulq_f32(kr, red[q]), vmulq_f32(kg, green[q])),
+                    vmulq_f32(kb, blue[q]),
+                );
+                let scaled = if coefficients.limited {
+                    vaddq_f32(
+                        vdupq_n_f32(16.0),
+                        vdivq_f32(vmulq_f32(y, vdupq_n_f32(219.0)), vdupq_n_f32(255.0)),
+                    )
+                } else {
+                    y
+                };
+                *value = vcvtq_u32_f32(vaddq_f32(scaled, vdupq_n_f32(0.5)));
+                let start = i * 16 + q * 4;
+                unsafe {
+                    vst1q_f32(
+                        u.as_mut_ptr().add(start),
+                        vdivq_f32(vsubq_f32(blue[q], y), u_scale),
+                    );
+                    vst1q_f32(
+                        v.as_mut_ptr().add(start),
+                        vdivq_f32(vsubq_f32(red[q], y), v_scale),
+                    );
+                }
+            }
+            unsafe { vst1q_u8(luma.as_mut_ptr().add(i * 16), narrow(quantized)) };
+        }
+        blocks * 16
+    }
+
+    /// Converts a prefix of the YCbCr samples to pixels and returns its length in pixels.
+    #[target_feature(enable = "neon")]
+    pub(super) fn to_rgb(
+        coefficients: Coefficients,
+        swap: bool,
+        luma: &[u8],
+        u: &[u8],
+        v: &[u8],
+        target: &mut [u8],
+    ) -> usize {
+        let (kr, kg, kb) = (
+            vdupq_n_f32(coefficients.kr),
+            vdupq_n_f32(coefficients.kg),
+            vdupq_n_f32(coefficients.kb),
+        );
+        let (u_scale, v_scale) = (
+            vdupq_n_f32(coefficients.u_scale),
+            vdupq_n_f32(coefficients.v_scale),
+        );
+        let (offset, half) = (vdupq_n_f32(128.0), vdupq_n_f32(0.5));
+        let blocks = luma.len() / 16;
+        for i in 0..blocks {
+            // SAFETY: `luma` holds `blocks` blocks of 16 samples, `u` and `v` as many blocks
+            // of 8 samples and `target` as many blocks of 16 pixels.
+            let (y, u, v) = unsafe {
+                (
+                    vld1q_u8(luma.as_ptr().add(i * 16)),
+                    vld1_u8(u.as_ptr().add(i * 8)),
+                    vld1_u8(v.as_ptr().add(i * 8)),
+                )
+            };
+            // Repeat each chroma sample for two pixels.
+            let (y, u, v) = (
+                to_f32(y),
+                to_f32(vcombine_u8(vzip1_u8(u, u), vzip2_u8(u, u))),
+                to_f32(vcombine_u8(vzip1_u8(v, v), vzip2_u8(v, v))),
+            );
+            let mut channels = [[vdupq_n_u32(0); 4]; 3];
+            for q in 0..4 {
+                let (y, u, v) = (y[q], vsubq_f32(u[q], offset), vsubq_f32(v[q], offset));
+                let (y, u, v) = if coefficients.limited {
+                    let (full, limited) = (vdupq_n_f32(255.0), vdupq_n_f32(224.0));
+                    (
+                        vdivq_f32(
+                            vmulq_f32(vsubq_f32(y, vdupq_n_f32(16.0)), full),
+                            vdupq_n_f32(219.0),
+                        ),
+                        vdivq_f32(vmulq_f32(u, full), limited),
+                        vdivq_f32(vmulq_f32(v, full), limited),
+                    )
+                } else {
+                    (y, u, v)
+                };
+                let r = vaddq_f32(y, vmulq_f32(v_scale, v));
+                let b = vaddq_f32(y, vmulq_f32(u_scale, u));
+                let g = vdivq_f32(
+                    vsubq_f32(vsubq_f32(y, vmulq_f32(kr, r)), vmulq_f32(kb, b)),
+                    kg,
+                );
+                // Conversions to unsigned integers saturate negative values to 0.
+                channels[0][q] = vcvtq_u32_f32(vaddq_f32(r, half));
+                channels[1][q] = vcvtq_u32_f32(vaddq_f32(g, half));
+                channels[2][q] = vcvtq_u32_f32(vaddq_f32(b, half));
+            }
+            let (red, green, blue) = (
+                narrow(channels[0]),
+                narrow(channels[1]),
+                narrow(channels[2]),
+            );
+            let (first, third) = if swap { (blue, red) } else { (red, blue) };
+            let pixels = uint8x16x4_t(first, green, third, vdupq_n_u8(255));
+            unsafe { vst4q_u8(target.as_mut_ptr().add(i * 64), pixels) };
+        }
+        blocks * 16
+    }
+}
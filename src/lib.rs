@@ -5,10 +5,16 @@
 //!
 //! **Repository:** [GitHub](https://github.com/Atliac/wgc)
 //!
+//! The frame-processing types ([`FrameSize`], [`PixelFormat`], [`WgcError`] and the
+//! [`CaptureSource`] / [`CaptureFrame`] traits) are platform-neutral, so code built on
+//! them can be compiled and tested on any platform. The capture backend itself is only
+//! available on Windows.
+//!
 //! **Getting Started:** [Tutorial](https://github.com/Atliac/wgc/blob/master/examples/tutorial.rs)
 
 // A macro that does nothing.
 #[cfg(not(feature = "tracing"))]
+#[allow(unused_macros)]
 macro_rules! noop_macro {
     ($($arg:tt)*) => {};
 }
@@ -17,35 +23,89 @@ macro_rules! noop_macro {
 macro_rules! use_tracing_macros {
     ($($tracing_macro:ident),+) => {
         $(
+// Not every macro is used on every platform.
 #[cfg(feature = "tracing")]
+#[allow(unused_imports)]
 pub(crate) use tracing::$tracing_macro;
 
 #[cfg(not(feature = "tracing"))]
+#[allow(unused_imports)]
 pub(crate) use noop_macro as $tracing_macro;
         )+
     };
 }
 use_tracing_macros!(debug, trace);
 
+pub mod common;
+pub use common::*;
+pub mod source;
+pub use source::*;
+pub mod wait;
+pub use wait::*;
+pub mod frame_rate;
+pub use frame_rate::*;
+pub mod metrics;
+pub use metrics::*;
+#[cfg(feature = "prometheus")]
+pub mod exporter;
+#[cfg(feature = "prometheus")]
+pub use exporter::*;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "async")]
+pub use stream::*;
+pub mod geometry;
+pub use geometry::*;
+pub mod cpu_frame;
+pub use cpu_frame::*;
+pub mod buffer;
+pub use buffer::*;
+pub mod convert;
+pub mod kernels;
+pub mod yuv;
+pub use yuv::*;
+pub mod hdr;
+pub use hdr::*;
+pub mod resize;
+pub mod tensor;
+pub use tensor::*;
+pub mod tiling;
+pub use tiling::*;
 pub mod settings;
 pub use settings::*;
+pub mod output;
+pub use output::*;
+pub mod synthetic;
+pub use synthetic::*;
+#[cfg(windows)]
 pub mod frame;
+#[cfg(windows)]
 pub use frame::*;
+#[cfg(windows)]
 pub mod capture;
+#[cfg(windows)]
 pub use capture::*;
 pub mod error;
 pub use error::*;
+#[cfg(windows)]
 pub mod capabilities;
+#[cfg(windows)]
 pub use capabilities::*;
 
 mod utils {
+    #[cfg(windows)]
     pub mod picker;
+    #[cfg(windows)]
     pub use picker::*;
+    #[cfg(windows)]
     pub mod window;
+    #[cfg(windows)]
     pub use window::*;
+    #[cfg(windows)]
     pub mod monitor;
+    #[cfg(windows)]
     pub use monitor::*;
-    pub(crate) mod qpc;
-    pub(crate) use qpc::*;
+    pub mod qpc;
+    pub use qpc::*;
 }
 pub use utils::*;
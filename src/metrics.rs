@@ -0,0 +1,542 @@
+//! Capture latency and throughput metrics.
+//!
+//! Every capture source records, for each delivered frame, the latency from its
+//! [`timestamp`](CaptureFrame::timestamp) to its delivery and the queue depth it was taken
+//! at, as well as the duration of each read and the frames dropped or skipped. The samples
+//! are kept in a rolling window of [`MetricsOptions::window`], split into slices so that old
+//! samples expire without being stored one by one, and durations are kept in
+//! [`DurationHistogram`]s. [`CaptureMetrics::snapshot`] returns the current window.
+//!
+//! With the `tracing` feature, a summary of the window is logged at the debug level every
+//! [`MetricsOptions::summary_interval`].
+
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::*;
+
+/// The number of sub-buckets per power of two, which bounds the relative error of a
+/// recorded value to about 3%.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+/// The largest recorded value, in microseconds, about 19 hours. Larger values are clamped.
+const MAX_MICROS: u64 = (1 << 36) - 1;
+/// The number of slices the rolling window is split into.
+const SLICES: usize = 10;
+
+/// Returns the bucket of a value, in microseconds.
+fn bucket_of(micros: u64) -> usize {
+    let micros = micros.min(MAX_MICROS);
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let shift = 63 - micros.leading_zeros() - SUB_BUCKET_BITS;
+    let sub_bucket = (micros >> shift) - SUB_BUCKETS;
+    ((shift as u64 + 1) * SUB_BUCKETS + sub_bucket) as usize
+}
+
+/// Returns the lowest value and the width of a bucket, in microseconds.
+fn bucket_range(bucket: usize) -> (u64, u64) {
+    let bucket = bucket as u64;
+    if bucket < SUB_BUCKETS {
+        return (bucket, 1);
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    let sub_bucket = bucket % SUB_BUCKETS;
+    ((SUB_BUCKETS + sub_bucket) << shift, 1 << shift)
+}
+
+/// A histogram of durations with a bounded relative error, in the manner of HdrHistogram.
+///
+/// Durations are recorded with a microsecond resolution into logarithmic buckets, each
+/// power of two being split into 32 linear sub-buckets. Percentiles are therefore accurate
+/// to about 3%, at a fixed memory cost, and histograms of different windows can be merged.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// let mut histogram = DurationHistogram::new();
+/// for millis in 1..=100 {
+///     histogram.record(Duration::from_millis(millis));
+/// }
+/// assert_eq!(histogram.count(), 100);
+/// assert_eq!(histogram.min(), Some(Duration::from_millis(1)));
+/// assert_eq!(histogram.mean(), Some(Duration::from_micros(50_500)));
+///
+/// let p99 = histogram.percentile(99.0).unwrap();
+/// assert!(p99.abs_diff(Duration::from_millis(99)) < Duration::from_millis(3));
+///
+/// // Durations of more than about 19 hours are clamped.
+/// let mut clamped = DurationHistogram::new();
+/// clamped.record(Duration::from_secs(100_000));
+/// clamped.record(Duration::MAX);
+/// assert_eq!(clamped.min(), clamped.max());
+/// assert!(clamped.max() < Some(Duration::from_secs(70_000)));
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct DurationHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum_micros: u128,
+    min_micros: u64,
+    max_micros: u64,
+}
+
+impl Default for DurationHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for DurationHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DurationHistogram")
+            .field("count", &self.count)
+            .field("min", &self.min())
+            .field("p50", &self.percentile(50.0))
+            .field("p99", &self.percentile(99.0))
+            .field("max", &self.max())
+            .finish()
+    }
+}
+
+impl DurationHistogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; bucket_of(MAX_MICROS) + 1],
+            count: 0,
+            sum_micros: 0,
+            min_micros: u64::MAX,
+            max_micros: 0,
+        }
+    }
+
+    /// Records a duration.
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros().min(MAX_MICROS as u128) as u64;
+        self.counts[bucket_of(micros)] += 1;
+        self.count += 1;
+        self.sum_micros += micros as u128;
+        self.min_micros = self.min_micros.min(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    /// Adds the durations recorded by `other` to this histogram.
+    pub fn merge(&mut self, other: &DurationHistogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum_micros += other.sum_micros;
+        self.min_micros = self.min_micros.min(other.min_micros);
+        self.max_micros = self.max_micros.max(other.max_micros);
+    }
+
+    /// Removes every recorded duration.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Returns the number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if no duration was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the smallest recorded duration, exactly.
+    pub fn min(&self) -> Option<Duration> {
+        (!self.is_empty()).then(|| Duration::from_micros(self.min_micros))
+    }
+
+    /// Returns the largest recorded duration, exactly.
+    pub fn max(&self) -> Option<Duration> {
+        (!self.is_empty()).then(|| Duration::from_micros(self.max_micros))
+    }
+
+    /// Returns the mean of the recorded durations, exactly.
+    pub fn mean(&self) -> Option<Duration> {
+        (!self.is_empty())
+            .then(|| Duration::from_micros((self.sum_micros / self.count as u128) as u64))
+    }
+
+    /// Returns the duration below or at which `percentile` percent of the recorded
+    /// durations fall, to within the precision of the histogram.
+    ///
+    /// `percentile` is clamped to `0.0..=100.0`.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.is_empty() {
+            return None;
+        }
+        let fraction = percentile.clamp(0.0, 100.0) / 100.0;
+        let rank = ((fraction * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let (low, width) = bucket_range(bucket);
+                let micros = (low + (width - 1) / 2).clamp(self.min_micros, self.max_micros);
+                return Some(Duration::from_micros(micros));
+            }
+        }
+        self.max()
+    }
+}
+
+/// Options of the metrics collected by a capture source.
+#[derive(smart_default::SmartDefault, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsOptions {
+    /// The span of the rolling window of a [`MetricsSnapshot`].
+    ///
+    /// The window is split into 10 slices and moves one slice at a time, so a snapshot
+    /// covers between 90% and 100% of it.
+    ///
+    /// Defaults to 10 seconds.
+    #[default(Duration::from_secs(10))]
+    pub window: Duration,
+    /// The interval between two summaries of the window, logged at the debug level with
+    /// the `tracing` feature, or `None` to log no summary.
+    ///
+    /// Defaults to 10 seconds.
+    #[default(Some(Duration::from_secs(10)))]
+    pub summary_interval: Option<Duration>,
+}
+
+/// The metrics of a capture source over its rolling window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsSnapshot {
+    /// The span of the rolling window.
+    pub window: Duration,
+    /// The number of frames delivered.
+    pub delivered: u64,
+    /// The rate of delivered frames, in frames per second, measured between the first and
+    /// the last delivery of the window. Zero with fewer than two deliveries.
+    pub frame_rate: f64,
+    /// The number of frames dropped because the content size changed.
+    pub dropped_for_resize: u64,
+    /// The number of frames known to be skipped because the frame queue was full.
+    pub skipped_for_overflow: u64,
+    /// The estimated number of frames skipped because the frame queue was full, see
+    /// [`FrameCounters::estimated_skipped_for_overflow`].
+    pub estimated_skipped_for_overflow: u64,
+    /// The latency from the timestamp of each frame to its delivery.
+    pub latency: DurationHistogram,
+    /// The duration of each read of the pixels of a frame.
+    pub read_duration: DurationHistogram,
+    /// The mean number of frames queued when a frame was taken, including it.
+    pub mean_queue_depth: f64,
+    /// The largest number of frames queued when a frame was taken, including it.
+    pub max_queue_depth: u32,
+}
+
+/// The samples recorded during one slice of the window.
+#[derive(Debug, Clone, Default)]
+struct Slice {
+    epoch: Option<u64>,
+    latency: DurationHistogram,
+    read_duration: DurationHistogram,
+    delivered: u64,
+    first_delivery: Option<Duration>,
+    last_delivery: Option<Duration>,
+    dropped_for_resize: u64,
+    skipped_for_overflow: u64,
+    estimated_skipped_for_overflow: u64,
+    queue_depth_sum: u64,
+    max_queue_depth: u32,
+}
+
+#[derive(Debug)]
+struct MetricsState {
+    slices: Vec<Slice>,
+    totals: FrameCounters,
+    item_closed: u64,
+    frame_size: Option<FrameSize>,
+    // Only read when summaries are logged.
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    last_summary: Option<Duration>,
+}
+
+/// Collects the metrics of a capture source.
+///
+/// Sources create their collector from [`WgcSettings::metrics`] and record into it as
+/// frames are delivered and read; [`Wgc::metrics`](crate::Wgc) and
+/// [`SyntheticSource::metrics`] return its snapshot. A collector can also be fed by hand,
+/// for example to measure a stage of a pipeline. Times are read from a [`Clock`], so tests
+/// can drive it with a [`FakeClock`].
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// let clock = FakeClock::new(Duration::from_secs(100));
+/// let metrics = CaptureMetrics::new(MetricsOptions::default(), clock.clone());
+///
+/// // Thirty frames at 30 fps, each delivered 8 ms after its timestamp.
+/// for _ in 0..30 {
+///     let timestamp = clock.elapsed_since_boot();
+///     clock.advance(Duration::from_millis(8));
+///     metrics.record_delivery(timestamp, 1);
+///     metrics.time_read(|| {
+///         clock.advance(Duration::from_millis(2));
+///         Ok::<_, WgcError>(())
+///     }).unwrap();
+///     clock.advance(Duration::from_micros(33_333 - 10_000));
+/// }
+/// metrics.record_dropped_for_resize();
+///
+/// let snapshot = metrics.snapshot();
+/// assert_eq!(snapshot.delivered, 30);
+/// assert!((snapshot.frame_rate - 30.0).abs() < 0.01);
+/// assert_eq!(snapshot.latency.percentile(50.0), Some(Duration::from_millis(8)));
+/// assert_eq!(snapshot.read_duration.max(), Some(Duration::from_millis(2)));
+/// assert_eq!(snapshot.dropped_for_resize, 1);
+///
+/// // The samples expire once the window has passed.
+/// clock.advance(Duration::from_secs(11));
+/// assert_eq!(metrics.snapshot().delivered, 0);
+/// ```
+#[derive(Debug)]
+pub struct CaptureMetrics {
+    options: MetricsOptions,
+    clock: Arc<dyn Clock>,
+    state: Mutex<MetricsState>,
+}
+
+impl CaptureMetrics {
+    /// Creates a collector that reads times from `clock`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window of `options` is zero.
+    pub fn new(options: MetricsOptions, clock: impl Clock + 'static) -> Self {
+        Self::with_shared_clock(options, Arc::new(clock))
+    }
+
+    pub(crate) fn with_shared_clock(options: MetricsOptions, clock: Arc<dyn Clock>) -> Self {
+        assert!(
+            !options.window.is_zero(),
+            "Metrics window must be greater than 0"
+        );
+        Self {
+            options,
+            clock,
+            state: Mutex::new(MetricsState {
+                slices: vec![Slice::default(); SLICES],
+                totals: FrameCounters::default(),
+                item_closed: 0,
+                frame_size: None,
+                last_summary: None,
+            }),
+        }
+    }
+
+    /// Returns the options of the collector.
+    pub fn options(&self) -> MetricsOptions {
+        self.options
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        // The state is updated field by field, so it is consistent enough to keep
+        // collecting after a panic.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the epoch of the slice containing `time`.
+    fn epoch_at(&self, time: Duration) -> u64 {
+        let slice_nanos = (self.options.window.as_nanos() / SLICES as u128).max(1);
+        (time.as_nanos() / slice_nanos) as u64
+    }
+
+    /// Calls `record` with the current time, its slice and the totals, resetting the slice
+    /// if it held an earlier epoch.
+    fn record(&self, record: impl FnOnce(Duration, &mut Slice, &mut FrameCounters)) {
+        let now = self.clock.elapsed_since_boot();
+        let epoch = self.epoch_at(now);
+        let mut state = self.lock();
+        let MetricsState { slices, totals, .. } = &mut *state;
+        let slice = &mut slices[epoch as usize % SLICES];
+        if slice.epoch != Some(epoch) {
+            *slice = Slice {
+                epoch: Some(epoch),
+                ..Default::default()
+            };
+        }
+        record(now, slice, totals);
+    }
+
+    /// Records the delivery of a frame with the given timestamp, taken when `queue_depth`
+    /// frames were queued, including it.
+    pub fn record_delivery(&self, timestamp: Duration, queue_depth: u32) {
+        self.record(|now, slice, totals| {
+            slice.latency.record(now.saturating_sub(timestamp));
+            slice.delivered += 1;
+            totals.delivered += 1;
+            slice.first_delivery.get_or_insert(now);
+            slice.last_delivery = Some(now);
+            slice.queue_depth_sum += queue_depth as u64;
+            slice.max_queue_depth = slice.max_queue_depth.max(queue_depth);
+        });
+        #[cfg(feature = "tracing")]
+        self.summarize_if_due();
+    }
+
+    /// Records the duration of a read of the pixels of a frame.
+    pub fn record_read(&self, duration: Duration) {
+        self.record(|_, slice, _| slice.read_duration.record(duration));
+    }
+
+    /// Runs `read` and records its duration if it succeeds.
+    pub fn time_read<T, E>(
+        &self,
+        read: impl FnOnce() -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        let start = self.clock.elapsed_since_boot();
+        let result = read();
+        if result.is_ok() {
+            self.record_read(self.clock.elapsed_since_boot().saturating_sub(start));
+        }
+        result
+    }
+
+    /// Records a frame dropped because the content size changed.
+    pub fn record_dropped_for_resize(&self) {
+        self.record(|_, slice, totals| {
+            slice.dropped_for_resize += 1;
+            totals.dropped_for_resize += 1;
+        });
+    }
+
+    /// Records frames known to be skipped because the frame queue was full.
+    pub fn record_skipped_for_overflow(&self, frames: u64) {
+        self.record(|_, slice, totals| {
+            slice.skipped_for_overflow += frames;
+            totals.skipped_for_overflow += frames;
+        });
+    }
+
+    /// Records frames estimated to be skipped because the frame queue was full.
+    pub fn record_estimated_skipped_for_overflow(&self, frames: u64) {
+        self.record(|_, slice, totals| {
+            slice.estimated_skipped_for_overflow += frames;
+            totals.estimated_skipped_for_overflow += frames;
+        });
+    }
+
+    /// Records the size of the frames the source currently delivers.
+    pub fn record_frame_size(&self, size: FrameSize) {
+        self.lock().frame_size = Some(size);
+    }
+
+    /// Records that the captured item was closed.
+    pub fn record_item_closed(&self) {
+        self.lock().item_closed += 1;
+    }
+
+    /// Returns the number of frames delivered, dropped and skipped since the collector was
+    /// created, outside of any window.
+    pub fn totals(&self) -> FrameCounters {
+        self.lock().totals
+    }
+
+    /// Returns the size of the frames the source currently delivers, if recorded.
+    pub fn frame_size(&self) -> Option<FrameSize> {
+        self.lock().frame_size
+    }
+
+    /// Returns the number of times the captured item was closed.
+    pub fn item_closed(&self) -> u64 {
+        self.lock().item_closed
+    }
+
+    /// Returns the metrics of the rolling window ending now.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let now_epoch = self.epoch_at(self.clock.elapsed_since_boot());
+        let oldest_epoch = now_epoch.saturating_sub(SLICES as u64 - 1);
+        let state = self.lock();
+        let mut snapshot = MetricsSnapshot {
+            window: self.options.window,
+            delivered: 0,
+            frame_rate: 0.0,
+            dropped_for_resize: 0,
+            skipped_for_overflow: 0,
+            estimated_skipped_for_overflow: 0,
+            latency: DurationHistogram::new(),
+            read_duration: DurationHistogram::new(),
+            mean_queue_depth: 0.0,
+            max_queue_depth: 0,
+        };
+        let mut queue_depth_sum = 0;
+        let mut first_delivery: Option<Duration> = None;
+        let mut last_delivery: Option<Duration> = None;
+        let current = state.slices.iter().filter(|slice| {
+            slice
+                .epoch
+                .is_some_and(|epoch| (oldest_epoch..=now_epoch).contains(&epoch))
+        });
+        for slice in current {
+            snapshot.delivered += slice.delivered;
+            snapshot.dropped_for_resize += slice.dropped_for_resize;
+            snapshot.skipped_for_overflow += slice.skipped_for_overflow;
+            snapshot.estimated_skipped_for_overflow += slice.estimated_skipped_for_overflow;
+            snapshot.latency.merge(&slice.latency);
+            snapshot.read_duration.merge(&slice.read_duration);
+            snapshot.max_queue_depth = snapshot.max_queue_depth.max(slice.max_queue_depth);
+            queue_depth_sum += slice.queue_depth_sum;
+            if let Some(first) = slice.first_delivery {
+                first_delivery = Some(first_delivery.map_or(first, |time| time.min(first)));
+            }
+            if let Some(last) = slice.last_delivery {
+                last_delivery = Some(last_delivery.map_or(last, |time| time.max(last)));
+            }
+        }
+        if snapshot.delivered > 0 {
+            snapshot.mean_queue_depth = queue_depth_sum as f64 / snapshot.delivered as f64;
+        }
+        if let (Some(first), Some(last)) = (first_delivery, last_delivery)
+            && last > first
+        {
+            snapshot.frame_rate = (snapshot.delivered - 1) as f64 / (last - first).as_secs_f64();
+        }
+        snapshot
+    }
+
+    /// Logs a summary of the window if the summary interval has elapsed since the last one.
+    #[cfg(feature = "tracing")]
+    fn summarize_if_due(&self) {
+        let Some(interval) = self.options.summary_interval else {
+            return;
+        };
+        let now = self.clock.elapsed_since_boot();
+        {
+            let mut state = self.lock();
+            let last_summary = *state.last_summary.get_or_insert(now);
+            if now.saturating_sub(last_summary) < interval {
+                return;
+            }
+            state.last_summary = Some(now);
+        }
+        let snapshot = self.snapshot();
+        debug!(
+            "Capture metrics: {:.1} fps, latency p50 {:?} p99 {:?}, read p50 {:?} p99 {:?}, {} dropped for resize, {} skipped for overflow ({} estimated), queue depth {:.2} (max {})",
+            snapshot.frame_rate,
+            snapshot.latency.percentile(50.0).unwrap_or_default(),
+            snapshot.latency.percentile(99.0).unwrap_or_default(),
+            snapshot.read_duration.percentile(50.0).unwrap_or_default(),
+            snapshot.read_duration.percentile(99.0).unwrap_or_default(),
+            snapshot.dropped_for_resize,
+            snapshot.skipped_for_overflow,
+            snapshot.estimated_skipped_for_overflow,
+            snapshot.mean_queue_depth,
+            snapshot.max_queue_depth
+        );
+    }
+}
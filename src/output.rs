@@ -0,0 +1,224 @@
+//! Several outputs read from one frame.
+//!
+//! A pipeline often needs the same frame at several sizes and formats, for example a
+//! letterboxed input for a detector, a crop for a classifier and the full frame for a
+//! recording. [`CaptureFrame::read_outputs`] produces them all from a single acquisition of
+//! the capture surface, each described by an [`OutputSpec`].
+
+use crate::*;
+
+/// Describes one output of [`CaptureFrame::read_outputs`].
+///
+/// The fields have the meaning of the [`WgcSettings`] fields of the same name, applied to
+/// this output only. [`OutputSpec::from_settings`] returns the spec of
+/// [`read_pixels`](CaptureFrame::read_pixels).
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// // The 640x640 input of a YOLO-family detector.
+/// let detection = OutputSpec {
+///     size: Some(FrameSize::new(640, 640)),
+///     resize_mode: ResizeMode::Fit { pad_color: Color::gray(114), alignment: Alignment::Center },
+///     pixel_format: PixelFormat::RGB24,
+///     ..Default::default()
+/// };
+/// let transform = detection.transform(FrameSize::new(1920, 1080)).unwrap();
+/// assert_eq!(transform.content_rect, Rect::new(0.0, 140.0, 640.0, 360.0));
+/// ```
+#[derive(smart_default::SmartDefault, Debug, Clone, Copy, PartialEq)]
+pub struct OutputSpec {
+    /// The desired size of the output, as passed to
+    /// [`read_pixels`](CaptureFrame::read_pixels).
+    ///
+    /// Defaults to `None`, which keeps the size of the cropped and reoriented frame.
+    #[default(None)]
+    pub size: Option<FrameSize>,
+    /// How the frame is fitted into [`size`](#structfield.size).
+    ///
+    /// Defaults to [`ResizeMode::Fit`], centered with 50% gray padding.
+    #[default(ResizeMode::default())]
+    pub resize_mode: ResizeMode,
+    /// The interpolation mode used when the frame is scaled.
+    ///
+    /// Defaults to [`FrameInterpolationMode::Linear`].
+    #[default(FrameInterpolationMode::Linear)]
+    pub frame_interpolation_mode: FrameInterpolationMode,
+    /// The pixel format of the output.
+    ///
+    /// The format is converted from the surface format of the source's
+    /// [`WgcSettings::pixel_format`], so the HDR formats are only available when the
+    /// source captures in an HDR format.
+    ///
+    /// Defaults to [`PixelFormat::RGBA8`].
+    #[default(PixelFormat::RGBA8)]
+    pub pixel_format: PixelFormat,
+    /// The region of interest, in frame coordinates.
+    ///
+    /// Defaults to `None`, which reads the whole frame.
+    #[default(None)]
+    pub crop: Option<Rect>,
+    /// The rotation or reflection applied after cropping.
+    ///
+    /// Defaults to [`Orientation::Identity`].
+    #[default(Orientation::Identity)]
+    pub orientation: Orientation,
+    /// How the alpha channel of the output is interpreted.
+    ///
+    /// Defaults to [`AlphaMode::Premultiplied`].
+    #[default(AlphaMode::Premultiplied)]
+    pub alpha_mode: AlphaMode,
+}
+
+impl OutputSpec {
+    /// Returns the spec of [`read_pixels`](CaptureFrame::read_pixels) with the given
+    /// desired size, for a source configured with `settings`.
+    pub fn from_settings(settings: &WgcSettings, size: Option<FrameSize>) -> Self {
+        Self {
+            size,
+            resize_mode: settings.resize_mode,
+            frame_interpolation_mode: settings.frame_interpolation_mode,
+            pixel_format: settings.pixel_format,
+            crop: settings.crop,
+            orientation: settings.orientation,
+            alpha_mode: settings.alpha_mode,
+        }
+    }
+
+    /// Returns the transform of the output for a frame of the given size: the frame is
+    /// cropped to [`crop`](#structfield.crop), reoriented by
+    /// [`orientation`](#structfield.orientation), then resized to
+    /// [`size`](#structfield.size) according to [`resize_mode`](#structfield.resize_mode).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidCrop`] if the crop region does not overlap the frame.
+    pub fn transform(
+        &self,
+        frame_size: FrameSize,
+    ) -> std::result::Result<LetterboxTransform, WgcError> {
+        let crop = match self.crop {
+            Some(region) => LetterboxTransform::crop(frame_size, region)?,
+            None => LetterboxTransform::identity(frame_size),
+        };
+        let orient = LetterboxTransform::orient(crop.output_size, self.orientation);
+        let resize = LetterboxTransform::new(
+            orient.output_size,
+            self.size.unwrap_or(orient.output_size),
+            self.resize_mode,
+        );
+        Ok(crop.then(&orient).then(&resize))
+    }
+}
+
+/// The pixel data of one output of [`CaptureFrame::read_outputs`].
+#[derive(Debug, Clone)]
+pub struct FrameOutput {
+    /// The tightly packed pixel data, of the
+    /// [`buffer_len`](PixelFormat::buffer_len) of the output size.
+    pub pixels: Vec<u8>,
+    /// The pixel format of the data.
+    pub pixel_format: PixelFormat,
+    /// How the alpha channel of the data is interpreted.
+    pub alpha_mode: AlphaMode,
+    /// The transform from frame to output coordinates. Its
+    /// [`output_size`](LetterboxTransform::output_size) is the size of the output.
+    pub transform: LetterboxTransform,
+}
+
+impl FrameOutput {
+    /// Returns the size of the output.
+    pub fn size(&self) -> FrameSize {
+        self.transform.output_size
+    }
+
+    /// Converts the output into a [`CpuFrame`], for example to export it as a tensor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] for the YCbCr formats.
+    pub fn into_cpu_frame(self) -> std::result::Result<CpuFrame, WgcError> {
+        Ok(
+            CpuFrame::from_packed(self.pixels, self.transform.output_size, self.pixel_format)?
+                .with_transform(self.transform)
+                .with_alpha_mode(self.alpha_mode),
+        )
+    }
+
+    /// Converts the output into a [`YuvFrame`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] unless the pixel format is a YCbCr
+    /// format.
+    pub fn into_yuv_frame(self) -> std::result::Result<YuvFrame, WgcError> {
+        Ok(
+            YuvFrame::from_raw(self.pixels, self.transform.output_size, self.pixel_format)?
+                .with_transform(self.transform),
+        )
+    }
+}
+
+impl CpuFrame {
+    /// Produces one output of [`CaptureFrame::read_outputs`] from the frame.
+    ///
+    /// This is the CPU equivalent of reading the frame with `spec`: the frame is cropped,
+    /// reoriented and resized, then converted to the pixel format and alpha mode of `spec`.
+    /// The [`transform`](FrameOutput::transform) of the output maps the coordinates of
+    /// `self` to those of the output.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidCrop`] if the crop region does not overlap the frame, and
+    /// [`WgcError::UnsupportedPixelFormat`] if the frame cannot be resized or converted to
+    /// the pixel format of `spec`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let frame = CpuFrame::from_packed([255, 0, 0, 255].repeat(8 * 4), FrameSize::new(8, 4), PixelFormat::BGRA8)?;
+    /// let thumbnail = frame.output(&OutputSpec {
+    ///     size: Some(FrameSize::new(4, 4)),
+    ///     pixel_format: PixelFormat::RGB24,
+    ///     ..Default::default()
+    /// })?;
+    /// assert_eq!(thumbnail.size(), FrameSize::new(4, 4));
+    /// assert_eq!(thumbnail.pixels[4 * 3..4 * 3 + 3], [0, 0, 255]);
+    ///
+    /// let crop = frame.output(&OutputSpec {
+    ///     crop: Some(Rect::new(2.0, 0.0, 2.0, 4.0)),
+    ///     orientation: Orientation::Rotate90,
+    ///     pixel_format: PixelFormat::BGRA8,
+    ///     ..Default::default()
+    /// })?;
+    /// assert_eq!(crop.size(), FrameSize::new(4, 2));
+    /// assert_eq!(crop.transform.unmap_point(Point::new(0.0, 0.0)), Point::new(2.0, 4.0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn output(&self, spec: &OutputSpec) -> std::result::Result<FrameOutput, WgcError> {
+        let mut frame = match spec.crop {
+            Some(region) => self.crop(region)?,
+            None => self.clone(),
+        };
+        if spec.orientation != Orientation::Identity {
+            frame = frame.orient(spec.orientation)?;
+        }
+        if let Some(size) = spec.size.filter(|&size| size != frame.size()) {
+            frame = frame.resize(size, spec.resize_mode, spec.frame_interpolation_mode)?;
+        }
+        // The transform maps the coordinates of `self`, whatever transform it carries.
+        let transform = spec.transform(self.size())?;
+        Ok(FrameOutput {
+            pixels: convert::into_pixels(frame, spec.pixel_format, spec.alpha_mode)?,
+            pixel_format: spec.pixel_format,
+            alpha_mode: spec.alpha_mode,
+            transform,
+        })
+    }
+}
@@ -0,0 +1,489 @@
+//! A pure-Rust implementation of the cropping, reorienting and scaling performed by
+//! [`Frame::read_pixels`](crate::Frame::read_pixels).
+//!
+//! The GPU path draws the frame with Direct2D. The functions in this module implement the
+//! same crop, orientation, fit-and-center algorithm and every [`FrameInterpolationMode`]
+//! on the CPU, so results can be computed without a GPU, compared in tests, or used where
+//! Direct2D is unavailable.
+
+use crate::*;
+
+impl CpuFrame {
+    /// Scales the frame to fit within `size` while maintaining its aspect ratio, and
+    /// letterboxes it (centered with 50% gray borders) to fill `size`.
+    ///
+    /// This is the CPU equivalent of [`CaptureFrame::read_pixels`] with a desired size: if
+    /// `size` equals the frame size, the pixels are copied unchanged. The
+    /// [`transform`](CpuFrame::transform) of the result maps the source of `self` to the
+    /// letterboxed output.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame is not [`PixelFormat::RGBA8`]
+    /// or [`PixelFormat::BGRA8`]. HDR frames are tone-mapped to one of them first, with
+    /// [`CpuFrame::tone_map`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let red = [255, 0, 0, 255].repeat(4 * 2);
+    /// let frame = CpuFrame::from_packed(red, FrameSize::new(4, 2), PixelFormat::RGBA8)?;
+    /// let boxed = frame.letterbox(FrameSize::new(4, 4), FrameInterpolationMode::Linear)?;
+    ///
+    /// assert_eq!(boxed.pixel(0, 0), &[128, 128, 128, 255]);
+    /// assert_eq!(boxed.pixel(0, 1), &[255, 0, 0, 255]);
+    /// assert_eq!(boxed.pixel(3, 2), &[255, 0, 0, 255]);
+    /// assert_eq!(boxed.pixel(3, 3), &[128, 128, 128, 255]);
+    /// assert_eq!(boxed.transform().content_rect, Rect::new(0.0, 1.0, 4.0, 2.0));
+    ///
+    /// // Every interpolation mode preserves a solid color.
+    /// for mode in [
+    ///     FrameInterpolationMode::NearestNeighbor,
+    ///     FrameInterpolationMode::Linear,
+    ///     FrameInterpolationMode::Cubic,
+    ///     FrameInterpolationMode::MultiSampleLinear,
+    ///     FrameInterpolationMode::HighQualityCubic,
+    /// ] {
+    ///     let large = frame.letterbox(FrameSize::new(40, 40), mode)?;
+    ///     assert_eq!(large.pixel(20, 20), &[255, 0, 0, 255]);
+    ///     let small = frame.letterbox(FrameSize::new(3, 3), mode)?;
+    ///     assert_eq!(small.pixel(1, 1), &[255, 0, 0, 255]);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn letterbox(
+        &self,
+        size: FrameSize,
+        interpolation_mode: FrameInterpolationMode,
+    ) -> std::result::Result<CpuFrame, WgcError> {
+        self.resize(size, ResizeMode::default(), interpolation_mode)
+    }
+
+    /// Resizes the frame to `size` with the given [`ResizeMode`].
+    ///
+    /// This is the CPU equivalent of [`CaptureFrame::read_pixels`] with a desired size and
+    /// [`WgcSettings::resize_mode`] set to `resize_mode`. The size of the result is the
+    /// [`output_size`](LetterboxTransform::output_size) of
+    /// [`LetterboxTransform::new`].
+    ///
+    /// Frames with [`AlphaMode::Straight`] are premultiplied before filtering and converted
+    /// back after, so that the colors of transparent pixels do not bleed into their
+    /// neighbours. The colors of nearly transparent pixels lose precision in the round trip.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame is not [`PixelFormat::RGBA8`]
+    /// or [`PixelFormat::BGRA8`]. HDR frames are tone-mapped to one of them first, with
+    /// [`CpuFrame::tone_map`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let data = vec![0, 0, 0, 255, 255, 255, 255, 255];
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(2, 1), PixelFormat::RGBA8)?;
+    /// let nearest = FrameInterpolationMode::NearestNeighbor;
+    ///
+    /// let stretched = frame.resize(FrameSize::new(4, 2), ResizeMode::Stretch, nearest)?;
+    /// assert_eq!(stretched.row(1), &[0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255]);
+    ///
+    /// // Filling a square keeps the center of the frame.
+    /// let fill = ResizeMode::Fill { alignment: Alignment::Center };
+    /// let filled = frame.resize(FrameSize::new(2, 2), fill, nearest)?;
+    /// assert_eq!(filled.row(0), &[0, 0, 0, 255, 255, 255, 255, 255]);
+    /// assert_eq!(filled.transform().scale_x, 2.0);
+    ///
+    /// // Left alignment keeps the left of the frame instead.
+    /// let fill = ResizeMode::Fill { alignment: Alignment::Left };
+    /// let filled = frame.resize(FrameSize::new(2, 2), fill, nearest)?;
+    /// assert_eq!(filled.row(0), &[0, 0, 0, 255, 0, 0, 0, 255]);
+    ///
+    /// // YOLO-style letterboxing, with the frame at the top.
+    /// let fit = ResizeMode::Fit { pad_color: Color::gray(114), alignment: Alignment::Top };
+    /// let boxed = frame.resize(FrameSize::new(2, 3), fit, nearest)?;
+    /// assert_eq!(boxed.row(0), &[0, 0, 0, 255, 255, 255, 255, 255]);
+    /// assert_eq!(boxed.pixel(0, 2), &[114, 114, 114, 255]);
+    ///
+    /// // Transparent padding.
+    /// let fit = ResizeMode::Fit { pad_color: Color::TRANSPARENT, alignment: Alignment::Center };
+    /// let boxed = frame.resize(FrameSize::new(2, 3), fit, nearest)?;
+    /// assert_eq!(boxed.pixel(1, 0), &[0, 0, 0, 0]);
+    ///
+    /// let scaled = frame.resize(FrameSize::new(8, 8), ResizeMode::ScaleOnly, nearest)?;
+    /// assert_eq!(scaled.size(), FrameSize::new(8, 4));
+    ///
+    /// // Opaque red next to transparent green, in straight alpha: the green does not bleed.
+    /// let data = vec![255, 0, 0, 255, 0, 255, 0, 0];
+    /// let straight = CpuFrame::from_packed(data, FrameSize::new(2, 1), PixelFormat::RGBA8)?
+    ///     .with_alpha_mode(AlphaMode::Straight);
+    /// let linear = FrameInterpolationMode::Linear;
+    /// let scaled = straight.resize(FrameSize::new(4, 1), ResizeMode::Stretch, linear)?;
+    /// assert_eq!(scaled.alpha_mode(), AlphaMode::Straight);
+    /// assert_eq!(scaled.pixel(1, 0), &[255, 0, 0, 191]);
+    /// assert_eq!(scaled.pixel(2, 0), &[255, 0, 0, 64]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize(
+        &self,
+        size: FrameSize,
+        resize_mode: ResizeMode,
+        interpolation_mode: FrameInterpolationMode,
+    ) -> std::result::Result<CpuFrame, WgcError> {
+        check_resizable(self.pixel_format())?;
+        let transform = LetterboxTransform::new(self.size(), size, resize_mode);
+        if transform == LetterboxTransform::identity(self.size()) {
+            return Ok(self.clone().into_packed());
+        }
+        if self.alpha_mode() == AlphaMode::Straight {
+            return self
+                .convert_alpha(AlphaMode::Premultiplied)?
+                .resize(size, resize_mode, interpolation_mode)?
+                .convert_alpha(AlphaMode::Straight);
+        }
+        let pad_color = resize_mode.pad_color().unwrap_or(Color::TRANSPARENT);
+        let mut canvas = self.blank(transform.output_size, &self.encode_color(pad_color)?)?;
+        if !self.size().is_empty() && !transform.output_size.is_empty() {
+            draw(
+                self,
+                &mut canvas,
+                transform.source_rect(),
+                interpolation_mode,
+            );
+        }
+        Ok(canvas.with_transform(self.transform().then(&transform)))
+    }
+
+    /// Crops the frame to `region`, in frame coordinates.
+    ///
+    /// This is the CPU equivalent of [`WgcSettings::crop`]. The edges of `region` are
+    /// clamped to the frame as by [`Rect::clamp_to`], and the pixels are copied unchanged, so
+    /// any pixel format is supported. The [`transform`](CpuFrame::transform) of the result
+    /// maps the source of `self` to the cropped frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidCrop`] if `region` does not overlap the frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // A 4x2 frame whose pixels hold their column and row.
+    /// let data = (0..2).flat_map(|y| (0..4).flat_map(move |x| [x, y, 0, 255])).collect();
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(4, 2), PixelFormat::RGBA8)?;
+    ///
+    /// let cropped = frame.crop(Rect::new(1.0, 1.0, 2.0, 1.0))?;
+    /// assert_eq!(cropped.size(), FrameSize::new(2, 1));
+    /// assert_eq!(cropped.row(0), &[1, 1, 0, 255, 2, 1, 0, 255]);
+    /// assert_eq!(cropped.transform().unmap_point(Point::new(0.0, 0.0)), Point::new(1.0, 1.0));
+    ///
+    /// // A region overflowing the frame is clamped to it.
+    /// let cropped = frame.crop(Rect::new(3.0, -1.0, 5.0, 5.0))?;
+    /// assert_eq!(cropped.size(), FrameSize::new(1, 2));
+    ///
+    /// assert!(matches!(
+    ///     frame.crop(Rect::new(4.0, 0.0, 1.0, 1.0)),
+    ///     Err(WgcError::InvalidCrop { .. })
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn crop(&self, region: Rect) -> std::result::Result<CpuFrame, WgcError> {
+        let transform = LetterboxTransform::crop(self.size(), region)?;
+        let bytes_per_pixel = self.pixel_format().bytes_per_pixel() as usize;
+        let start = (-transform.offset_x) as usize * bytes_per_pixel;
+        let end = start + transform.output_size.width as usize * bytes_per_pixel;
+        let top = (-transform.offset_y) as u32;
+        let data = (top..top + transform.output_size.height)
+            .flat_map(|y| &self.row(y)[start..end])
+            .copied()
+            .collect();
+        Ok(
+            CpuFrame::from_packed(data, transform.output_size, self.pixel_format())?
+                .with_metadata_of(self)
+                .with_transform(self.transform().then(&transform)),
+        )
+    }
+
+    /// Returns the frame rotated or mirrored by `orientation`.
+    ///
+    /// Pixels are moved, not resampled, so every pixel format is supported and the
+    /// [`transform`](CpuFrame::transform) of the result maps pixel corners exactly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if the frame cannot be allocated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // A 3x2 frame of one byte per pixel, numbered in reading order.
+    /// let format = PixelFormat::gray8(ColorMatrix::Bt709);
+    /// let frame = CpuFrame::from_packed(vec![0, 1, 2, 3, 4, 5], FrameSize::new(3, 2), format)?;
+    ///
+    /// let rotated = frame.orient(Orientation::Rotate90)?;
+    /// assert_eq!(rotated.size(), FrameSize::new(2, 3));
+    /// assert_eq!(rotated.data(), &[3, 0, 4, 1, 5, 2]);
+    ///
+    /// let transposed = frame.orient(Orientation::Transpose)?;
+    /// assert_eq!(transposed.data(), &[0, 3, 1, 4, 2, 5]);
+    ///
+    /// // The centre of pixel 1 in the rotated frame is the centre of pixel 3 in the source.
+    /// let source = rotated.transform().unmap_point(Point::new(0.5, 0.5));
+    /// assert_eq!(source, Point::new(0.5, 1.5));
+    ///
+    /// // Orientations compose with crops and undo each other.
+    /// let cropped = frame.crop(Rect::new(1.0, 0.0, 2.0, 2.0))?.orient(Orientation::Rotate180)?;
+    /// assert_eq!(cropped.data(), &[5, 4, 2, 1]);
+    /// assert_eq!(cropped.transform().unmap_point(Point::new(0.0, 0.0)), Point::new(3.0, 2.0));
+    /// let restored = rotated.orient(Orientation::Rotate270)?;
+    /// assert_eq!(restored.data(), frame.data());
+    /// assert_eq!(restored.transform(), LetterboxTransform::identity(frame.size()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn orient(&self, orientation: Orientation) -> std::result::Result<CpuFrame, WgcError> {
+        let size = self.size();
+        let output = orientation.output_size(size);
+        let bytes_per_pixel = self.pixel_format().bytes_per_pixel() as usize;
+        let (swap, flip_x, flip_y) = orientation.flags();
+        let mut data = vec![0; output.pixel_count() * bytes_per_pixel];
+        for y in 0..size.height {
+            let row = self.row(y);
+            for (x, pixel) in (0..size.width).zip(row.chunks_exact(bytes_per_pixel)) {
+                let (u, v) = if swap { (y, x) } else { (x, y) };
+                let u = if flip_x { output.width - 1 - u } else { u };
+                let v = if flip_y { output.height - 1 - v } else { v };
+                let start = (v as usize * output.width as usize + u as usize) * bytes_per_pixel;
+                data[start..start + bytes_per_pixel].copy_from_slice(pixel);
+            }
+        }
+        Ok(CpuFrame::from_packed(data, output, self.pixel_format())?
+            .with_metadata_of(self)
+            .with_transform(
+                self.transform()
+                    .then(&LetterboxTransform::orient(size, orientation)),
+            ))
+    }
+
+    /// Returns the bytes of a pixel of `color` in the pixel format of the frame.
+    ///
+    /// Padding is stored in the alpha mode of the frame, which is premultiplied like the
+    /// pixels drawn by the GPU path unless converted.
+    pub(crate) fn encode_color(&self, color: Color) -> std::result::Result<Vec<u8>, WgcError> {
+        let Color { r, g, b, a } = match self.alpha_mode() {
+            AlphaMode::Premultiplied => color.premultiplied(),
+            AlphaMode::Straight => color,
+            AlphaMode::Opaque => Color {
+                a: 255,
+                ..color.premultiplied()
+            },
+        };
+        let pixel =
+            CpuFrame::from_packed(vec![r, g, b, a], FrameSize::new(1, 1), PixelFormat::RGBA8)?;
+        Ok(pixel.convert(self.pixel_format())?.into_data())
+    }
+
+    /// Creates a tightly packed frame of the given size, with the same pixel format and
+    /// metadata as `self`, filled with `color`.
+    fn blank(&self, size: FrameSize, color: &[u8]) -> std::result::Result<CpuFrame, WgcError> {
+        Ok(
+            CpuFrame::from_packed(color.repeat(size.pixel_count()), size, self.pixel_format())?
+                .with_metadata_of(self),
+        )
+    }
+}
+
+fn check_resizable(pixel_format: PixelFormat) -> std::result::Result<(), WgcError> {
+    if pixel_format == PixelFormat::RGBA8 || pixel_format == PixelFormat::BGRA8 {
+        Ok(())
+    } else {
+        Err(WgcError::UnsupportedPixelFormat(pixel_format))
+    }
+}
+
+/// The source pixels contributing to one destination pixel along one axis.
+struct Taps {
+    /// The destination pixel index.
+    dst: usize,
+    /// The fraction of the destination pixel covered by the destination rectangle.
+    coverage: f32,
+    /// Pairs of source pixel index and weight. The weights sum to 1.
+    weights: Vec<(usize, f32)>,
+}
+
+/// Computes the taps of every destination pixel along one axis.
+///
+/// The destination range `[dst_start, dst_start + dst_len)` is mapped onto the source range
+/// `[0, src_len)`, and only destination pixels in `[0, dst_limit)` are produced.
+fn axis_taps(
+    src_len: usize,
+    dst_start: f32,
+    dst_len: f32,
+    dst_limit: usize,
+    interpolation_mode: FrameInterpolationMode,
+) -> Vec<Taps> {
+    let dst_end = dst_start + dst_len;
+    let scale = dst_len / src_len as f32;
+    let first = dst_start.floor().max(0.0) as usize;
+    let last = (dst_end.ceil() as usize).min(dst_limit);
+    (first..last)
+        .filter_map(|dst| {
+            let coverage = (dst_end.min(dst as f32 + 1.0) - dst_start.max(dst as f32)).max(0.0);
+            if coverage <= 0.0 {
+                return None;
+            }
+            // The continuous source coordinate of the destination pixel's center.
+            let center = (dst as f32 + 0.5 - dst_start) / scale;
+            let weights = match interpolation_mode {
+                FrameInterpolationMode::NearestNeighbor => nearest_weights(center, src_len),
+                FrameInterpolationMode::Linear => linear_weights(center, src_len),
+                FrameInterpolationMode::Cubic => cubic_weights(center, src_len),
+                FrameInterpolationMode::MultiSampleLinear => {
+                    // Two samples a quarter of a destination pixel on either side of the
+                    // center; combined with the other axis, this gives four samples.
+                    let offset = 0.25 / scale;
+                    let mut weights = linear_weights(center - offset, src_len);
+                    weights.extend(linear_weights(center + offset, src_len));
+                    weights.iter_mut().for_each(|(_, weight)| *weight /= 2.0);
+                    weights
+                }
+                FrameInterpolationMode::HighQualityCubic if scale < 1.0 => {
+                    let footprint_start = (dst as f32 - dst_start) / scale;
+                    area_weights(footprint_start, footprint_start + 1.0 / scale, src_len)
+                }
+                FrameInterpolationMode::HighQualityCubic => cubic_weights(center, src_len),
+            };
+            Some(Taps {
+                dst,
+                coverage,
+                weights,
+            })
+        })
+        .collect()
+}
+
+fn clamp_index(index: isize, len: usize) -> usize {
+    index.clamp(0, len as isize - 1) as usize
+}
+
+fn nearest_weights(center: f32, len: usize) -> Vec<(usize, f32)> {
+    vec![(clamp_index(center.floor() as isize, len), 1.0)]
+}
+
+fn linear_weights(center: f32, len: usize) -> Vec<(usize, f32)> {
+    let position = center - 0.5;
+    let index = position.floor();
+    let t = position - index;
+    let index = index as isize;
+    vec![
+        (clamp_index(index, len), 1.0 - t),
+        (clamp_index(index + 1, len), t),
+    ]
+}
+
+fn cubic_weights(center: f32, len: usize) -> Vec<(usize, f32)> {
+    // Catmull-Rom spline.
+    fn kernel(x: f32) -> f32 {
+        let x = x.abs();
+        if x < 1.0 {
+            1.5 * x * x * x - 2.5 * x * x + 1.0
+        } else if x < 2.0 {
+            -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+        } else {
+            0.0
+        }
+    }
+    let position = center - 0.5;
+    let index = position.floor();
+    let t = position - index;
+    let index = index as isize;
+    (-1..=2)
+        .map(|offset| (clamp_index(index + offset, len), kernel(t - offset as f32)))
+        .collect()
+}
+
+/// Averages the source pixels overlapping `[start, end)`, weighted by their overlap.
+fn area_weights(start: f32, end: f32, len: usize) -> Vec<(usize, f32)> {
+    let start = start.max(0.0);
+    let end = end.min(len as f32);
+    if end <= start {
+        return nearest_weights(start, len);
+    }
+    let total = end - start;
+    (start.floor() as usize..(end.ceil() as usize).min(len))
+        .map(|index| {
+            let overlap = end.min(index as f32 + 1.0) - start.max(index as f32);
+            (index, overlap / total)
+        })
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect()
+}
+
+/// Draws `src` into `dst` at `dest_rect`, blending the partially covered pixels on the
+/// edges of the rectangle with the existing content.
+fn draw(
+    src: &CpuFrame,
+    dst: &mut CpuFrame,
+    dest_rect: Rect,
+    interpolation_mode: FrameInterpolationMode,
+) {
+    let Rect {
+        x: left,
+        y: top,
+        width,
+        height,
+    } = dest_rect;
+    let channels = src.pixel_format().bytes_per_pixel() as usize;
+    let src_size = src.size();
+    let dst_size = dst.size();
+    let columns = axis_taps(
+        src_size.width as usize,
+        left,
+        width,
+        dst_size.width as usize,
+        interpolation_mode,
+    );
+    let rows = axis_taps(
+        src_size.height as usize,
+        top,
+        height,
+        dst_size.height as usize,
+        interpolation_mode,
+    );
+
+    let mut value = vec![0.0f32; channels];
+    for row in &rows {
+        for column in &columns {
+            value.fill(0.0);
+            for &(sy, wy) in &row.weights {
+                let src_row = src.row(sy as u32);
+                for &(sx, wx) in &column.weights {
+                    let weight = wx * wy;
+                    let pixel = &src_row[sx * channels..(sx + 1) * channels];
+                    for (sum, &channel) in value.iter_mut().zip(pixel) {
+                        *sum += weight * channel as f32;
+                    }
+                }
+            }
+            let coverage = row.coverage * column.coverage;
+            let pixel = dst.pixel_mut(column.dst as u32, row.dst as u32);
+            for (out, &sum) in pixel.iter_mut().zip(&value) {
+                let blended = sum * coverage + *out as f32 * (1.0 - coverage);
+                *out = blended.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::time::Duration;
-use windows::{Graphics::DirectX::DirectXPixelFormat, Win32::Graphics::Dxgi::Common::DXGI_FORMAT};
+
+pub use crate::common::PixelFormat;
+use crate::*;
 
 /// Configuration settings for Windows Graphics Capture (WGC).
 ///
@@ -109,6 +111,138 @@ pub struct WgcSettings {
     /// Defaults to [`FrameInterpolationMode::Linear`].
     #[default(FrameInterpolationMode::Linear)]
     pub frame_interpolation_mode: FrameInterpolationMode,
+    /// How frames are fitted into the desired output size.
+    ///
+    /// This setting is used when a desired size is passed to
+    /// [`read_pixels`](crate::CaptureFrame::read_pixels) and differs from the frame size.
+    ///
+    /// Defaults to [`ResizeMode::Fit`], centered with 50% gray padding.
+    #[default(ResizeMode::default())]
+    pub resize_mode: ResizeMode,
+    /// The region of interest, in frame coordinates.
+    ///
+    /// Set this field to `Some(rect)` to read only that part of each frame. The frame is
+    /// cropped before it is resized, and [`read_pixels`](crate::CaptureFrame::read_pixels)
+    /// without a desired size returns the cropped area at its native size. The region is
+    /// clamped to the current frame size as by [`Rect::clamp_to`], so it follows the
+    /// capture item when it shrinks; reads fail with [`WgcError::InvalidCrop`] while the
+    /// region lies entirely outside the frame.
+    ///
+    /// Defaults to `None`, which reads the whole frame.
+    #[default(None)]
+    pub crop: Option<Rect>,
+    /// The rotation or reflection applied to each frame, for example to turn the capture
+    /// of a monitor mounted in portrait upright.
+    ///
+    /// The frame is reoriented after it is cropped and before it is resized, so
+    /// [`crop`](#structfield.crop) is given in the coordinates of the captured frame and a
+    /// desired size in those of the reoriented frame. The whole read is described by
+    /// [`CaptureFrame::output_transform`](crate::CaptureFrame::output_transform).
+    ///
+    /// Defaults to [`Orientation::Identity`].
+    #[default(Orientation::Identity)]
+    pub orientation: Orientation,
+    /// How the alpha channel of the returned pixels is interpreted.
+    ///
+    /// Direct2D reads frames with premultiplied alpha. Other modes are produced by a CPU
+    /// pass after the pixels are read; the mode of a frame is reported by
+    /// [`CaptureFrame::alpha_mode`](crate::CaptureFrame::alpha_mode) and
+    /// [`CpuFrame::alpha_mode`](crate::CpuFrame::alpha_mode).
+    ///
+    /// Defaults to [`AlphaMode::Premultiplied`].
+    #[default(AlphaMode::Premultiplied)]
+    pub alpha_mode: AlphaMode,
+    /// The options of the latency and throughput metrics collected by the source.
+    ///
+    /// Defaults to a 10-second window, summarized every 10 seconds with the `tracing`
+    /// feature.
+    #[default(MetricsOptions::default())]
+    pub metrics: MetricsOptions,
+}
+
+/// Specifies how a frame is fitted into a desired output size.
+///
+/// The geometry of each mode is described by [`LetterboxTransform::new`](crate::LetterboxTransform::new),
+/// which is shared by the GPU and CPU read paths.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// // Letterboxing as expected by YOLO-family models.
+/// let settings = WgcSettings {
+///     resize_mode: ResizeMode::Fit {
+///         pad_color: Color::gray(114),
+///         alignment: Alignment::Center,
+///     },
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scales the frame to fit within the desired size while maintaining its aspect ratio,
+    /// and pads the remaining area (letterboxing).
+    Fit {
+        /// The color of the padding. Use [`Color::TRANSPARENT`](crate::Color::TRANSPARENT)
+        /// for transparent padding.
+        pad_color: Color,
+        /// The position of the frame within the padding.
+        alignment: Alignment,
+    },
+
+    /// Scales the frame to cover the desired size while maintaining its aspect ratio, and
+    /// crops the parts that overflow.
+    Fill {
+        /// The part of the frame that is kept.
+        alignment: Alignment,
+    },
+
+    /// Scales the frame to exactly the desired size, without maintaining its aspect ratio.
+    Stretch,
+
+    /// Scales the frame to fit within the desired size while maintaining its aspect ratio,
+    /// without padding.
+    ///
+    /// The output size is derived from the frame size, and may be smaller than the desired
+    /// size along one axis.
+    ScaleOnly,
+}
+
+/// Specifies how the alpha channel of pixel data is interpreted.
+///
+/// Alpha only varies for captures of windows with transparent areas; frames of monitors and
+/// opaque windows are identical in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// The color channels are multiplied by alpha, as read by Direct2D. Suitable for
+    /// compositing and filtering.
+    Premultiplied,
+    /// The color channels are independent of alpha, as expected by most image file formats.
+    /// Colors of fully transparent pixels are lost and read as black.
+    Straight,
+    /// Alpha is set to fully opaque, which shows transparent areas composited over black.
+    Opaque,
+}
+
+impl Default for ResizeMode {
+    /// Returns [`ResizeMode::Fit`], centered with 50% gray padding.
+    fn default() -> Self {
+        ResizeMode::Fit {
+            pad_color: Color::GRAY,
+            alignment: Alignment::Center,
+        }
+    }
+}
+
+impl ResizeMode {
+    /// Returns the color of the padding, or `None` if the mode does not pad the frame.
+    pub fn pad_color(&self) -> Option<Color> {
+        match self {
+            ResizeMode::Fit { pad_color, .. } => Some(*pad_color),
+            _ => None,
+        }
+    }
 }
 
 /// Specifies the interpolation method used for scaling frames.
@@ -148,6 +282,7 @@ pub enum FrameInterpolationMode {
     HighQualityCubic,
 }
 
+#[cfg(windows)]
 impl From<FrameInterpolationMode> for windows::Win32::Graphics::Direct2D::D2D1_INTERPOLATION_MODE {
     fn from(value: FrameInterpolationMode) -> Self {
         use windows::Win32::Graphics::Direct2D::*;
@@ -162,80 +297,3 @@ impl From<FrameInterpolationMode> for windows::Win32::Graphics::Direct2D::D2D1_I
         }
     }
 }
-
-/// Specifies the pixel format for capture output.
-///
-/// Contains the underlying DirectX pixel format and the number of bytes per pixel.
-/// Use the predefined constants [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`]
-/// for common formats, or create a custom format with [`PixelFormat::new`].
-#[derive(Debug, Clone, Copy)]
-pub struct PixelFormat {
-    /// The underlying DirectX pixel format.
-    format: DirectXPixelFormat,
-    /// The number of bytes required to store a single pixel.
-    bytes_per_pixel: u32,
-}
-
-impl PixelFormat {
-    /// Creates a new custom pixel format.
-    ///
-    /// # Arguments
-    ///
-    /// * `format` - The underlying DirectX pixel format.
-    /// * `bytes_per_pixel` - The number of bytes required to store a single pixel.
-    ///
-    /// # Example
-    ///
-    /// ```
-    /// use wgc::settings::PixelFormat;
-    /// use windows::Graphics::DirectX::DirectXPixelFormat;
-    ///
-    /// let custom_format = PixelFormat::new(
-    ///     DirectXPixelFormat::R8G8B8A8UIntNormalized,
-    ///     4,
-    /// );
-    /// ```
-    pub fn new(format: DirectXPixelFormat, bytes_per_pixel: u32) -> Self {
-        Self {
-            format,
-            bytes_per_pixel,
-        }
-    }
-
-    /// RGBA 8-bit per channel format (32 bits per pixel).
-    ///
-    /// Each pixel consists of 4 bytes: Red, Green, Blue, and Alpha, in that order.
-    /// This is a common format for capture output.
-    pub const RGBA8: Self = Self {
-        format: DirectXPixelFormat::R8G8B8A8UIntNormalized,
-        bytes_per_pixel: 4,
-    };
-
-    /// BGRA 8-bit per channel format (32 bits per pixel).
-    ///
-    /// Each pixel consists of 4 bytes: Blue, Green, Red, and Alpha, in that order.
-    /// This format is commonly used in Windows GDI and DirectComposition.
-    pub const BGRA8: Self = Self {
-        format: DirectXPixelFormat::B8G8R8A8UIntNormalized,
-        bytes_per_pixel: 4,
-    };
-
-    pub fn format(&self) -> DirectXPixelFormat {
-        self.format
-    }
-    pub fn bytes_per_pixel(&self) -> u32 {
-        self.bytes_per_pixel
-    }
-}
-
-impl From<PixelFormat> for DirectXPixelFormat {
-    fn from(pixel_format: PixelFormat) -> Self {
-        pixel_format.format
-    }
-}
-
-impl From<PixelFormat> for DXGI_FORMAT {
-    fn from(pixel_format: PixelFormat) -> Self {
-        Self(pixel_format.format.0)
-    }
-}
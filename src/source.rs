@@ -0,0 +1,581 @@
+//! Platform-neutral abstractions over capture backends.
+//!
+//! [`Wgc`](crate::Wgc) is one implementation of [`CaptureSource`]. Code that only needs
+//! frame sizes, timestamps and pixels should be written against these traits so it can be
+//! exercised with any source, including on platforms without Windows Graphics Capture.
+
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// A single captured frame.
+///
+/// Besides describing the frame and reading its pixels, backends only have to number
+/// their frames and count them; the other metadata and ways of reading the frame have
+/// default implementations.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use wgc::*;
+///
+/// /// A frame of another capture backend, already in memory.
+/// struct Still {
+///     pixels: Vec<u8>,
+///     size: FrameSize,
+///     captured_at: Instant,
+/// }
+///
+/// impl CaptureFrame for Still {
+///     fn size(&self) -> Result<FrameSize, WgcError> {
+///         Ok(self.size)
+///     }
+///
+///     fn pixel_format(&self) -> PixelFormat {
+///         PixelFormat::RGBA8
+///     }
+///
+///     fn render_time(&self) -> Result<Instant, WgcError> {
+///         Ok(self.captured_at)
+///     }
+///
+///     fn sequence(&self) -> u64 {
+///         0
+///     }
+///
+///     fn counters(&self) -> FrameCounters {
+///         FrameCounters { delivered: 1, ..Default::default() }
+///     }
+///
+///     fn read_pixels(&self, desired_size: Option<FrameSize>) -> Result<Vec<u8>, WgcError> {
+///         let frame = CpuFrame::from_packed(self.pixels.clone(), self.size, PixelFormat::RGBA8)?;
+///         match desired_size.filter(|&size| size != self.size) {
+///             Some(size) => Ok(frame
+///                 .resize(size, ResizeMode::default(), FrameInterpolationMode::Linear)?
+///                 .into_data()),
+///             None => Ok(frame.into_data()),
+///         }
+///     }
+/// }
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let still = Still {
+///     pixels: [0, 0, 255, 255].repeat(4 * 2),
+///     size: FrameSize::new(4, 2),
+///     captured_at: Instant::now(),
+/// };
+/// assert_eq!(still.alpha_mode(), AlphaMode::Premultiplied);
+/// assert_eq!(still.output_transform(None)?, LetterboxTransform::identity(still.size));
+/// let thumbnail = still.to_cpu_frame(Some(FrameSize::new(2, 2)))?;
+/// assert_eq!(thumbnail.transform().content_rect, Rect::new(0.0, 0.5, 2.0, 1.0));
+///
+/// let outputs = still.read_outputs(&[OutputSpec {
+///     pixel_format: PixelFormat::BGRA8,
+///     ..Default::default()
+/// }])?;
+/// assert_eq!(outputs[0].pixels[..4], [255, 0, 0, 255]);
+/// # Ok(())
+/// # }
+/// ```
+pub trait CaptureFrame {
+    /// Returns the size of the frame.
+    fn size(&self) -> std::result::Result<FrameSize, WgcError>;
+
+    /// Returns the pixel format of the data returned by [`read_pixels`](Self::read_pixels).
+    fn pixel_format(&self) -> PixelFormat;
+
+    /// Returns the time at which the frame was rendered.
+    fn render_time(&self) -> std::result::Result<Instant, WgcError>;
+
+    /// Returns the time at which the frame was rendered, as the duration since the system
+    /// booted on the performance-counter [`Clock`].
+    ///
+    /// Unlike [`render_time`](Self::render_time), the timestamp is the exact value reported
+    /// by the source, so it can be serialized and compared with timestamps of other
+    /// processes. [`Clock::instant_at`] and [`Clock::system_time_at`] convert it.
+    ///
+    /// The default implementation derives it from [`render_time`](Self::render_time) on
+    /// [`QpcClock`], so it is only as exact as that conversion. Sources that know the
+    /// timestamp should return it.
+    fn timestamp(&self) -> std::result::Result<Duration, WgcError> {
+        let age = Instant::now().saturating_duration_since(self.render_time()?);
+        Ok(QpcClock.elapsed_since_boot().saturating_sub(age))
+    }
+
+    /// Returns the sequence number of the frame, that is, the number of frames delivered
+    /// by its source before this one.
+    fn sequence(&self) -> u64;
+
+    /// Returns the counters of its source as of the delivery of this frame, which is
+    /// included in [`delivered`](FrameCounters::delivered).
+    fn counters(&self) -> FrameCounters;
+
+    /// Returns how the alpha channel of the data returned by
+    /// [`read_pixels`](Self::read_pixels) is interpreted.
+    ///
+    /// Defaults to [`AlphaMode::Premultiplied`], the alpha mode of the capture surface.
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Premultiplied
+    }
+
+    /// Returns the transform applied by [`read_pixels`](Self::read_pixels) for the given
+    /// desired size.
+    ///
+    /// Its [`output_size`](LetterboxTransform::output_size) is the size of the pixel data.
+    ///
+    /// The default implementation assumes that the source neither crops nor reorients the
+    /// frame, and resizes it with the default [`ResizeMode`].
+    fn output_transform(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<LetterboxTransform, WgcError> {
+        let size = self.size()?;
+        Ok(match desired_size.filter(|&desired| desired != size) {
+            Some(desired) => LetterboxTransform::new(size, desired, ResizeMode::default()),
+            None => LetterboxTransform::identity(size),
+        })
+    }
+
+    /// Reads the pixel data of the frame.
+    ///
+    /// If the source's [`WgcSettings::crop`] is set, the frame is first cropped to that
+    /// region, which then takes the place of the frame's native size below. The frame is
+    /// then reoriented by [`WgcSettings::orientation`], which swaps its width and height
+    /// for quarter turns.
+    ///
+    /// If a `desired_size` is provided and differs from the frame's native size, the image
+    /// is resized according to the source's [`ResizeMode`]; by default, it is scaled to fit
+    /// within the desired dimensions while maintaining aspect ratio and letterboxed to fill
+    /// the desired size.
+    ///
+    /// The returned buffer is tightly packed and its length is the
+    /// [`buffer_len`](PixelFormat::buffer_len) of the
+    /// [`output_size`](LetterboxTransform::output_size) of
+    /// [`output_transform`](Self::output_transform), which is
+    /// `width * height * bytes_per_pixel` for formats other than YCbCr.
+    fn read_pixels(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<Vec<u8>, WgcError>;
+
+    /// Reads several outputs from the frame, one for each spec, in order.
+    ///
+    /// The capture surface is acquired once and every output is produced from it, which
+    /// is cheaper than reading the frame once per output. Each output is cropped,
+    /// reoriented, resized and converted independently of the source's settings; the
+    /// formats are converted from the surface format of the source's
+    /// [`WgcSettings::pixel_format`].
+    ///
+    /// # Errors
+    ///
+    /// Fails as a whole if any output fails, for example with [`WgcError::InvalidCrop`] or
+    /// [`WgcError::UnsupportedPixelFormat`].
+    ///
+    /// The default implementation reads the frame once with
+    /// [`to_cpu_frame`](Self::to_cpu_frame) and produces each output with
+    /// [`CpuFrame::output`], so the outputs are taken from the frame as
+    /// [`read_pixels`](Self::read_pixels) returns it, and YCbCr pixel formats are not
+    /// supported. [`Frame`](crate::Frame) reads its outputs from the capture surface
+    /// instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let item = SyntheticItem {
+    ///     size: FrameSize::new(1280, 720),
+    ///     ..Default::default()
+    /// };
+    /// let frame = SyntheticSource::new(item, WgcSettings::default())?.next().unwrap()?;
+    ///
+    /// let outputs = frame.read_outputs(&[
+    ///     // A letterboxed detector input.
+    ///     OutputSpec {
+    ///         size: Some(FrameSize::new(640, 640)),
+    ///         pixel_format: PixelFormat::RGB24,
+    ///         ..Default::default()
+    ///     },
+    ///     // A square crop for a classifier.
+    ///     OutputSpec {
+    ///         size: Some(FrameSize::new(224, 224)),
+    ///         crop: Some(Rect::new(280.0, 0.0, 720.0, 720.0)),
+    ///         ..Default::default()
+    ///     },
+    ///     // The full frame for a recording.
+    ///     OutputSpec {
+    ///         pixel_format: PixelFormat::BGRA8,
+    ///         ..Default::default()
+    ///     },
+    /// ])?;
+    /// assert_eq!(outputs[0].pixels.len(), 640 * 640 * 3);
+    /// assert_eq!(outputs[0].transform.content_rect, Rect::new(0.0, 140.0, 640.0, 360.0));
+    /// assert_eq!(outputs[1].size(), FrameSize::new(224, 224));
+    /// assert_eq!(outputs[2].size(), FrameSize::new(1280, 720));
+    ///
+    /// // Each output equals the pixels read with the same settings.
+    /// let settings = WgcSettings { pixel_format: PixelFormat::BGRA8, ..Default::default() };
+    /// let item = SyntheticItem { size: FrameSize::new(1280, 720), ..Default::default() };
+    /// let frame = SyntheticSource::new(item, settings)?.next().unwrap()?;
+    /// assert_eq!(frame.read_pixels(None)?, outputs[2].pixels);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn read_outputs(
+        &self,
+        specs: &[OutputSpec],
+    ) -> std::result::Result<Vec<FrameOutput>, WgcError> {
+        let frame = self.to_cpu_frame(None)?;
+        specs.iter().map(|spec| frame.output(spec)).collect()
+    }
+
+    /// Reads the pixel data of the frame into `buffer`, writing rows `stride` bytes apart.
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels). Bytes
+    /// between the end of a row and the start of the next are left untouched, as is the
+    /// rest of the buffer after the last row.
+    ///
+    /// The default implementation copies the data returned by
+    /// [`read_pixels`](Self::read_pixels). [`Frame`](crate::Frame) converts the rows
+    /// directly into the buffer without allocating when its pixel format has a
+    /// [`RowKernel`](crate::kernels::RowKernel) from its surface format (all but
+    /// [`PixelFormat::R10G10B10A2`] and the YCbCr formats) and the alpha mode is
+    /// [`AlphaMode::Premultiplied`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidStride`] if `stride` is smaller than a row of pixels,
+    /// [`WgcError::BufferTooSmall`] if `buffer` cannot hold all rows of the
+    /// [`output_size`](LetterboxTransform::output_size) of
+    /// [`output_transform`](Self::output_transform), and
+    /// [`WgcError::UnsupportedPixelFormat`] for the YCbCr formats, whose planes have no
+    /// single stride.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let item = SyntheticItem {
+    ///     pattern: TestPattern::Solid([255, 0, 0, 255]),
+    ///     size: FrameSize::new(2, 2),
+    ///     ..Default::default()
+    /// };
+    /// let frame = SyntheticSource::new(item, WgcSettings::default())?.next().unwrap()?;
+    ///
+    /// // Rows of 8 bytes, padded to 12.
+    /// let mut buffer = [0; 20];
+    /// frame.read_pixels_into(None, &mut buffer, 12)?;
+    /// assert_eq!(&buffer[..12], &[255, 0, 0, 255, 255, 0, 0, 255, 0, 0, 0, 0]);
+    /// assert_eq!(&buffer[12..], &[255, 0, 0, 255, 255, 0, 0, 255]);
+    ///
+    /// assert!(matches!(
+    ///     frame.read_pixels_into(None, &mut buffer, 16),
+    ///     Err(WgcError::BufferTooSmall { expected: 24, actual: 20 })
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn read_pixels_into(
+        &self,
+        desired_size: Option<FrameSize>,
+        buffer: &mut [u8],
+        stride: usize,
+    ) -> std::result::Result<(), WgcError> {
+        let size = self.output_transform(desired_size)?.output_size;
+        let pixel_format = self.pixel_format();
+        check_buffer(pixel_format, size, buffer.len(), stride)?;
+        let data = self.read_pixels(desired_size)?;
+        let row_bytes = size.width as usize * pixel_format.bytes_per_pixel() as usize;
+        copy_rows(
+            &data,
+            row_bytes,
+            buffer,
+            stride,
+            row_bytes,
+            size.height as usize,
+        );
+        Ok(())
+    }
+
+    /// Reads the tightly packed pixel data of the frame into a buffer taken from `pool`.
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels). The
+    /// buffer returns to the pool when dropped, so a capture loop that drops each buffer
+    /// before reading the next frame settles on a single allocation.
+    fn read_pixels_pooled(
+        &self,
+        desired_size: Option<FrameSize>,
+        pool: &BufferPool,
+    ) -> std::result::Result<PooledBuffer, WgcError> {
+        let size = self.output_transform(desired_size)?.output_size;
+        let pixel_format = self.pixel_format();
+        let mut buffer = pool.get(size, pixel_format);
+        if pixel_format.yuv_layout().is_some() {
+            // Planar data has no single stride, so it is converted first and then copied.
+            buffer.copy_from_slice(&self.read_pixels(desired_size)?);
+        } else {
+            let stride = size.width as usize * pixel_format.bytes_per_pixel() as usize;
+            self.read_pixels_into(desired_size, &mut buffer, stride)?;
+        }
+        Ok(buffer)
+    }
+
+    /// Reads the pixel data of the frame into an owned [`CpuFrame`], along with its
+    /// pixel format, alpha mode, render time, sequence number and letterbox transform.
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] for the YCbCr formats, which are read
+    /// with [`to_yuv_frame`](Self::to_yuv_frame) instead.
+    fn to_cpu_frame(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<CpuFrame, WgcError> {
+        let transform = self.output_transform(desired_size)?;
+        let data = self.read_pixels(desired_size)?;
+        Ok(
+            CpuFrame::from_packed(data, transform.output_size, self.pixel_format())?
+                .with_render_time(self.render_time()?)
+                .with_timestamp(self.timestamp()?)
+                .with_sequence(self.sequence())
+                .with_transform(transform)
+                .with_alpha_mode(self.alpha_mode()),
+        )
+    }
+
+    /// Reads the pixel data of the frame into an owned [`YuvFrame`], along with its render
+    /// time, sequence number and letterbox transform.
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] unless the pixel format is a YCbCr
+    /// format.
+    fn to_yuv_frame(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<YuvFrame, WgcError> {
+        let transform = self.output_transform(desired_size)?;
+        let data = self.read_pixels(desired_size)?;
+        Ok(
+            YuvFrame::from_raw(data, transform.output_size, self.pixel_format())?
+                .with_render_time(self.render_time()?)
+                .with_timestamp(self.timestamp()?)
+                .with_sequence(self.sequence())
+                .with_transform(transform),
+        )
+    }
+
+    /// Reads the frame and converts it to a normalized floating-point tensor, as by
+    /// [`CpuFrame::to_tensor`].
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels); pass
+    /// the input size of the model to letterbox the frame into it.
+    fn to_tensor<T: TensorElement>(
+        &self,
+        desired_size: Option<FrameSize>,
+        options: &TensorOptions,
+    ) -> std::result::Result<Tensor<T>, WgcError>
+    where
+        Self: Sized,
+    {
+        self.to_cpu_frame(desired_size)?.to_tensor(options)
+    }
+
+    /// Reads the frame and returns an image pyramid of up to `levels` frames, as by
+    /// [`CpuFrame::pyramid`].
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels).
+    fn to_pyramid(
+        &self,
+        desired_size: Option<FrameSize>,
+        levels: usize,
+        filter: PyramidFilter,
+    ) -> std::result::Result<Vec<CpuFrame>, WgcError> {
+        self.to_cpu_frame(desired_size)?.pyramid(levels, filter)
+    }
+
+    /// Reads the frame and splits it into overlapping tiles, as by [`CpuFrame::tiles`].
+    ///
+    /// `desired_size` has the same meaning as in [`read_pixels`](Self::read_pixels); pass
+    /// `None` to tile the frame at full resolution. The transform of each tile maps back to
+    /// the coordinates of the captured frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let item = SyntheticItem {
+    ///     size: FrameSize::new(1920, 1080),
+    ///     ..Default::default()
+    /// };
+    /// let frame = SyntheticSource::new(item, WgcSettings::default())?.next().unwrap()?;
+    ///
+    /// // Full-resolution tiles for small objects, plus an overview for large ones.
+    /// let tiles = frame.to_tiles(None, &TileOptions::default())?;
+    /// assert_eq!(tiles.len(), 4 * 2);
+    /// let overview = frame.to_pyramid(None, 3, PyramidFilter::Gaussian)?.pop().unwrap();
+    /// assert_eq!(overview.size(), FrameSize::new(480, 270));
+    ///
+    /// let last = &tiles[7];
+    /// assert_eq!((last.x, last.y), (1280, 440));
+    /// let detection = last.frame.transform().unmap_rect(Rect::new(600.0, 600.0, 40.0, 40.0));
+    /// assert_eq!(detection, Rect::new(1880.0, 1040.0, 40.0, 40.0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn to_tiles(
+        &self,
+        desired_size: Option<FrameSize>,
+        options: &TileOptions,
+    ) -> std::result::Result<Vec<Tile>, WgcError> {
+        self.to_cpu_frame(desired_size)?.tiles(options)
+    }
+}
+
+/// Returns the transform of a read of a frame of the given size: the frame is cropped to
+/// [`WgcSettings::crop`], reoriented by [`WgcSettings::orientation`], then resized to
+/// `desired_size` according to [`WgcSettings::resize_mode`]. Without a desired size, the
+/// reoriented area keeps its size.
+pub(crate) fn read_transform(
+    frame_size: FrameSize,
+    desired_size: Option<FrameSize>,
+    settings: &WgcSettings,
+) -> std::result::Result<LetterboxTransform, WgcError> {
+    OutputSpec::from_settings(settings, desired_size).transform(frame_size)
+}
+
+/// A stream of captured frames.
+///
+/// A capture source is an [`Iterator`] that blocks until the next frame is available and
+/// returns `None` once the source is closed.
+///
+/// # Example
+///
+/// ```
+/// use wgc::{CaptureFrame, CaptureSource, WgcError};
+///
+/// fn total_pixels<S: CaptureSource>(source: S) -> Result<usize, WgcError> {
+///     let mut total = 0;
+///     for frame in source {
+///         total += frame?.size()?.pixel_count();
+///     }
+///     Ok(total)
+/// }
+/// ```
+pub trait CaptureSource:
+    Iterator<Item = std::result::Result<<Self as CaptureSource>::Frame, WgcError>>
+{
+    /// The type of frame yielded by this source.
+    type Frame: CaptureFrame;
+
+    /// Returns the number of frames delivered, dropped and skipped so far.
+    fn counters(&self) -> FrameCounters;
+}
+
+/// Counts the frames of a [`CaptureSource`], to check that a recording is complete.
+///
+/// Every frame produced by the content is either delivered, dropped because the content
+/// size changed, or skipped because the frame queue was full, so
+/// [`total`](Self::total) is the number of frames produced so far.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let item = SyntheticItem {
+///     size_changes: vec![(2, FrameSize::new(320, 240))],
+///     frame_count: Some(5),
+///     ..Default::default()
+/// };
+/// let mut source = SyntheticSource::new(item, WgcSettings::default())?;
+/// let frames = source.by_ref().collect::<Result<Vec<_>, _>>()?;
+///
+/// let counters = source.counters();
+/// assert_eq!(counters.delivered, 4);
+/// assert_eq!(counters.dropped_for_resize, 1);
+/// assert_eq!(counters.total(), 5);
+///
+/// // Each frame carries the counters as of its delivery.
+/// assert_eq!(frames[2].counters().dropped(), 1);
+/// assert_eq!(frames[2].counters().delivered, frames[2].sequence() + 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FrameCounters {
+    /// The number of frames delivered by the source.
+    pub delivered: u64,
+    /// The number of frames dropped because the content size changed, while the frame pool
+    /// was recreated.
+    pub dropped_for_resize: u64,
+    /// The number of frames known to be skipped because the frame queue was full.
+    ///
+    /// A [`SyntheticSource`] counts them exactly. Windows Graphics Capture does not report
+    /// them, so this is always 0 for [`Wgc`](crate::Wgc), which estimates them in
+    /// [`estimated_skipped_for_overflow`](Self::estimated_skipped_for_overflow) instead.
+    pub skipped_for_overflow: u64,
+    /// The estimated number of frames skipped because the frame queue was full, for
+    /// sources that cannot count them.
+    ///
+    /// [`Wgc`](crate::Wgc) infers them from the gaps between frame timestamps after the
+    /// consumer fell behind, so a pause in the repaints of the content can be counted as
+    /// skipped frames. This is a hint for tuning the queue length, not a count to validate
+    /// a dataset with, and it is left out of [`dropped`](Self::dropped) and
+    /// [`total`](Self::total).
+    pub estimated_skipped_for_overflow: u64,
+}
+
+impl FrameCounters {
+    /// Returns the number of frames known not to be delivered.
+    pub fn dropped(&self) -> u64 {
+        self.dropped_for_resize + self.skipped_for_overflow
+    }
+
+    /// Returns the number of frames known to be produced by the content, delivered or not.
+    pub fn total(&self) -> u64 {
+        self.delivered + self.dropped()
+    }
+}
+
+/// Tracks the size of a frame pool's buffers.
+///
+/// Frame pools allocate their buffers for a fixed size. When the content size of an
+/// arriving frame no longer matches, the frame is dropped and the pool is recreated with
+/// the new size, so that subsequent frames are delivered at their full resolution.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FramePoolState {
+    buffer_size: FrameSize,
+}
+
+impl FramePoolState {
+    pub(crate) fn new(buffer_size: FrameSize) -> Self {
+        Self { buffer_size }
+    }
+
+    pub(crate) fn buffer_size(&self) -> FrameSize {
+        self.buffer_size
+    }
+
+    /// Returns `true` if a frame with the given content size must be dropped because the
+    /// pool has to be recreated first.
+    pub(crate) fn needs_recreate(&self, content_size: FrameSize) -> bool {
+        content_size != self.buffer_size
+    }
+
+    /// Records that the pool has been recreated with the given size.
+    pub(crate) fn recreated(&mut self, buffer_size: FrameSize) {
+        self.buffer_size = buffer_size;
+    }
+}
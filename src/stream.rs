@@ -0,0 +1,376 @@
+//! An asynchronous [`Stream`] of frames, captured on a dedicated thread.
+//!
+//! Available with the `async` feature. [`FrameStream`] owns a thread that opens the capture
+//! source, runs its event loop (for [`Wgc`](crate::Wgc), the message pump and the
+//! `FrameArrived` handling) and sends the frames through a bounded channel, so that async
+//! code can await frames without dedicating one of its own threads to the capture. What
+//! happens when the consumer falls behind and the channel fills up is set by
+//! [`Backpressure`].
+//!
+//! The stream works with any executor, as it only relies on [`Waker`]s.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures_core::Stream;
+
+use crate::*;
+
+/// The interval at which the capture thread checks whether the stream was dropped while
+/// no frame arrives.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What the capture thread does with a new frame when the channel of a [`FrameStream`] is
+/// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Backpressure {
+    /// Waits until the consumer takes a frame. Meanwhile the frames queue up in the frame
+    /// pool of the capture, which skips them once it is full.
+    #[default]
+    Block,
+    /// Drops the oldest frame in the channel, so that the consumer gets the latest frames.
+    DropOldest,
+    /// Drops the new frame, so that the consumer gets the frames already in the channel.
+    DropNewest,
+}
+
+/// Options for a [`FrameStream`].
+#[derive(smart_default::SmartDefault, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// The number of frames the channel holds before [`backpressure`](Self::backpressure)
+    /// applies. Must be greater than 0.
+    ///
+    /// Defaults to 2.
+    #[default(2)]
+    pub capacity: usize,
+    /// What to do with a new frame when the channel is full.
+    ///
+    /// Defaults to [`Backpressure::Block`].
+    pub backpressure: Backpressure,
+}
+
+/// The channel between the capture thread and the stream.
+#[derive(Debug)]
+struct Channel<T> {
+    state: Mutex<ChannelState<T>>,
+    /// Notified when a frame is taken or the stream is dropped.
+    space: Condvar,
+}
+
+#[derive(Debug)]
+struct ChannelState<T> {
+    queue: VecDeque<std::result::Result<T, WgcError>>,
+    waker: Option<Waker>,
+    /// Set by the capture thread when it stops.
+    finished: bool,
+    /// Set when the stream is dropped.
+    cancelled: bool,
+    dropped: u64,
+}
+
+impl<T> Channel<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(ChannelState {
+                queue: VecDeque::with_capacity(capacity),
+                waker: None,
+                finished: false,
+                cancelled: false,
+                dropped: 0,
+            }),
+            space: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ChannelState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Sends `item` according to `options`. Returns `false` if the stream was dropped.
+    fn send(&self, item: std::result::Result<T, WgcError>, options: &StreamOptions) -> bool {
+        let mut state = self.lock();
+        while state.queue.len() >= options.capacity && !state.cancelled {
+            match options.backpressure {
+                Backpressure::Block => {
+                    state = self
+                        .space
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                Backpressure::DropOldest => {
+                    trace!("Oldest frame dropped as the stream is full");
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                }
+                Backpressure::DropNewest => {
+                    trace!("Newest frame dropped as the stream is full");
+                    state.dropped += 1;
+                    return true;
+                }
+            }
+        }
+        if state.cancelled {
+            return false;
+        }
+        state.queue.push_back(item);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+}
+
+/// Marks the channel finished when the capture thread stops, even by a panic.
+struct Finish<'a, T>(&'a Channel<T>);
+
+impl<T> Drop for Finish<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A [`Stream`] of frames captured on a dedicated thread.
+///
+/// The thread opens the source, waits for its frames and sends them through a bounded
+/// channel of [`StreamOptions::capacity`] frames. The stream ends when the source is
+/// closed, and dropping the stream stops the capture: the thread notices it within 50 ms,
+/// then drops the source and exits.
+///
+/// Frames are sent as they are delivered, errors included. A [`Wgc`](crate::Wgc) frame is
+/// bound to the Direct2D context of its thread, so `FrameStream::capture` converts each
+/// frame on the capture thread, and `FrameStream::capture_cpu` reads it into a
+/// [`CpuFrame`].
+///
+/// # Example
+///
+/// ```
+/// use futures::StreamExt;
+/// use futures::executor::block_on;
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let item = SyntheticItem { frame_count: Some(10), ..Default::default() };
+/// let mut stream = FrameStream::spawn(StreamOptions::default(), move || {
+///     SyntheticSource::new(item, WgcSettings::default())
+/// })?;
+///
+/// let indices = block_on(async {
+///     let mut indices = Vec::new();
+///     while let Some(frame) = stream.next().await {
+///         indices.push(frame?.index());
+///     }
+///     Ok::<_, WgcError>(indices)
+/// })?;
+/// assert_eq!(indices, (0..10).collect::<Vec<_>>());
+/// assert_eq!(stream.dropped(), 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FrameStream<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T: Send + 'static> FrameStream<T> {
+    /// Starts a thread that opens a source with `open` and streams its frames.
+    ///
+    /// The source is opened on the capture thread, so it does not need to be [`Send`].
+    /// Waits until it is opened, and returns the error of `open` if it fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`StreamOptions::capacity`] is 0, or if `open` panics.
+    pub fn spawn<S, O>(options: StreamOptions, open: O) -> std::result::Result<Self, WgcError>
+    where
+        S: FrameWaiter<Frame = T>,
+        O: FnOnce() -> std::result::Result<S, WgcError> + Send + 'static,
+    {
+        Self::spawn_map(options, open, Ok)
+    }
+
+    /// Starts a thread that opens a source with `open` and streams its frames, converted by
+    /// `map` on the capture thread.
+    ///
+    /// This streams sources whose frames cannot be sent to another thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`StreamOptions::capacity`] is 0, or if `open` panics.
+    pub fn spawn_map<S, O, M>(
+        options: StreamOptions,
+        open: O,
+        mut map: M,
+    ) -> std::result::Result<Self, WgcError>
+    where
+        S: FrameWaiter,
+        O: FnOnce() -> std::result::Result<S, WgcError> + Send + 'static,
+        M: FnMut(S::Frame) -> std::result::Result<T, WgcError> + Send + 'static,
+    {
+        assert!(
+            options.capacity > 0,
+            "Stream capacity must be greater than 0"
+        );
+        let channel = Arc::new(Channel::new(options.capacity));
+        let (opened_sender, opened) = std::sync::mpsc::sync_channel(1);
+        let thread = std::thread::Builder::new()
+            .name("wgc-capture".into())
+            .spawn({
+                let channel = channel.clone();
+                move || {
+                    let _finish = Finish(&*channel);
+                    let mut source = match open() {
+                        Ok(source) => {
+                            let _ = opened_sender.send(Ok(()));
+                            source
+                        }
+                        Err(err) => {
+                            let _ = opened_sender.send(Err(err));
+                            return;
+                        }
+                    };
+                    debug!("Capture thread started");
+                    while !channel.is_cancelled() {
+                        let item = match source.next_timeout(POLL_INTERVAL) {
+                            Ok(NextFrame::Frame(frame)) => map(frame),
+                            Ok(NextFrame::TimedOut) => continue,
+                            Ok(NextFrame::Closed) => break,
+                            Err(err) => Err(err),
+                        };
+                        if !channel.send(item, &options) {
+                            break;
+                        }
+                    }
+                    debug!("Capture thread stopped");
+                }
+            })?;
+        match opened.recv() {
+            Ok(result) => result.map(|()| Self { channel }),
+            // The sender was dropped without sending, so `open` panicked.
+            Err(_) => match thread.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("The capture thread stopped before opening the source"),
+            },
+        }
+    }
+}
+
+#[cfg(windows)]
+impl<T: Send + 'static> FrameStream<T> {
+    /// Captures `item` on a dedicated thread, converting each frame with `map` on that
+    /// thread.
+    ///
+    /// The stream yields the results of `map` rather than [`Frame`]s, as a [`Frame`] is
+    /// bound to the Direct2D device context of the capture thread and cannot be sent to
+    /// the consumer. [`capture_cpu`](FrameStream::capture_cpu) covers the common case of
+    /// reading each frame into a [`CpuFrame`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use wgc::*;
+    ///
+    /// # async fn run() -> Result<(), WgcError> {
+    /// let item = new_item_with_picker(None)?;
+    /// let options = StreamOptions { backpressure: Backpressure::DropOldest, ..Default::default() };
+    /// let mut stream = FrameStream::capture(item, WgcSettings::default(), options, |frame| {
+    ///     frame.to_cpu_frame(Some(FrameSize::new(640, 640)))
+    /// })?;
+    /// while let Some(frame) = stream.next().await {
+    ///     let frame = frame?;
+    ///     println!("{:?}", frame.size());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture<M>(
+        item: windows::Graphics::Capture::GraphicsCaptureItem,
+        settings: WgcSettings,
+        options: StreamOptions,
+        map: M,
+    ) -> std::result::Result<Self, WgcError>
+    where
+        M: FnMut(Frame) -> std::result::Result<T, WgcError> + Send + 'static,
+    {
+        Self::spawn_map(options, move || Wgc::new(item, settings), map)
+    }
+}
+
+#[cfg(windows)]
+impl FrameStream<CpuFrame> {
+    /// Captures `item` on a dedicated thread, reading each frame at its native size into
+    /// a [`CpuFrame`] with [`CaptureFrame::to_cpu_frame`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use wgc::*;
+    ///
+    /// # async fn run() -> Result<(), WgcError> {
+    /// let item = new_item_with_picker(None)?;
+    /// let mut stream = FrameStream::capture_cpu(item, WgcSettings::default(), StreamOptions::default())?;
+    /// while let Some(frame) = stream.next().await {
+    ///     let frame = frame?;
+    ///     println!("{:?}", frame.size());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture_cpu(
+        item: windows::Graphics::Capture::GraphicsCaptureItem,
+        settings: WgcSettings,
+        options: StreamOptions,
+    ) -> std::result::Result<Self, WgcError> {
+        Self::capture(item, settings, options, |frame| frame.to_cpu_frame(None))
+    }
+}
+
+impl<T> FrameStream<T> {
+    /// Returns the number of frames dropped so far because the channel was full.
+    ///
+    /// Always 0 with [`Backpressure::Block`], where the frames skipped by the capture are
+    /// counted by its [`FrameCounters`] instead.
+    pub fn dropped(&self) -> u64 {
+        self.channel.lock().dropped
+    }
+}
+
+impl<T> Stream for FrameStream<T> {
+    type Item = std::result::Result<T, WgcError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.channel.lock();
+        if let Some(item) = state.queue.pop_front() {
+            drop(state);
+            self.channel.space.notify_one();
+            return Poll::Ready(Some(item));
+        }
+        if state.finished {
+            return Poll::Ready(None);
+        }
+        match &mut state.waker {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for FrameStream<T> {
+    fn drop(&mut self) {
+        self.channel.lock().cancelled = true;
+        self.channel.space.notify_all();
+    }
+}
@@ -0,0 +1,687 @@
+//! A deterministic, platform-neutral capture source for headless testing.
+//!
+//! [`SyntheticSource`] follows the same iterator contract as [`Wgc`](crate::Wgc): it yields
+//! frames one by one, drops the first frame after a content size change (just like a
+//! recreated frame pool), and ends when the source is closed. Frames are generated on the
+//! CPU, so no display or GPU is needed.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// The content drawn into each frame of a [`SyntheticSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestPattern {
+    /// A single solid color, given as RGBA with straight alpha. Like captured frames, it is
+    /// read with premultiplied alpha unless [`WgcSettings::alpha_mode`] says otherwise.
+    Solid([u8; 4]),
+
+    /// Eight vertical bars of equal width: white, yellow, cyan, green, magenta, red, blue
+    /// and black, from left to right.
+    ColorBars,
+
+    /// A horizontal red gradient that scrolls one pixel to the left on every frame, over a
+    /// vertical green gradient.
+    MovingGradient,
+
+    /// The frame index encoded as a 8x8 grid of cells, in row-major order, least
+    /// significant bit first. A cell is white if its bit is set, and black otherwise.
+    FrameCounter,
+
+    /// Alternating black and white squares, starting with black in the top-left corner.
+    Checkerboard {
+        /// The side length of each square, in pixels.
+        cell_size: u32,
+    },
+}
+
+/// Describes the content produced by a [`SyntheticSource`].
+///
+/// This is the synthetic counterpart of a `GraphicsCaptureItem`.
+#[derive(smart_default::SmartDefault, Debug, Clone)]
+pub struct SyntheticItem {
+    /// The pattern drawn into each frame.
+    ///
+    /// Defaults to [`TestPattern::ColorBars`].
+    #[default(TestPattern::ColorBars)]
+    pub pattern: TestPattern,
+    /// The initial size of the content.
+    ///
+    /// Defaults to `640x480`.
+    #[default(FrameSize::new(640, 480))]
+    pub size: FrameSize,
+    /// Content size changes, as `(frame_index, new_size)` pairs.
+    ///
+    /// The content has the new size starting at the given frame index. As with a real
+    /// capture session, the first frame at a new size is dropped.
+    pub size_changes: Vec<(u64, FrameSize)>,
+    /// The time between two consecutive frames.
+    ///
+    /// Defaults to 1/60 of a second.
+    #[default(Duration::from_nanos(16_666_667))]
+    pub frame_interval: Duration,
+    /// The number of frames generated before the source is closed, or `None` for an
+    /// endless source.
+    ///
+    /// Defaults to `None`.
+    #[default(None)]
+    pub frame_count: Option<u64>,
+    /// Whether to pace the frames in real time.
+    ///
+    /// If `true`, the iterator blocks until each frame's render time has been reached on
+    /// the clock of the source, set with [`SyntheticSource::with_clock`].
+    /// Otherwise, frames are returned immediately, with render times still spaced by
+    /// [`frame_interval`](#structfield.frame_interval).
+    ///
+    /// Defaults to `false`.
+    #[default(false)]
+    pub realtime: bool,
+}
+
+impl SyntheticItem {
+    /// Returns the content size at the given frame index.
+    pub fn size_at(&self, index: u64) -> FrameSize {
+        self.size_changes
+            .iter()
+            .filter(|(start, _)| *start <= index)
+            .max_by_key(|(start, _)| *start)
+            .map_or(self.size, |(_, size)| *size)
+    }
+}
+
+/// A capture source that generates deterministic test frames.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let item = SyntheticItem {
+///     pattern: TestPattern::ColorBars,
+///     size: FrameSize::new(8, 2),
+///     size_changes: vec![(2, FrameSize::new(16, 4))],
+///     frame_count: Some(4),
+///     ..Default::default()
+/// };
+/// let source = SyntheticSource::new(item, WgcSettings::default())?;
+///
+/// // Frame 2 is dropped because the content size changed.
+/// let frames = source.collect::<Result<Vec<_>, _>>()?;
+/// let indices: Vec<u64> = frames.iter().map(|frame| frame.index()).collect();
+/// assert_eq!(indices, [0, 1, 3]);
+/// assert_eq!(frames[2].sequence(), 2);
+/// assert_eq!(frames[2].size()?, FrameSize::new(16, 4));
+///
+/// // The first bar is white and the second is yellow.
+/// let pixels = frames[0].read_pixels(None)?;
+/// assert_eq!(&pixels[0..4], &[255, 255, 255, 255]);
+/// assert_eq!(&pixels[4..8], &[255, 255, 0, 255]);
+///
+/// // Formats without alpha are converted after the frame is rendered.
+/// let settings = WgcSettings {
+///     pixel_format: PixelFormat::RGB24,
+///     ..Default::default()
+/// };
+/// let item = SyntheticItem {
+///     pattern: TestPattern::ColorBars,
+///     size: FrameSize::new(8, 2),
+///     ..Default::default()
+/// };
+/// let frame = SyntheticSource::new(item, settings)?.next().unwrap()?;
+/// assert_eq!(&frame.read_pixels(None)?[0..6], &[255, 255, 255, 255, 255, 0]);
+///
+/// // A region of interest is clamped to the frame size as the item resizes.
+/// let settings = WgcSettings {
+///     crop: Some(Rect::new(6.0, 0.0, 4.0, 2.0)),
+///     ..Default::default()
+/// };
+/// let item = SyntheticItem {
+///     size: FrameSize::new(8, 2),
+///     size_changes: vec![(1, FrameSize::new(16, 4))],
+///     frame_count: Some(3),
+///     ..Default::default()
+/// };
+/// let frames = SyntheticSource::new(item, settings)?.collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(frames[0].output_transform(None)?.output_size, FrameSize::new(2, 2));
+/// assert_eq!(frames[1].to_cpu_frame(None)?.size(), FrameSize::new(4, 2));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SyntheticSource {
+    item: SyntheticItem,
+    settings: WgcSettings,
+    pool_state: FramePoolState,
+    clock: Arc<dyn Clock>,
+    metrics: Arc<CaptureMetrics>,
+    closed: bool,
+    start_time: Instant,
+    start_timestamp: Duration,
+    queue: VecDeque<u64>,
+    next_index: u64,
+    counters: FrameCounters,
+}
+
+/// Creates the metrics collector of a source whose frames have the given size.
+fn new_metrics(
+    settings: &WgcSettings,
+    clock: &Arc<dyn Clock>,
+    frame_size: FrameSize,
+) -> Arc<CaptureMetrics> {
+    let metrics = CaptureMetrics::with_shared_clock(settings.metrics, clock.clone());
+    metrics.record_frame_size(frame_size);
+    Arc::new(metrics)
+}
+
+impl SyntheticSource {
+    /// Creates a source drawing the frames of `item` in the pixel format of `settings`.
+    ///
+    /// Frames are stamped on the performance-counter [`Clock`] from the time of this call;
+    /// [`with_clock`](Self::with_clock) sets another clock.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if [`WgcSettings::pixel_format`] is not
+    /// captured as [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`], as the HDR formats are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`WgcSettings::frame_queue_length`] is not positive, like
+    /// [`Wgc::new`](crate::Wgc::new).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let settings = WgcSettings { pixel_format: PixelFormat::GRAY8, ..Default::default() };
+    /// let frame = SyntheticSource::new(SyntheticItem::default(), settings)?.next().unwrap()?;
+    /// assert_eq!(frame.read_pixels(None)?.len(), 640 * 480);
+    ///
+    /// let settings = WgcSettings { pixel_format: PixelFormat::RGBA16F, ..Default::default() };
+    /// assert!(matches!(
+    ///     SyntheticSource::new(SyntheticItem::default(), settings),
+    ///     Err(WgcError::UnsupportedPixelFormat(PixelFormat::RGBA16F))
+    /// ));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(item: SyntheticItem, settings: WgcSettings) -> std::result::Result<Self, WgcError> {
+        let surface_format = settings.pixel_format.surface_format();
+        if surface_format != PixelFormat::RGBA8 && surface_format != PixelFormat::BGRA8 {
+            return Err(WgcError::UnsupportedPixelFormat(settings.pixel_format));
+        }
+        assert!(
+            settings.frame_queue_length > 0,
+            "Frame queue length must be greater than 0"
+        );
+        let pool_state = FramePoolState::new(item.size);
+        let clock: Arc<dyn Clock> = Arc::new(QpcClock);
+        Ok(Self {
+            start_timestamp: clock.elapsed_since_boot(),
+            metrics: new_metrics(&settings, &clock, item.size),
+            item,
+            settings,
+            pool_state,
+            closed: false,
+            clock,
+            start_time: Instant::now(),
+            queue: VecDeque::new(),
+            next_index: 0,
+            counters: FrameCounters::default(),
+        })
+    }
+
+    /// Stamps the frames with timestamps read from `clock` instead of the performance
+    /// counter, and paces a [`realtime`](SyntheticItem::realtime) source on it.
+    ///
+    /// The first frame is stamped with the time of `clock` when this is called, and each
+    /// following frame one [`frame_interval`](SyntheticItem::frame_interval) later,
+    /// whether or not the source runs in real time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let clock = FakeClock::new(Duration::from_secs(10));
+    /// let item = SyntheticItem {
+    ///     frame_interval: Duration::from_millis(40),
+    ///     frame_count: Some(3),
+    ///     ..Default::default()
+    /// };
+    /// let source = SyntheticSource::new(item, WgcSettings::default())?.with_clock(clock);
+    /// let timestamps = source
+    ///     .map(|frame| frame?.timestamp())
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(timestamps[0], Duration::from_secs(10));
+    /// assert_eq!(timestamps[2], Duration::from_millis(10_080));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.start_timestamp = clock.elapsed_since_boot();
+        self.clock = Arc::new(clock);
+        self.metrics = new_metrics(&self.settings, &self.clock, self.pool_state.buffer_size());
+        self
+    }
+
+    /// Returns the number of frames delivered, dropped for a content size change and
+    /// skipped for a full frame queue so far.
+    ///
+    /// Frames are only skipped by a [`realtime`](SyntheticItem::realtime) source: frames
+    /// rendered while [`frame_queue_length`](WgcSettings::frame_queue_length) frames are
+    /// already waiting are skipped, as by a full frame pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let clock = FakeClock::new(Duration::from_secs(1));
+    /// let item = SyntheticItem {
+    ///     frame_interval: Duration::from_millis(5),
+    ///     realtime: true,
+    ///     ..Default::default()
+    /// };
+    /// let settings = WgcSettings {
+    ///     frame_queue_length: 2,
+    ///     ..Default::default()
+    /// };
+    /// let mut source = SyntheticSource::new(item, settings)?.with_clock(clock.clone());
+    /// source.next().unwrap()?;
+    ///
+    /// // A slow consumer lets the queue fill up: of the 20 frames rendered meanwhile,
+    /// // the queue holds the first 2.
+    /// clock.advance(Duration::from_millis(100));
+    /// let frame = source.next().unwrap()?;
+    /// assert_eq!(frame.index(), 1);
+    /// let counters = frame.counters();
+    /// // The skipped frames are counted exactly, not estimated.
+    /// assert_eq!(counters.skipped_for_overflow, 18);
+    /// assert_eq!(counters.estimated_skipped_for_overflow, 0);
+    /// assert_eq!(counters.total(), source.counters().total());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn counters(&self) -> FrameCounters {
+        self.counters
+    }
+
+    /// Returns the latency and throughput metrics of the source over the window of
+    /// [`WgcSettings::metrics`], measured on the clock set with
+    /// [`with_clock`](Self::with_clock).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let clock = FakeClock::new(Duration::from_secs(10));
+    /// let item = SyntheticItem {
+    ///     size: FrameSize::new(16, 16),
+    ///     frame_interval: Duration::from_millis(10),
+    ///     ..Default::default()
+    /// };
+    /// let mut source = SyntheticSource::new(item, WgcSettings::default())?.with_clock(clock.clone());
+    ///
+    /// // Each frame is delivered 5 ms after it is rendered.
+    /// for index in 0..50 {
+    ///     clock.set(Duration::from_millis(10_005 + index * 10));
+    ///     source.next().unwrap()?.read_pixels(None)?;
+    /// }
+    /// let metrics = source.metrics();
+    /// assert_eq!(metrics.delivered, 50);
+    /// assert!((metrics.frame_rate - 100.0).abs() < 0.01);
+    /// assert_eq!(metrics.latency.max(), Some(Duration::from_millis(5)));
+    /// assert_eq!(metrics.read_duration.count(), 50);
+    /// assert_eq!(metrics.max_queue_depth, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the collector of the metrics of the source, which can be shared with other
+    /// threads.
+    pub fn metrics_collector(&self) -> Arc<CaptureMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Returns the offset of the render time of a frame from the start of the source.
+    fn offset_of(&self, index: u64) -> Duration {
+        Duration::from_nanos((self.item.frame_interval.as_nanos() * index as u128) as u64)
+    }
+
+    /// Returns `true` if the frame at `index` is past the end of the source.
+    fn is_closed_at(&self, index: u64) -> bool {
+        self.item
+            .frame_count
+            .is_some_and(|frame_count| index >= frame_count)
+    }
+
+    /// Queues the frames rendered up to now, skipping those that find the queue full.
+    fn queue_rendered_frames(&mut self) {
+        let elapsed = self
+            .clock
+            .elapsed_since_boot()
+            .saturating_sub(self.start_timestamp);
+        let interval = self.item.frame_interval.as_nanos().max(1);
+        let mut rendered = (elapsed.as_nanos() / interval) as u64 + 1;
+        if let Some(frame_count) = self.item.frame_count {
+            rendered = rendered.min(frame_count);
+        }
+        let pending = rendered.saturating_sub(self.next_index);
+        let free = (self.settings.frame_queue_length as usize).saturating_sub(self.queue.len());
+        let queued = pending.min(free as u64);
+        self.queue.extend(self.next_index..self.next_index + queued);
+        let skipped = pending - queued;
+        if skipped > 0 {
+            trace!("{} frames skipped as the frame queue was full", skipped);
+            self.counters.skipped_for_overflow += skipped;
+            self.metrics.record_skipped_for_overflow(skipped);
+        }
+        self.next_index += pending;
+    }
+}
+
+impl Iterator for SyntheticSource {
+    type Item = std::result::Result<SyntheticFrame, WgcError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_until(Duration::MAX) {
+                Ok(NextFrame::Frame(frame)) => return Some(Ok(frame)),
+                Ok(NextFrame::TimedOut) => continue,
+                Ok(NextFrame::Closed) => {
+                    debug!("Synthetic source closed");
+                    return None;
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Frames are paced and deadlines are measured on the clock set with
+/// [`with_clock`](SyntheticSource::with_clock), which [`Clock::sleep`] advances, so a
+/// [`FakeClock`] runs a real-time source without waiting.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let clock = FakeClock::new(Duration::from_secs(10));
+/// let item = SyntheticItem {
+///     frame_interval: Duration::from_millis(200),
+///     frame_count: Some(2),
+///     realtime: true,
+///     ..Default::default()
+/// };
+/// let mut source = SyntheticSource::new(item, WgcSettings::default())?.with_clock(clock.clone());
+/// assert!(source.try_next()?.into_frame().is_some());
+///
+/// // The second frame is rendered 200 ms after the first.
+/// assert!(source.try_next()?.is_timed_out());
+/// assert!(source.next_timeout(Duration::from_millis(10))?.is_timed_out());
+/// assert_eq!(clock.elapsed_since_boot(), Duration::from_millis(10_010));
+/// let frame = source.next_timeout(Duration::from_secs(5))?.into_frame().unwrap();
+/// assert_eq!(frame.timestamp()?, Duration::from_millis(10_200));
+/// assert_eq!(clock.elapsed_since_boot(), Duration::from_millis(10_200));
+/// assert!(source.next_timeout(Duration::from_secs(5))?.is_closed());
+/// # Ok(())
+/// # }
+/// ```
+impl FrameWaiter for SyntheticSource {
+    type Frame = SyntheticFrame;
+
+    fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    fn try_take(&mut self) -> std::result::Result<Option<SyntheticFrame>, WgcError> {
+        loop {
+            if self.item.realtime {
+                self.queue_rendered_frames();
+            }
+            // The frames queued when this one is taken, including it.
+            let queue_depth = self.queue.len().max(1) as u32;
+            let index = match self.queue.pop_front() {
+                Some(index) => index,
+                None if !self.item.realtime && !self.is_closed_at(self.next_index) => {
+                    self.next_index += 1;
+                    self.next_index - 1
+                }
+                None => return Ok(None),
+            };
+
+            let frame_size = self.item.size_at(index);
+            if self.pool_state.needs_recreate(frame_size) {
+                trace!(
+                    "Frame dropped as buffer size changed from {:?} to {:?}",
+                    self.pool_state.buffer_size(),
+                    frame_size
+                );
+                self.pool_state.recreated(frame_size);
+                self.counters.dropped_for_resize += 1;
+                self.metrics.record_dropped_for_resize();
+                self.metrics.record_frame_size(frame_size);
+                continue;
+            }
+            trace!("Got synthetic frame {}", index);
+            let sequence = self.counters.delivered;
+            self.counters.delivered += 1;
+            let offset = self.offset_of(index);
+            let timestamp = self.start_timestamp + offset;
+            self.metrics.record_delivery(timestamp, queue_depth);
+            return Ok(Some(SyntheticFrame {
+                index,
+                sequence,
+                counters: self.counters,
+                metrics: self.metrics.clone(),
+                size: frame_size,
+                pattern: self.item.pattern,
+                render_time: self.start_time + offset,
+                timestamp,
+                settings: self.settings,
+            }));
+        }
+    }
+
+    fn wait(&mut self, timeout: Duration) -> std::result::Result<bool, WgcError> {
+        if !self.queue.is_empty() {
+            return Ok(true);
+        }
+        if self.is_closed_at(self.next_index) {
+            if !self.closed {
+                self.closed = true;
+                self.metrics.record_item_closed();
+            }
+            return Ok(false);
+        }
+        if self.item.realtime {
+            let timestamp = self.start_timestamp + self.offset_of(self.next_index);
+            let until_render = timestamp.saturating_sub(self.clock.elapsed_since_boot());
+            self.clock.sleep(until_render.min(timeout));
+        }
+        Ok(true)
+    }
+}
+
+impl CaptureSource for SyntheticSource {
+    type Frame = SyntheticFrame;
+
+    fn counters(&self) -> FrameCounters {
+        SyntheticSource::counters(self)
+    }
+}
+
+/// A frame produced by a [`SyntheticSource`].
+#[derive(Debug, Clone)]
+pub struct SyntheticFrame {
+    index: u64,
+    sequence: u64,
+    counters: FrameCounters,
+    metrics: Arc<CaptureMetrics>,
+    size: FrameSize,
+    pattern: TestPattern,
+    render_time: Instant,
+    timestamp: Duration,
+    settings: WgcSettings,
+}
+
+impl SyntheticFrame {
+    /// Returns the index of the frame within its source, counting dropped frames.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the pattern drawn into the frame.
+    pub fn pattern(&self) -> TestPattern {
+        self.pattern
+    }
+
+    /// Returns the RGBA color of the pixel at `(x, y)`.
+    fn color_at(&self, x: u32, y: u32) -> [u8; 4] {
+        const BLACK: [u8; 4] = [0, 0, 0, 255];
+        const WHITE: [u8; 4] = [255, 255, 255, 255];
+        let FrameSize { width, height } = self.size;
+        match self.pattern {
+            TestPattern::Solid([r, g, b, a]) => {
+                let Color { r, g, b, a } = Color::rgba(r, g, b, a).premultiplied();
+                [r, g, b, a]
+            }
+            TestPattern::ColorBars => {
+                const BARS: [[u8; 4]; 8] = [
+                    [255, 255, 255, 255],
+                    [255, 255, 0, 255],
+                    [0, 255, 255, 255],
+                    [0, 255, 0, 255],
+                    [255, 0, 255, 255],
+                    [255, 0, 0, 255],
+                    [0, 0, 255, 255],
+                    [0, 0, 0, 255],
+                ];
+                BARS[(x as u64 * 8 / width as u64) as usize]
+            }
+            TestPattern::MovingGradient => {
+                let shifted = (x as u64 + self.index) % width as u64;
+                let r = shifted * 255 / (width as u64 - 1).max(1);
+                let g = y as u64 * 255 / (height as u64 - 1).max(1);
+                [r as u8, g as u8, 128, 255]
+            }
+            TestPattern::FrameCounter => {
+                let column = x as u64 * 8 / width as u64;
+                let row = y as u64 * 8 / height as u64;
+                if (self.index >> (row * 8 + column)) & 1 == 1 {
+                    WHITE
+                } else {
+                    BLACK
+                }
+            }
+            TestPattern::Checkerboard { cell_size } => {
+                let cell_size = cell_size.max(1);
+                if (x / cell_size + y / cell_size).is_multiple_of(2) {
+                    BLACK
+                } else {
+                    WHITE
+                }
+            }
+        }
+    }
+
+    /// Draws the frame at its native size.
+    fn render(&self) -> Vec<u8> {
+        let swap_red_blue = self.settings.pixel_format.surface_format() == PixelFormat::BGRA8;
+        let mut buffer = Vec::with_capacity(self.size.pixel_count() * 4);
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let mut color = self.color_at(x, y);
+                if swap_red_blue {
+                    color.swap(0, 2);
+                }
+                buffer.extend_from_slice(&color);
+            }
+        }
+        buffer
+    }
+
+    /// Returns the frame at its native size, in the surface format of the source.
+    fn surface(&self) -> std::result::Result<CpuFrame, WgcError> {
+        CpuFrame::from_packed(
+            self.render(),
+            self.size,
+            self.settings.pixel_format.surface_format(),
+        )
+    }
+}
+
+impl CaptureFrame for SyntheticFrame {
+    fn size(&self) -> std::result::Result<FrameSize, WgcError> {
+        Ok(self.size)
+    }
+
+    fn pixel_format(&self) -> PixelFormat {
+        self.settings.pixel_format
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.settings.alpha_mode
+    }
+
+    fn render_time(&self) -> std::result::Result<Instant, WgcError> {
+        Ok(self.render_time)
+    }
+
+    fn timestamp(&self) -> std::result::Result<Duration, WgcError> {
+        Ok(self.timestamp)
+    }
+
+    fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    fn counters(&self) -> FrameCounters {
+        self.counters
+    }
+
+    fn output_transform(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<LetterboxTransform, WgcError> {
+        read_transform(self.size, desired_size, &self.settings)
+    }
+
+    fn read_pixels(
+        &self,
+        desired_size: Option<FrameSize>,
+    ) -> std::result::Result<Vec<u8>, WgcError> {
+        self.metrics.time_read(|| {
+            let spec = OutputSpec::from_settings(&self.settings, desired_size);
+            Ok(self.surface()?.output(&spec)?.pixels)
+        })
+    }
+
+    fn read_outputs(
+        &self,
+        specs: &[OutputSpec],
+    ) -> std::result::Result<Vec<FrameOutput>, WgcError> {
+        self.metrics.time_read(|| {
+            let surface = self.surface()?;
+            specs.iter().map(|spec| surface.output(spec)).collect()
+        })
+    }
+}
@@ -0,0 +1,295 @@
+//! Conversion of frames to normalized floating-point tensors for inference.
+//!
+//! [`CpuFrame::to_tensor`] performs the preprocessing most vision models expect in a
+//! single pass: channel selection and reordering, dropping alpha, scaling to `[0, 1]`,
+//! per-channel mean/std normalization and the NCHW or NHWC memory layout.
+
+pub use half::f16;
+
+use crate::*;
+
+/// The memory layout of a tensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TensorLayout {
+    /// Planar: all values of the first channel, then the second, and so on. The shape is
+    /// `[1, channels, height, width]`.
+    Nchw,
+    /// Interleaved: the channels of the first pixel, then the second, and so on. The shape
+    /// is `[1, height, width, channels]`.
+    Nhwc,
+}
+
+/// The channels of a tensor, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TensorChannels {
+    /// Red, green and blue.
+    Rgb,
+    /// Blue, green and red.
+    Bgr,
+    /// A single luma channel, computed with the weights of
+    /// [`TensorOptions::color_matrix`].
+    Gray,
+}
+
+impl TensorChannels {
+    /// Returns the number of channels.
+    pub fn count(&self) -> usize {
+        match self {
+            TensorChannels::Rgb | TensorChannels::Bgr => 3,
+            TensorChannels::Gray => 1,
+        }
+    }
+}
+
+/// Options for [`CpuFrame::to_tensor`].
+///
+/// Each output value is `(pixel * scale - mean[c]) / std[c]`, where `c` is the index of
+/// the output channel: for [`TensorChannels::Bgr`], `mean[0]` applies to blue, and for
+/// [`TensorChannels::Gray`] only `mean[0]` and `std[0]` are used.
+///
+/// The defaults produce RGB values in `[0, 1]` in the NCHW layout.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// let options = TensorOptions {
+///     layout: TensorLayout::Nhwc,
+///     ..TensorOptions::imagenet()
+/// };
+/// assert_eq!(options.mean, [0.485, 0.456, 0.406]);
+/// ```
+#[derive(Debug, smart_default::SmartDefault, Clone, Copy, PartialEq)]
+pub struct TensorOptions {
+    /// The memory layout.
+    ///
+    /// Defaults to [`TensorLayout::Nchw`].
+    #[default(TensorLayout::Nchw)]
+    pub layout: TensorLayout,
+    /// The channels of the tensor.
+    ///
+    /// Defaults to [`TensorChannels::Rgb`].
+    #[default(TensorChannels::Rgb)]
+    pub channels: TensorChannels,
+    /// The factor applied to 8-bit pixel values before normalization.
+    ///
+    /// Defaults to `1.0 / 255.0`, mapping pixel values to `[0, 1]`.
+    #[default(1.0 / 255.0)]
+    pub scale: f32,
+    /// The per-channel mean subtracted from the scaled values.
+    ///
+    /// Defaults to `[0.0; 3]`.
+    #[default([0.0; 3])]
+    pub mean: [f32; 3],
+    /// The per-channel standard deviation the values are divided by.
+    ///
+    /// Defaults to `[1.0; 3]`.
+    #[default([1.0; 3])]
+    pub std: [f32; 3],
+    /// The matrix whose luma weights compute [`TensorChannels::Gray`], as for
+    /// [`PixelFormat::gray8`].
+    ///
+    /// Defaults to [`ColorMatrix::Bt601`].
+    pub color_matrix: ColorMatrix,
+}
+
+impl TensorOptions {
+    /// Returns RGB options normalized with the ImageNet mean and standard deviation, as
+    /// expected by most torchvision models.
+    pub fn imagenet() -> Self {
+        Self {
+            mean: [0.485, 0.456, 0.406],
+            std: [0.229, 0.224, 0.225],
+            ..Default::default()
+        }
+    }
+}
+
+/// A floating-point type that tensors can be made of.
+///
+/// Implemented for [`f32`] and [`f16`].
+pub trait TensorElement: Copy + Default {
+    /// Converts from `f32`, rounding to the nearest representable value.
+    fn from_f32(value: f32) -> Self;
+}
+
+impl TensorElement for f32 {
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+impl TensorElement for f16 {
+    fn from_f32(value: f32) -> Self {
+        f16::from_f32(value)
+    }
+}
+
+/// A 4-dimensional tensor holding a single image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor<T> {
+    data: Vec<T>,
+    shape: [usize; 4],
+    layout: TensorLayout,
+    transform: LetterboxTransform,
+}
+
+impl<T> Tensor<T> {
+    /// Returns the values of the tensor, in the order given by its layout.
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Consumes the tensor and returns its values.
+    pub fn into_data(self) -> Vec<T> {
+        self.data
+    }
+
+    /// Returns the shape of the tensor: `[1, channels, height, width]` for
+    /// [`TensorLayout::Nchw`] and `[1, height, width, channels]` for
+    /// [`TensorLayout::Nhwc`].
+    pub fn shape(&self) -> [usize; 4] {
+        self.shape
+    }
+
+    /// Returns the memory layout of the tensor.
+    pub fn layout(&self) -> TensorLayout {
+        self.layout
+    }
+
+    /// Returns the transform from the capture item to the tensor's pixels, used to map
+    /// detections back to the source.
+    pub fn transform(&self) -> LetterboxTransform {
+        self.transform
+    }
+}
+
+impl CpuFrame {
+    /// Converts the frame to a normalized floating-point tensor.
+    ///
+    /// Alpha is dropped. Combine with [`CpuFrame::resize`] or a desired size when reading
+    /// the frame to produce the input size of a model.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame is not [`PixelFormat::RGBA8`],
+    /// [`PixelFormat::BGRA8`], [`PixelFormat::RGB24`] or [`PixelFormat::BGR24`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// // Two BGRA pixels: red and blue.
+    /// let data = vec![0, 0, 255, 255, 255, 0, 0, 255];
+    /// let frame = CpuFrame::from_packed(data, FrameSize::new(2, 1), PixelFormat::BGRA8)?;
+    ///
+    /// let nchw = frame.to_tensor::<f32>(&TensorOptions::default())?;
+    /// assert_eq!(nchw.shape(), [1, 3, 1, 2]);
+    /// assert_eq!(nchw.data(), &[1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+    ///
+    /// let options = TensorOptions {
+    ///     layout: TensorLayout::Nhwc,
+    ///     channels: TensorChannels::Bgr,
+    ///     mean: [0.5; 3],
+    ///     std: [0.5; 3],
+    ///     ..Default::default()
+    /// };
+    /// let nhwc = frame.to_tensor::<f16>(&options)?;
+    /// assert_eq!(nhwc.shape(), [1, 1, 2, 3]);
+    /// let values: Vec<f32> = nhwc.data().iter().map(|value| value.to_f32()).collect();
+    /// assert_eq!(values, [-1.0, -1.0, 1.0, 1.0, -1.0, -1.0]);
+    ///
+    /// let gray = frame.to_tensor::<f32>(&TensorOptions {
+    ///     channels: TensorChannels::Gray,
+    ///     scale: 1.0,
+    ///     ..Default::default()
+    /// })?;
+    /// assert_eq!(gray.shape(), [1, 1, 1, 2]);
+    /// assert!((gray.data()[0] - 0.299 * 255.0).abs() < 1e-3);
+    ///
+    /// // The luma weights match those of the gray pixel formats.
+    /// let gray = frame.to_tensor::<f32>(&TensorOptions {
+    ///     channels: TensorChannels::Gray,
+    ///     scale: 1.0,
+    ///     color_matrix: ColorMatrix::Bt709,
+    ///     ..Default::default()
+    /// })?;
+    /// let gray8 = frame.convert(PixelFormat::gray8(ColorMatrix::Bt709))?;
+    /// assert_eq!(gray.data()[1].round() as u8, gray8.data()[1]);
+    /// assert!((gray.data()[1] - 0.0722 * 255.0).abs() < 1e-3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_tensor<T: TensorElement>(
+        &self,
+        options: &TensorOptions,
+    ) -> std::result::Result<Tensor<T>, WgcError> {
+        let (red, blue) = match self.pixel_format() {
+            PixelFormat::RGBA8 | PixelFormat::RGB24 => (0, 2),
+            PixelFormat::BGRA8 | PixelFormat::BGR24 => (2, 0),
+            pixel_format => return Err(WgcError::UnsupportedPixelFormat(pixel_format)),
+        };
+        let bytes_per_pixel = self.pixel_format().bytes_per_pixel() as usize;
+        let channels = options.channels.count();
+        let width = self.size().width as usize;
+        let height = self.size().height as usize;
+        let shape = match options.layout {
+            TensorLayout::Nchw => [1, channels, height, width],
+            TensorLayout::Nhwc => [1, height, width, channels],
+        };
+
+        // Fold the normalization into a single multiply-add per value.
+        let mut multiplier = [0.0; 3];
+        let mut bias = [0.0; 3];
+        for c in 0..channels {
+            multiplier[c] = options.scale / options.std[c];
+            bias[c] = -options.mean[c] / options.std[c];
+        }
+
+        // Formats and channels with a vectorized kernel are normalized a row at a time.
+        let kernel = kernels::TensorKernel::new(self.pixel_format(), options);
+        let mut row_values = vec![0.0; width * channels];
+        let plane = width * height;
+        let mut data = vec![T::default(); plane * channels];
+        for (y, row) in self.rows().enumerate() {
+            if let Some(kernel) = &kernel {
+                kernel.run(row, &mut row_values);
+            } else {
+                for (pixel, output) in row
+                    .chunks_exact(bytes_per_pixel)
+                    .zip(row_values.chunks_exact_mut(channels))
+                {
+                    let (r, g, b) = (pixel[red] as f32, pixel[1] as f32, pixel[blue] as f32);
+                    let values = match options.channels {
+                        TensorChannels::Rgb => [r, g, b],
+                        TensorChannels::Bgr => [b, g, r],
+                        TensorChannels::Gray => [options.color_matrix.luma(r, g, b), 0.0, 0.0],
+                    };
+                    for c in 0..channels {
+                        output[c] = values[c] * multiplier[c] + bias[c];
+                    }
+                }
+            }
+            for (x, values) in row_values.chunks_exact(channels).enumerate() {
+                let index = y * width + x;
+                for (c, &value) in values.iter().enumerate() {
+                    let value = T::from_f32(value);
+                    match options.layout {
+                        TensorLayout::Nchw => data[c * plane + index] = value,
+                        TensorLayout::Nhwc => data[index * channels + c] = value,
+                    }
+                }
+            }
+        }
+
+        Ok(Tensor {
+            data,
+            shape,
+            layout: options.layout,
+            transform: self.transform(),
+        })
+    }
+}
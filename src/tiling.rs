@@ -0,0 +1,408 @@
+//! Image pyramids and tile grids for small-object detection.
+//!
+//! Detectors with a fixed input size miss small objects on high-resolution frames once the
+//! frame is scaled down to fit. A common remedy is to run the detector on overlapping
+//! full-resolution tiles ([`CpuFrame::tiles`]) as well as on a downscaled overview
+//! ([`CpuFrame::pyramid`]). Every tile and level carries a [`LetterboxTransform`] that maps
+//! its detections back to the coordinates of the source frame.
+
+use crate::*;
+
+/// The filter used to halve each level of an image pyramid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PyramidFilter {
+    /// Averages each 2x2 block of pixels.
+    Area,
+    /// Weights the 4x4 pixels around each block with the binomial kernel `[1, 3, 3, 1] / 8`
+    /// along each axis, an approximation of a Gaussian blur that suppresses more aliasing.
+    #[default]
+    Gaussian,
+}
+
+impl PyramidFilter {
+    /// Returns the offsets of the taps relative to the first pixel of a block, their
+    /// weights, and the base-2 logarithm of the sum of the weights.
+    fn taps(&self) -> (&'static [i64], &'static [u32], u32) {
+        match self {
+            PyramidFilter::Area => (&[0, 1], &[1, 1], 1),
+            PyramidFilter::Gaussian => (&[-1, 0, 1, 2], &[1, 3, 3, 1], 3),
+        }
+    }
+}
+
+/// Specifies how tiles at the right and bottom edges of a frame are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TileEdges {
+    /// The last tile of each row and column is moved inward so that it ends at the edge of
+    /// the frame, overlapping its neighbour more. Tiles are only padded when the frame is
+    /// smaller than a tile.
+    #[default]
+    Shift,
+    /// Every tile keeps the regular step, and tiles extending past the edge of the frame are
+    /// padded with [`TileOptions::pad_color`].
+    Pad,
+}
+
+/// Describes the grid of [`CpuFrame::tiles`].
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let options = TileOptions {
+///     tile_size: FrameSize::new(640, 640),
+///     overlap: 128,
+///     ..Default::default()
+/// };
+/// // A 4K monitor is covered by 8 columns and 4 rows, 512 pixels apart except at the edges.
+/// let regions = options.regions(FrameSize::new(3840, 2160))?;
+/// assert_eq!(regions.len(), 8 * 4);
+/// assert_eq!(regions[1], Rect::new(512.0, 0.0, 640.0, 640.0));
+/// assert_eq!(regions[7], Rect::new(3200.0, 0.0, 640.0, 640.0));
+/// assert_eq!(regions[31], Rect::new(3200.0, 1520.0, 640.0, 640.0));
+///
+/// // With padding, the tiles keep their step and overflow the frame.
+/// let padded = TileOptions { edges: TileEdges::Pad, ..options };
+/// assert_eq!(padded.regions(FrameSize::new(3840, 2160))?[31], Rect::new(3584.0, 1536.0, 640.0, 640.0));
+///
+/// assert!(TileOptions { overlap: 640, ..options }.regions(FrameSize::new(3840, 2160)).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(smart_default::SmartDefault, Debug, Clone, Copy, PartialEq)]
+pub struct TileOptions {
+    /// The size of each tile, usually the input size of the detector.
+    ///
+    /// Defaults to 640x640.
+    #[default(FrameSize::new(640, 640))]
+    pub tile_size: FrameSize,
+    /// The number of pixels shared by neighbouring tiles, so that objects cut by the edge
+    /// of one tile are whole in the next. Must be smaller than the width and height of a
+    /// tile.
+    ///
+    /// Defaults to `128`, 20% of the default tile size.
+    #[default(128)]
+    pub overlap: u32,
+    /// How tiles at the right and bottom edges are placed.
+    ///
+    /// Defaults to [`TileEdges::Shift`].
+    #[default(TileEdges::Shift)]
+    pub edges: TileEdges,
+    /// The color of the parts of tiles that extend past the frame, stored in the alpha mode
+    /// of the frame like the padding of [`ResizeMode::Fit`].
+    ///
+    /// Defaults to [`Color::GRAY`].
+    #[default(Color::GRAY)]
+    pub pad_color: Color,
+}
+
+impl TileOptions {
+    /// Returns the areas of a frame of `size` covered by the tiles, in row-major order.
+    ///
+    /// Every area has the size of a tile; parts of it outside the frame are padding.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidTileOptions`] if the tile size is empty or the overlap is
+    /// not smaller than the tile size.
+    pub fn regions(&self, size: FrameSize) -> std::result::Result<Vec<Rect>, WgcError> {
+        let FrameSize { width, height } = self.tile_size;
+        if self.tile_size.is_empty() || self.overlap >= width.min(height) {
+            return Err(WgcError::InvalidTileOptions {
+                tile_size: self.tile_size,
+                overlap: self.overlap,
+            });
+        }
+        let columns = self.positions(size.width, width);
+        let rows = self.positions(size.height, height);
+        Ok(rows
+            .iter()
+            .flat_map(|&y| {
+                columns
+                    .iter()
+                    .map(move |&x| Rect::new(x as f32, y as f32, width as f32, height as f32))
+            })
+            .collect())
+    }
+
+    /// Returns the start of each tile along an axis of `length` pixels.
+    fn positions(&self, length: u32, tile: u32) -> Vec<u32> {
+        if length <= tile {
+            return vec![0];
+        }
+        let step = tile - self.overlap;
+        let count = (length - tile).div_ceil(step) + 1;
+        (0..count)
+            .map(|i| match self.edges {
+                TileEdges::Shift => (i * step).min(length - tile),
+                TileEdges::Pad => i * step,
+            })
+            .collect()
+    }
+}
+
+/// A tile of a frame, produced by [`CpuFrame::tiles`].
+#[derive(Debug, Clone)]
+pub struct Tile {
+    /// The column of the tile in the grid.
+    pub column: u32,
+    /// The row of the tile in the grid.
+    pub row: u32,
+    /// The horizontal offset of the tile in the tiled frame.
+    pub x: u32,
+    /// The vertical offset of the tile in the tiled frame.
+    pub y: u32,
+    /// The pixels of the tile. Its [`transform`](CpuFrame::transform) maps the source of
+    /// the tiled frame to the tile, and its
+    /// [`content_rect`](LetterboxTransform::content_rect) excludes the padding.
+    pub frame: CpuFrame,
+}
+
+/// Returns `true` if every byte of a pixel of the format is a channel, so that pixels can
+/// be averaged byte by byte.
+fn has_byte_channels(pixel_format: PixelFormat) -> bool {
+    matches!(pixel_format, PixelFormat::RGBA8 | PixelFormat::BGRA8)
+        || matches!(
+            pixel_format.encoding(),
+            Encoding::Rgb24 | Encoding::Bgr24 | Encoding::Gray8(_)
+        )
+}
+
+impl CpuFrame {
+    /// Returns an image pyramid of up to `levels` frames, each half the size of the
+    /// previous one, rounded up.
+    ///
+    /// The first level is a copy of the frame. The pyramid stops early at a level of 1x1
+    /// pixels. The [`transform`](CpuFrame::transform) of each level scales by exactly a
+    /// power of two, so where an odd size is halved, the content rectangle ends half a
+    /// pixel before the last row or column.
+    ///
+    /// Colors are averaged as stored, which is exact for premultiplied and opaque alpha.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] unless every channel of the format is a
+    /// byte: [`PixelFormat::RGBA8`], [`PixelFormat::BGRA8`], [`PixelFormat::RGB24`],
+    /// [`PixelFormat::BGR24`] and the 8-bit luma formats.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let format = PixelFormat::GRAY8;
+    /// let frame = CpuFrame::from_packed(vec![0, 100, 200, 40, 60, 80], FrameSize::new(3, 2), format)?;
+    ///
+    /// let pyramid = frame.pyramid(4, PyramidFilter::Area)?;
+    /// // The pyramid stops at 1x1.
+    /// assert_eq!(pyramid.len(), 3);
+    /// assert_eq!(pyramid[1].size(), FrameSize::new(2, 1));
+    /// // The last column averages the edge pixels with themselves.
+    /// assert_eq!(pyramid[1].data(), &[50, 140]);
+    ///
+    /// // Detections on a level map back to the frame.
+    /// let transform = pyramid[2].transform();
+    /// assert_eq!(transform.unmap_point(Point::new(0.5, 0.5)), Point::new(2.0, 2.0));
+    ///
+    /// // A solid color survives the Gaussian filter unchanged.
+    /// let red = CpuFrame::from_packed([255, 0, 0, 255].repeat(64), FrameSize::new(8, 8), PixelFormat::RGBA8)?;
+    /// let pyramid = red.pyramid(3, PyramidFilter::Gaussian)?;
+    /// assert_eq!(pyramid[2].size(), FrameSize::new(2, 2));
+    /// assert_eq!(pyramid[2].data(), [255, 0, 0, 255].repeat(4));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pyramid(
+        &self,
+        levels: usize,
+        filter: PyramidFilter,
+    ) -> std::result::Result<Vec<CpuFrame>, WgcError> {
+        if !has_byte_channels(self.pixel_format()) {
+            return Err(WgcError::UnsupportedPixelFormat(self.pixel_format()));
+        }
+        let mut pyramid = Vec::with_capacity(levels);
+        if levels > 0 {
+            pyramid.push(self.clone().into_packed());
+        }
+        while pyramid.len() < levels {
+            let last = &pyramid[pyramid.len() - 1];
+            let size = last.size();
+            if size.width <= 1 && size.height <= 1 {
+                break;
+            }
+            let next = last.downsample(filter)?;
+            pyramid.push(next);
+        }
+        Ok(pyramid)
+    }
+
+    /// Halves the frame for the next level of a pyramid.
+    fn downsample(&self, filter: PyramidFilter) -> std::result::Result<CpuFrame, WgcError> {
+        let size = self.size();
+        let output = FrameSize::new(size.width.div_ceil(2), size.height.div_ceil(2));
+        let channels = self.pixel_format().bytes_per_pixel() as usize;
+        let (offsets, weights, shift) = filter.taps();
+        // The source index of each tap of each output index, clamped to the frame.
+        let taps = |length: u32, outputs: u32| -> Vec<usize> {
+            (0..outputs as i64)
+                .flat_map(|i| {
+                    offsets
+                        .iter()
+                        .map(move |offset| (2 * i + offset).clamp(0, length as i64 - 1) as usize)
+                })
+                .collect()
+        };
+        let columns = taps(size.width, output.width);
+        let rows = taps(size.height, output.height);
+
+        // Filter the rows, then the columns of the filtered rows, rounding once at the end.
+        let row_len = output.width as usize * channels;
+        let mut filtered = vec![0u32; row_len * size.height as usize];
+        for (y, target) in filtered.chunks_exact_mut(row_len.max(1)).enumerate() {
+            let row = self.row(y as u32);
+            for (x, pixel) in target.chunks_exact_mut(channels).enumerate() {
+                let sources = &columns[x * offsets.len()..(x + 1) * offsets.len()];
+                for (&source, &weight) in sources.iter().zip(weights) {
+                    let source = &row[source * channels..(source + 1) * channels];
+                    for (sum, &value) in pixel.iter_mut().zip(source) {
+                        *sum += value as u32 * weight;
+                    }
+                }
+            }
+        }
+        let round = 1 << (2 * shift - 1);
+        let mut data = vec![0u8; row_len * output.height as usize];
+        for (y, target) in data.chunks_exact_mut(row_len.max(1)).enumerate() {
+            let sources = &rows[y * offsets.len()..(y + 1) * offsets.len()];
+            for (i, value) in target.iter_mut().enumerate() {
+                let sum: u32 = sources
+                    .iter()
+                    .zip(weights)
+                    .map(|(&source, &weight)| filtered[source * row_len + i] * weight)
+                    .sum();
+                *value = ((sum + round) >> (2 * shift)) as u8;
+            }
+        }
+
+        let half = LetterboxTransform {
+            scale_x: 0.5,
+            scale_y: 0.5,
+            content_rect: Rect::new(0.0, 0.0, size.width as f32 / 2.0, size.height as f32 / 2.0),
+            output_size: output,
+            ..LetterboxTransform::identity(size)
+        };
+        Ok(CpuFrame::from_packed(data, output, self.pixel_format())?
+            .with_metadata_of(self)
+            .with_transform(self.transform().then(&half)))
+    }
+
+    /// Splits the frame into a grid of overlapping tiles, as described by `options`.
+    ///
+    /// Pixels are copied unchanged, so any pixel format is supported; padding requires a
+    /// format that [`convert`](CpuFrame::convert) produces from [`PixelFormat::RGBA8`].
+    /// The [`transform`](CpuFrame::transform) of each tile maps the source of `self` to the
+    /// tile, so detections on a tile map back with
+    /// [`unmap_rect`](LetterboxTransform::unmap_rect), through any crop or letterbox that
+    /// produced `self`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::InvalidTileOptions`] if the options are invalid, and
+    /// [`WgcError::UnsupportedPixelFormat`] if tiles need padding in a format it cannot be
+    /// stored in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let format = PixelFormat::GRAY8;
+    /// let frame = CpuFrame::from_packed((0..15).collect(), FrameSize::new(5, 3), format)?;
+    /// let options = TileOptions {
+    ///     tile_size: FrameSize::new(3, 3),
+    ///     overlap: 1,
+    ///     edges: TileEdges::Pad,
+    ///     pad_color: Color::BLACK,
+    /// };
+    /// let tiles = frame.tiles(&options)?;
+    /// assert_eq!(tiles.len(), 2);
+    /// assert_eq!((tiles[1].column, tiles[1].x), (1, 2));
+    /// assert_eq!(tiles[1].frame.data(), &[2, 3, 4, 7, 8, 9, 12, 13, 14]);
+    ///
+    /// // A detection on the second tile, in frame coordinates.
+    /// let detection = tiles[1].frame.transform().unmap_rect(Rect::new(1.0, 0.0, 2.0, 1.0));
+    /// assert_eq!(detection, Rect::new(3.0, 0.0, 2.0, 1.0));
+    ///
+    /// // Larger tiles overflow the frame and are padded.
+    /// let options = TileOptions { tile_size: FrameSize::new(4, 4), ..options };
+    /// let tiles = frame.tiles(&options)?;
+    /// assert_eq!(tiles[1].frame.row(0), &[3, 4, 0, 0]);
+    /// assert_eq!(tiles[1].frame.row(3), &[0, 0, 0, 0]);
+    /// assert_eq!(tiles[1].frame.transform().content_rect, Rect::new(0.0, 0.0, 2.0, 3.0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tiles(&self, options: &TileOptions) -> std::result::Result<Vec<Tile>, WgcError> {
+        let size = self.size();
+        let regions = options.regions(size)?;
+        let tile_size = options.tile_size;
+        let bytes_per_pixel = self.pixel_format().bytes_per_pixel() as usize;
+        let padded = regions.iter().any(|region| {
+            region.right() > size.width as f32 || region.bottom() > size.height as f32
+        });
+        let pad = if padded {
+            self.encode_color(options.pad_color)?
+        } else {
+            Vec::new()
+        };
+        let columns = regions
+            .iter()
+            .take_while(|region| region.y == regions[0].y)
+            .count();
+
+        let mut tiles = Vec::with_capacity(regions.len());
+        for (i, region) in regions.iter().enumerate() {
+            let (x, y) = (region.x as u32, region.y as u32);
+            let content = FrameSize::new(
+                (size.width - x).min(tile_size.width),
+                (size.height - y).min(tile_size.height),
+            );
+            let content_bytes = content.width as usize * bytes_per_pixel;
+            let mut data = Vec::with_capacity(tile_size.pixel_count() * bytes_per_pixel);
+            for row in 0..tile_size.height {
+                if row < content.height {
+                    let source = &self.row(y + row)[x as usize * bytes_per_pixel..];
+                    data.extend_from_slice(&source[..content_bytes]);
+                    for _ in content.width..tile_size.width {
+                        data.extend_from_slice(&pad);
+                    }
+                } else {
+                    for _ in 0..tile_size.width {
+                        data.extend_from_slice(&pad);
+                    }
+                }
+            }
+            let transform = LetterboxTransform {
+                offset_x: -region.x,
+                offset_y: -region.y,
+                content_rect: content.into(),
+                output_size: tile_size,
+                ..LetterboxTransform::identity(size)
+            };
+            tiles.push(Tile {
+                column: (i % columns) as u32,
+                row: (i / columns) as u32,
+                x,
+                y,
+                frame: CpuFrame::from_packed(data, tile_size, self.pixel_format())?
+                    .with_metadata_of(self)
+                    .with_transform(self.transform().then(&transform)),
+            });
+        }
+        Ok(tiles)
+    }
+}
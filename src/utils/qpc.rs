@@ -1,6 +1,19 @@
+//! The performance counter (QPC), the monotonic clock of frame timestamps.
+//!
+//! Windows Graphics Capture stamps each frame with its `SystemRelativeTime`, the time since
+//! the system booted as measured by the performance counter. [`Clock`] abstracts over the
+//! counter, so timing code can run against [`QpcClock`] in production and [`FakeClock`] in
+//! deterministic tests.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(windows)]
 use windows::Win32::System::Performance::{QueryPerformanceCounter, QueryPerformanceFrequency};
 
 /// Receives the current performance-counter frequency, in counts per second
+#[cfg(windows)]
 pub(crate) fn qpc_frequency() -> i64 {
     let mut frequency = 0;
     unsafe {
@@ -10,6 +23,7 @@ pub(crate) fn qpc_frequency() -> i64 {
 }
 
 /// Receives the current performance-counter value
+#[cfg(windows)]
 pub(crate) fn qpc_now() -> i64 {
     let mut qpc = 0;
     unsafe {
@@ -18,10 +32,189 @@ pub(crate) fn qpc_now() -> i64 {
     qpc
 }
 
-/// Returns the duration since the system booted
-pub(crate) fn elapsed_since_system_boot() -> std::time::Duration {
-    let qpc_now = qpc_now();
-    let qpc_frequency = qpc_frequency();
-    let duration = qpc_now as u128 * 1_000_000_000 / qpc_frequency as u128;
-    std::time::Duration::from_nanos(duration as u64)
+/// Converts a performance-counter value to a duration
+fn counts_to_duration(counts: i64, frequency: i64) -> Duration {
+    let nanos = counts.max(0) as u128 * 1_000_000_000 / frequency.max(1) as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+/// A monotonic clock that counts from system boot, like the performance counter.
+///
+/// Timestamps on this clock, such as [`CaptureFrame::timestamp`](crate::CaptureFrame::timestamp),
+/// are durations since boot. They can be stored, serialized and compared across processes,
+/// and converted to [`Instant`] or [`SystemTime`] when needed.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// let clock = FakeClock::new(Duration::from_secs(100));
+/// let timestamp = clock.elapsed_since_boot();
+/// clock.advance(Duration::from_millis(250));
+/// assert_eq!(clock.elapsed_since_boot() - timestamp, Duration::from_millis(250));
+///
+/// // A timestamp in the past converts to a point in the past.
+/// let instant = clock.instant_at(timestamp);
+/// assert!(instant.elapsed() >= Duration::from_millis(250));
+///
+/// // A timestamp beyond what an `Instant` or a `SystemTime` can hold does not overflow.
+/// let _ = clock.instant_at(Duration::MAX);
+/// let _ = clock.system_time_at(Duration::MAX);
+/// ```
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Returns the current counter value.
+    fn qpc_now(&self) -> i64;
+
+    /// Returns the counter frequency, in counts per second.
+    fn qpc_frequency(&self) -> i64;
+
+    /// Returns the duration since the system booted.
+    fn elapsed_since_boot(&self) -> Duration {
+        counts_to_duration(self.qpc_now(), self.qpc_frequency())
+    }
+
+    /// Converts a timestamp on this clock to an [`Instant`], by its distance from the
+    /// current time.
+    ///
+    /// A timestamp too far from the current time for an [`Instant`] to represent converts
+    /// to the current time.
+    fn instant_at(&self, since_boot: Duration) -> Instant {
+        let now = Instant::now();
+        let elapsed = self.elapsed_since_boot();
+        match elapsed.checked_sub(since_boot) {
+            Some(age) => now.checked_sub(age).unwrap_or(now),
+            None => now.checked_add(since_boot - elapsed).unwrap_or(now),
+        }
+    }
+
+    /// Converts a timestamp on this clock to a [`SystemTime`], by its distance from the
+    /// current time.
+    ///
+    /// The result follows adjustments of the system time made since the timestamp was
+    /// taken, so it is suited to labelling recordings rather than measuring intervals. A
+    /// timestamp too far from the current time for a [`SystemTime`] to represent converts
+    /// to the current time.
+    fn system_time_at(&self, since_boot: Duration) -> SystemTime {
+        let now = SystemTime::now();
+        let elapsed = self.elapsed_since_boot();
+        match elapsed.checked_sub(since_boot) {
+            Some(age) => now.checked_sub(age).unwrap_or(now),
+            None => now.checked_add(since_boot - elapsed).unwrap_or(now),
+        }
+    }
+
+    /// Blocks the current thread until `duration` has elapsed on this clock.
+    ///
+    /// The default implementation sleeps for `duration` of real time.
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The clock of the system's performance counter.
+///
+/// On platforms other than Windows, which have no performance counter, it counts
+/// nanoseconds from the first time any `QpcClock` is read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QpcClock;
+
+impl Clock for QpcClock {
+    #[cfg(windows)]
+    fn qpc_now(&self) -> i64 {
+        qpc_now()
+    }
+
+    #[cfg(windows)]
+    fn qpc_frequency(&self) -> i64 {
+        qpc_frequency()
+    }
+
+    #[cfg(not(windows))]
+    fn qpc_now(&self) -> i64 {
+        static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as i64
+    }
+
+    #[cfg(not(windows))]
+    fn qpc_frequency(&self) -> i64 {
+        1_000_000_000
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same counter, so a test can keep one clone and advance the time seen
+/// by the code under test.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    counts: Arc<AtomicI64>,
+    frequency: i64,
+}
+
+impl FakeClock {
+    /// The frequency of [`FakeClock::new`], 10 MHz, which is that of the performance
+    /// counter on most systems.
+    pub const DEFAULT_FREQUENCY: i64 = 10_000_000;
+
+    /// Creates a clock reading `elapsed_since_boot`, counting at
+    /// [`DEFAULT_FREQUENCY`](Self::DEFAULT_FREQUENCY).
+    pub fn new(elapsed_since_boot: Duration) -> Self {
+        Self::with_frequency(elapsed_since_boot, Self::DEFAULT_FREQUENCY)
+    }
+
+    /// Creates a clock reading `elapsed_since_boot`, counting at `frequency` counts per
+    /// second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frequency` is not positive.
+    pub fn with_frequency(elapsed_since_boot: Duration, frequency: i64) -> Self {
+        assert!(frequency > 0, "Clock frequency must be positive");
+        let clock = Self {
+            counts: Arc::new(AtomicI64::new(0)),
+            frequency,
+        };
+        clock.set(elapsed_since_boot);
+        clock
+    }
+
+    fn duration_to_counts(&self, duration: Duration) -> i64 {
+        (duration.as_nanos() * self.frequency as u128 / 1_000_000_000) as i64
+    }
+
+    /// Sets the time since boot, rounded down to a whole count.
+    pub fn set(&self, elapsed_since_boot: Duration) {
+        self.counts.store(
+            self.duration_to_counts(elapsed_since_boot),
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Moves the clock forward by `duration`, rounded down to a whole count.
+    pub fn advance(&self, duration: Duration) {
+        self.counts
+            .fetch_add(self.duration_to_counts(duration), Ordering::SeqCst);
+    }
+}
+
+impl Clock for FakeClock {
+    fn qpc_now(&self) -> i64 {
+        self.counts.load(Ordering::SeqCst)
+    }
+
+    fn qpc_frequency(&self) -> i64 {
+        self.frequency
+    }
+
+    /// Advances the clock by `duration` instead of blocking, so that code waiting on the
+    /// clock runs instantly.
+    ///
+    /// Unlike [`advance`](Self::advance), the duration is rounded up to a whole count, so
+    /// that the clock reaches any deadline `duration` away.
+    fn sleep(&self, duration: Duration) {
+        let counts = (duration.as_nanos() * self.frequency as u128).div_ceil(1_000_000_000);
+        self.counts.fetch_add(counts as i64, Ordering::SeqCst);
+    }
 }
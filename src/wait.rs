@@ -0,0 +1,174 @@
+//! Non-blocking and deadline-bounded frame acquisition.
+//!
+//! Iterating a capture source blocks until the next frame arrives, which is forever if
+//! the captured window stops repainting. [`FrameWaiter::try_next`],
+//! [`next_timeout`](FrameWaiter::next_timeout) and
+//! [`next_deadline`](FrameWaiter::next_deadline) bound the wait instead, and report a
+//! timeout as [`NextFrame::TimedOut`].
+//!
+//! The deadline logic only relies on the [`Clock`] and the two primitives of
+//! [`FrameWaiter`], so it can be tested with a [`FakeClock`] and a scripted waiter.
+
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// The result of waiting for a frame until a deadline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NextFrame<F> {
+    /// A frame was delivered.
+    Frame(F),
+    /// No frame arrived before the deadline. The source is still open.
+    TimedOut,
+    /// The source was closed, so no more frames will arrive.
+    Closed,
+}
+
+impl<F> NextFrame<F> {
+    /// Returns the frame, if one was delivered.
+    pub fn into_frame(self) -> Option<F> {
+        match self {
+            NextFrame::Frame(frame) => Some(frame),
+            NextFrame::TimedOut | NextFrame::Closed => None,
+        }
+    }
+
+    /// Returns `true` if no frame arrived before the deadline.
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self, NextFrame::TimedOut)
+    }
+
+    /// Returns `true` if the source was closed.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, NextFrame::Closed)
+    }
+}
+
+/// A capture source that can wait for its next frame with a deadline.
+///
+/// Implementors provide a non-blocking [`try_take`](Self::try_take) and a bounded
+/// [`wait`](Self::wait) for events; the provided methods loop over them until a frame is
+/// delivered, the source is closed, or the deadline measured on [`clock`](Self::clock)
+/// passes.
+///
+/// # Example
+///
+/// A scripted waiter, whose frame arrives 50 ms after it is created:
+///
+/// ```
+/// use std::time::Duration;
+/// use wgc::*;
+///
+/// struct Scripted {
+///     clock: FakeClock,
+///     arrival: Duration,
+///     closed: bool,
+/// }
+///
+/// impl FrameWaiter for Scripted {
+///     type Frame = &'static str;
+///
+///     fn clock(&self) -> &dyn Clock {
+///         &self.clock
+///     }
+///
+///     fn try_take(&mut self) -> Result<Option<Self::Frame>, WgcError> {
+///         Ok((self.clock.elapsed_since_boot() >= self.arrival).then_some("frame"))
+///     }
+///
+///     fn wait(&mut self, timeout: Duration) -> Result<bool, WgcError> {
+///         // Sleeps until the frame arrives or the timeout elapses.
+///         let until_arrival = self.arrival.saturating_sub(self.clock.elapsed_since_boot());
+///         self.clock.advance(timeout.min(until_arrival));
+///         Ok(!self.closed)
+///     }
+/// }
+///
+/// # fn main() -> Result<(), WgcError> {
+/// let clock = FakeClock::new(Duration::from_secs(1));
+/// let mut waiter = Scripted { clock: clock.clone(), arrival: Duration::from_millis(1050), closed: false };
+///
+/// assert_eq!(waiter.try_next()?, NextFrame::TimedOut);
+/// assert_eq!(waiter.next_timeout(Duration::from_millis(20))?, NextFrame::TimedOut);
+/// assert_eq!(clock.elapsed_since_boot(), Duration::from_millis(1020));
+/// assert_eq!(waiter.next_timeout(Duration::from_secs(1))?, NextFrame::Frame("frame"));
+/// assert_eq!(clock.elapsed_since_boot(), Duration::from_millis(1050));
+///
+/// waiter.arrival = Duration::MAX;
+/// waiter.closed = true;
+/// assert_eq!(waiter.next_timeout(Duration::from_secs(1))?, NextFrame::Closed);
+/// # Ok(())
+/// # }
+/// ```
+pub trait FrameWaiter {
+    /// The type of frame delivered.
+    type Frame;
+
+    /// Returns the clock on which deadlines are measured.
+    fn clock(&self) -> &dyn Clock;
+
+    /// Takes the next frame if one is ready, without blocking.
+    fn try_take(&mut self) -> std::result::Result<Option<Self::Frame>, WgcError>;
+
+    /// Processes pending events, then blocks until an event arrives that may have made a
+    /// frame ready, or until `timeout` elapses.
+    ///
+    /// Returns `false` if the source was closed. A zero `timeout` only processes pending
+    /// events.
+    fn wait(&mut self, timeout: Duration) -> std::result::Result<bool, WgcError>;
+
+    /// Returns the next frame if one is ready once pending events are processed, and
+    /// [`NextFrame::TimedOut`] otherwise. Never blocks.
+    fn try_next(&mut self) -> std::result::Result<NextFrame<Self::Frame>, WgcError> {
+        let now = self.clock().elapsed_since_boot();
+        self.next_until(now)
+    }
+
+    /// Waits at most `timeout` for the next frame.
+    fn next_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> std::result::Result<NextFrame<Self::Frame>, WgcError> {
+        let deadline = self.clock().elapsed_since_boot().saturating_add(timeout);
+        self.next_until(deadline)
+    }
+
+    /// Waits for the next frame until `deadline`.
+    ///
+    /// An [`Instant`] cannot be read from [`clock`](Self::clock), so the deadline is
+    /// converted to it once, by its distance from the current time, and then measured on
+    /// the clock like the other deadlines. A deadline that has passed behaves as
+    /// [`try_next`](Self::try_next).
+    fn next_deadline(
+        &mut self,
+        deadline: Instant,
+    ) -> std::result::Result<NextFrame<Self::Frame>, WgcError> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let deadline = self.clock().elapsed_since_boot().saturating_add(remaining);
+        self.next_until(deadline)
+    }
+
+    /// Waits for the next frame until `deadline`, given as a time since boot on
+    /// [`clock`](Self::clock).
+    ///
+    /// Pending events are processed at least once, even if the deadline has passed.
+    fn next_until(
+        &mut self,
+        deadline: Duration,
+    ) -> std::result::Result<NextFrame<Self::Frame>, WgcError> {
+        let mut processed = false;
+        loop {
+            if let Some(frame) = self.try_take()? {
+                return Ok(NextFrame::Frame(frame));
+            }
+            let remaining = deadline.saturating_sub(self.clock().elapsed_since_boot());
+            if remaining.is_zero() && processed {
+                return Ok(NextFrame::TimedOut);
+            }
+            if !self.wait(remaining)? {
+                return Ok(NextFrame::Closed);
+            }
+            processed = true;
+        }
+    }
+}
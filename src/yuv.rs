@@ -0,0 +1,378 @@
+//! YCbCr frames and their conversion to and from RGB.
+//!
+//! Frames in the YCbCr formats ([`PixelFormat::NV12`], [`PixelFormat::I420`],
+//! [`PixelFormat::YUY2`] and the variants created by [`PixelFormat::yuv`]) are captured as
+//! [`PixelFormat::BGRA8`] and converted on the CPU by [`CpuFrame::to_yuv`].
+//! [`YuvFrame::to_rgb`] performs the inverse conversion, for example to verify encoder input.
+//! Both run a row at a time through the [`YuvKernel`](crate::kernels::YuvKernel).
+
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// An owned frame in a YCbCr format.
+///
+/// The planes are stored back to back without padding: the Y plane is `width` bytes per
+/// row, and the chroma planes of [`YuvLayout::Nv12`] and [`YuvLayout::I420`] cover
+/// `(width + 1) / 2` by `(height + 1) / 2` chroma samples.
+///
+/// # Example
+///
+/// ```
+/// use wgc::*;
+///
+/// # fn main() -> Result<(), WgcError> {
+/// // Round-trip color bars through every layout, with 8-pixel wide bars so that no
+/// // chroma sample straddles two bars.
+/// let item = SyntheticItem {
+///     pattern: TestPattern::ColorBars,
+///     size: FrameSize::new(64, 8),
+///     ..Default::default()
+/// };
+/// let frame = SyntheticSource::new(item, WgcSettings::default())?.next().unwrap()?;
+/// let rgba = frame.to_cpu_frame(None)?;
+///
+/// for layout in [YuvLayout::Nv12, YuvLayout::I420, YuvLayout::Yuy2] {
+///     for matrix in [ColorMatrix::Bt601, ColorMatrix::Bt709, ColorMatrix::Bt2020] {
+///         for range in [ColorRange::Limited, ColorRange::Full] {
+///             let yuv = rgba.to_yuv(PixelFormat::yuv(layout, matrix, range))?;
+///             assert_eq!(yuv.data().len(), yuv.pixel_format().buffer_len(rgba.size()));
+///
+///             let round_trip = yuv.to_rgb(PixelFormat::RGBA8)?;
+///             for (a, b) in rgba.data().iter().zip(round_trip.data()) {
+///                 assert!(a.abs_diff(*b) <= 2, "{layout:?} {matrix:?} {range:?}");
+///             }
+///         }
+///     }
+/// }
+///
+/// // Sources deliver YCbCr pixels directly.
+/// let settings = WgcSettings {
+///     pixel_format: PixelFormat::I420,
+///     ..Default::default()
+/// };
+/// let item = SyntheticItem {
+///     pattern: TestPattern::ColorBars,
+///     size: FrameSize::new(64, 8),
+///     ..Default::default()
+/// };
+/// let frame = SyntheticSource::new(item, settings)?.next().unwrap()?;
+/// let round_trip = frame.to_yuv_frame(None)?.to_rgb(PixelFormat::RGBA8)?;
+/// assert!(rgba.data().iter().zip(round_trip.data()).all(|(a, b)| a.abs_diff(*b) <= 2));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct YuvFrame {
+    data: Vec<u8>,
+    size: FrameSize,
+    pixel_format: PixelFormat,
+    render_time: Option<Instant>,
+    timestamp: Option<Duration>,
+    sequence: u64,
+    transform: LetterboxTransform,
+}
+
+impl YuvFrame {
+    /// Creates a frame from raw YCbCr data, such as the output of
+    /// [`CaptureFrame::read_pixels`] with a YCbCr pixel format.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if `pixel_format` is not a YCbCr format,
+    /// and [`WgcError::BufferTooSmall`] if `data` is shorter than the
+    /// [`buffer_len`](PixelFormat::buffer_len) of `size`.
+    pub fn from_raw(
+        data: Vec<u8>,
+        size: FrameSize,
+        pixel_format: PixelFormat,
+    ) -> std::result::Result<Self, WgcError> {
+        if pixel_format.yuv_layout().is_none() {
+            return Err(WgcError::UnsupportedPixelFormat(pixel_format));
+        }
+        let expected = pixel_format.buffer_len(size);
+        if data.len() < expected {
+            return Err(WgcError::BufferTooSmall {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            data,
+            size,
+            pixel_format,
+            render_time: None,
+            timestamp: None,
+            sequence: 0,
+            transform: LetterboxTransform::identity(size),
+        })
+    }
+
+    /// Sets the time at which the frame was rendered.
+    pub fn with_render_time(mut self, render_time: Instant) -> Self {
+        self.render_time = Some(render_time);
+        self
+    }
+
+    /// Sets the time at which the frame was rendered, as the duration since the system
+    /// booted on the performance-counter [`Clock`].
+    pub fn with_timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets the sequence number of the frame.
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Sets the transform from the source frame to this frame.
+    pub fn with_transform(mut self, transform: LetterboxTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Returns the size of the frame.
+    pub fn size(&self) -> FrameSize {
+        self.size
+    }
+
+    /// Returns the pixel format of the frame.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Returns the time at which the frame was rendered, if known.
+    pub fn render_time(&self) -> Option<Instant> {
+        self.render_time
+    }
+
+    /// Returns the time at which the frame was rendered as the duration since the system
+    /// booted, if known. See [`CaptureFrame::timestamp`].
+    pub fn timestamp(&self) -> Option<Duration> {
+        self.timestamp
+    }
+
+    /// Returns the sequence number of the frame.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the transform from the frame it was read from to this frame.
+    pub fn transform(&self) -> LetterboxTransform {
+        self.transform
+    }
+
+    /// Returns the raw data of all planes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the frame and returns the raw data of all planes.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Returns the planes of the frame: Y and interleaved UV for [`YuvLayout::Nv12`], Y, U
+    /// and V for [`YuvLayout::I420`], and the single interleaved plane of
+    /// [`YuvLayout::Yuy2`].
+    pub fn planes(&self) -> Vec<&[u8]> {
+        let luma_len = self.size.pixel_count();
+        let chroma_len = self.chroma_size().pixel_count();
+        match self.layout() {
+            YuvLayout::Nv12 => vec![
+                &self.data[..luma_len],
+                &self.data[luma_len..luma_len + 2 * chroma_len],
+            ],
+            YuvLayout::I420 => vec![
+                &self.data[..luma_len],
+                &self.data[luma_len..luma_len + chroma_len],
+                &self.data[luma_len + chroma_len..luma_len + 2 * chroma_len],
+            ],
+            YuvLayout::Yuy2 => vec![&self.data[..self.pixel_format.buffer_len(self.size)]],
+        }
+    }
+
+    /// Converts the frame back to [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`], with
+    /// opaque alpha.
+    ///
+    /// Chroma is upsampled by repeating each sample.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if `pixel_format` is not
+    /// [`PixelFormat::RGBA8`] or [`PixelFormat::BGRA8`].
+    pub fn to_rgb(&self, pixel_format: PixelFormat) -> std::result::Result<CpuFrame, WgcError> {
+        let Some(kernel) = kernels::YuvKernel::new(pixel_format, self.pixel_format) else {
+            return Err(WgcError::UnsupportedPixelFormat(pixel_format));
+        };
+        let width = self.size.width as usize;
+        let chroma_width = self.chroma_size().width as usize;
+        let planes = self.planes();
+
+        let mut data = vec![0; self.size.pixel_count() * 4];
+        // The samples of a row, for the layouts that interleave them.
+        let (mut luma, mut u, mut v) =
+            (vec![0; width], vec![0; chroma_width], vec![0; chroma_width]);
+        for y in 0..self.size.height as usize {
+            let target = &mut data[y * width * 4..(y + 1) * width * 4];
+            let chroma = (y / 2) * chroma_width..(y / 2 + 1) * chroma_width;
+            match self.layout() {
+                YuvLayout::Nv12 => {
+                    let samples = &planes[1][chroma.start * 2..chroma.end * 2];
+                    for (i, sample) in samples.chunks_exact(2).enumerate() {
+                        (u[i], v[i]) = (sample[0], sample[1]);
+                    }
+                    kernel.to_rgb(&planes[0][y * width..(y + 1) * width], &u, &v, target);
+                }
+                YuvLayout::I420 => kernel.to_rgb(
+                    &planes[0][y * width..(y + 1) * width],
+                    &planes[1][chroma.clone()],
+                    &planes[2][chroma],
+                    target,
+                ),
+                YuvLayout::Yuy2 => {
+                    let groups = &planes[0][y * chroma_width * 4..(y + 1) * chroma_width * 4];
+                    for (x, sample) in luma.iter_mut().enumerate() {
+                        *sample = groups[(x / 2) * 4 + (x % 2) * 2];
+                    }
+                    for (i, group) in groups.chunks_exact(4).enumerate() {
+                        (u[i], v[i]) = (group[1], group[3]);
+                    }
+                    kernel.to_rgb(&luma, &u, &v, target);
+                }
+            }
+        }
+        let mut frame = CpuFrame::from_packed(data, self.size, pixel_format)?
+            .with_sequence(self.sequence)
+            .with_transform(self.transform)
+            .with_alpha_mode(AlphaMode::Opaque);
+        if let Some(render_time) = self.render_time {
+            frame = frame.with_render_time(render_time);
+        }
+        if let Some(timestamp) = self.timestamp {
+            frame = frame.with_timestamp(timestamp);
+        }
+        Ok(frame)
+    }
+
+    fn layout(&self) -> YuvLayout {
+        self.pixel_format
+            .yuv_layout()
+            .expect("YuvFrame always has a YCbCr format")
+    }
+
+    fn chroma_size(&self) -> FrameSize {
+        FrameSize::new(self.size.width.div_ceil(2), self.size.height.div_ceil(2))
+    }
+}
+
+impl CpuFrame {
+    /// Converts the frame to a YCbCr format.
+    ///
+    /// Chroma is subsampled by averaging the pixels each sample covers. Alpha is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WgcError::UnsupportedPixelFormat`] if the frame is not [`PixelFormat::RGBA8`]
+    /// or [`PixelFormat::BGRA8`], or if `pixel_format` is not a YCbCr format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wgc::*;
+    ///
+    /// # fn main() -> Result<(), WgcError> {
+    /// let white = [255; 4].repeat(2 * 2);
+    /// let frame = CpuFrame::from_packed(white, FrameSize::new(2, 2), PixelFormat::RGBA8)?;
+    ///
+    /// let nv12 = frame.to_yuv(PixelFormat::NV12)?;
+    /// assert_eq!(nv12.planes(), [&[235, 235, 235, 235][..], &[128, 128]]);
+    ///
+    /// let full = PixelFormat::yuv(YuvLayout::Yuy2, ColorMatrix::Bt709, ColorRange::Full);
+    /// assert_eq!(frame.to_yuv(full)?.data(), &[255, 128, 255, 128, 255, 128, 255, 128]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_yuv(&self, pixel_format: PixelFormat) -> std::result::Result<YuvFrame, WgcError> {
+        if !matches!(self.pixel_format(), PixelFormat::RGBA8 | PixelFormat::BGRA8) {
+            return Err(WgcError::UnsupportedPixelFormat(self.pixel_format()));
+        }
+        let (Some(layout), Some(kernel)) = (
+            pixel_format.yuv_layout(),
+            kernels::YuvKernel::new(self.pixel_format(), pixel_format),
+        ) else {
+            return Err(WgcError::UnsupportedPixelFormat(pixel_format));
+        };
+        let size = self.size();
+        let width = size.width as usize;
+        // Each chroma sample covers two columns and, except in YUY2, two rows.
+        let sample_rows = if layout == YuvLayout::Yuy2 { 1 } else { 2 };
+
+        let mut data = Vec::with_capacity(pixel_format.buffer_len(size));
+        let chroma_size = FrameSize::new(size.width.div_ceil(2), size.height.div_ceil(2));
+        let mut chroma = Vec::with_capacity(chroma_size.pixel_count());
+        // The luma and unquantized chroma of the rows covered by a chroma sample.
+        let mut luma = vec![0; width * sample_rows];
+        let mut u = vec![0.0; width * sample_rows];
+        let mut v = vec![0.0; width * sample_rows];
+        for y in (0..size.height).step_by(sample_rows) {
+            // The last row of a frame with an odd height has its own chroma samples.
+            let rows = sample_rows.min((size.height - y) as usize);
+            for row in 0..rows {
+                let samples = row * width..(row + 1) * width;
+                kernel.to_yuv(
+                    self.row(y + row as u32),
+                    &mut luma[samples.clone()],
+                    &mut u[samples.clone()],
+                    &mut v[samples],
+                );
+            }
+            for x in (0..width).step_by(2) {
+                // Average the chroma of the pixels the sample covers, clamped to the frame.
+                let (mut sample_u, mut sample_v, mut count) = (0.0, 0.0, 0.0);
+                for row in 0..rows {
+                    for i in row * width + x..row * width + (x + 2).min(width) {
+                        sample_u += u[i];
+                        sample_v += v[i];
+                        count += 1.0;
+                    }
+                }
+                let (sample_u, sample_v) = (
+                    kernel.quantize_chroma(sample_u / count),
+                    kernel.quantize_chroma(sample_v / count),
+                );
+                if layout == YuvLayout::Yuy2 {
+                    // An odd last pixel is repeated.
+                    let second = (x + 1).min(width - 1);
+                    data.extend_from_slice(&[luma[x], sample_u, luma[second], sample_v]);
+                } else {
+                    chroma.push((sample_u, sample_v));
+                }
+            }
+            if layout != YuvLayout::Yuy2 {
+                data.extend_from_slice(&luma[..rows * width]);
+            }
+        }
+        match layout {
+            YuvLayout::Nv12 => data.extend(chroma.iter().flat_map(|&(u, v)| [u, v])),
+            YuvLayout::I420 => {
+                data.extend(chroma.iter().map(|&(u, _)| u));
+                data.extend(chroma.iter().map(|&(_, v)| v));
+            }
+            YuvLayout::Yuy2 => {}
+        }
+
+        let mut frame = YuvFrame::from_raw(data, size, pixel_format)?
+            .with_sequence(self.sequence())
+            .with_transform(self.transform());
+        if let Some(render_time) = self.render_time() {
+            frame = frame.with_render_time(render_time);
+        }
+        if let Some(timestamp) = self.timestamp() {
+            frame = frame.with_timestamp(timestamp);
+        }
+        Ok(frame)
+    }
+}
@@ -0,0 +1,181 @@
+//! Scrapes a `MetricsExporter` over loopback and checks the OpenMetrics output.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use wgc::*;
+
+/// A parsed HTTP response.
+struct Response {
+    status: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sends a request with a minimal HTTP/1.1 client and reads the response until the server
+/// closes the connection.
+fn request(addr: SocketAddr, method: &str, path: &str) -> Response {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nAccept: application/openmetrics-text\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.split("\r\n");
+    let status = lines.next().unwrap().to_string();
+    let headers = lines
+        .map(|line| {
+            let (key, value) = line.split_once(':').unwrap();
+            (key.trim().to_string(), value.trim().to_string())
+        })
+        .collect();
+    Response {
+        status,
+        headers,
+        body: body.to_string(),
+    }
+}
+
+/// Runs a synthetic capture of six frames that resizes at the third, which is dropped, and
+/// serves its metrics.
+fn captured_exporter() -> MetricsExporter {
+    let clock = FakeClock::new(Duration::from_secs(60));
+    let item = SyntheticItem {
+        size: FrameSize::new(64, 48),
+        frame_interval: Duration::from_millis(10),
+        frame_count: Some(6),
+        size_changes: vec![(2, FrameSize::new(32, 24))],
+        ..Default::default()
+    };
+    let mut source = SyntheticSource::new(item, WgcSettings::default())
+        .unwrap()
+        .with_clock(clock);
+    for frame in source.by_ref() {
+        frame.unwrap().read_pixels(None).unwrap();
+    }
+    MetricsExporter::serve("127.0.0.1:0", source.metrics_collector()).unwrap()
+}
+
+#[test]
+fn serves_openmetrics_text() {
+    let exporter = captured_exporter();
+    let response = request(exporter.local_addr(), "GET", "/metrics");
+
+    assert_eq!(response.status, "HTTP/1.1 200 OK");
+    assert_eq!(
+        response.header("Content-Type"),
+        Some("application/openmetrics-text; version=1.0.0; charset=utf-8")
+    );
+    assert_eq!(
+        response.header("Content-Length"),
+        Some(response.body.len().to_string().as_str())
+    );
+
+    let body = &response.body;
+    for line in [
+        "# TYPE wgc_frames_delivered counter",
+        "wgc_frames_delivered_total 5",
+        "# TYPE wgc_frames_dropped_for_resize counter",
+        "wgc_frames_dropped_for_resize_total 1",
+        "wgc_frames_skipped_for_overflow_total 0",
+        "wgc_frames_estimated_skipped_for_overflow_total 0",
+        "# TYPE wgc_item_closed counter",
+        "wgc_item_closed_total 1",
+        "# TYPE wgc_frame_width_pixels gauge",
+        "# UNIT wgc_frame_width_pixels pixels",
+        "wgc_frame_width_pixels 32",
+        "wgc_frame_height_pixels 24",
+        "# TYPE wgc_capture_latency_seconds summary",
+        "# UNIT wgc_capture_latency_seconds seconds",
+    ] {
+        assert!(
+            body.lines().any(|l| l == line),
+            "missing {line:?} in:\n{body}"
+        );
+    }
+    for quantile in ["0.5", "0.9", "0.99", "0.999"] {
+        for summary in ["wgc_capture_latency_seconds", "wgc_read_duration_seconds"] {
+            let prefix = format!("{summary}{{quantile=\"{quantile}\"}} ");
+            let value = body
+                .lines()
+                .find_map(|l| l.strip_prefix(prefix.as_str()))
+                .unwrap_or_else(|| panic!("missing {prefix:?} in:\n{body}"));
+            assert!(value.parse::<f64>().unwrap() >= 0.0);
+        }
+    }
+    assert!(body.ends_with("# EOF\n"));
+}
+
+#[test]
+fn every_sample_belongs_to_a_declared_family() {
+    let exporter = captured_exporter();
+    let body = request(exporter.local_addr(), "GET", "/metrics").body;
+
+    let mut families = Vec::new();
+    for line in body.lines() {
+        if let Some(declaration) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = declaration.split_once(' ').unwrap();
+            assert!(["counter", "gauge", "summary"].contains(&kind));
+            families.push((name.to_string(), kind.to_string()));
+        } else if let Some(comment) = line.strip_prefix("# ") {
+            assert!(
+                comment == "EOF" || comment.starts_with("HELP ") || comment.starts_with("UNIT "),
+                "unexpected comment {line:?}"
+            );
+        } else {
+            let (name, value) = line.rsplit_once(' ').unwrap();
+            let name = name.split('{').next().unwrap();
+            let (family, kind) = families.last().expect("sample before any family");
+            let expected = if kind == "counter" {
+                format!("{family}_total")
+            } else {
+                family.clone()
+            };
+            assert_eq!(name, expected);
+            assert!(value.parse::<f64>().unwrap().is_finite());
+        }
+    }
+    assert_eq!(body.lines().filter(|&l| l == "# EOF").count(), 1);
+}
+
+#[test]
+fn rejects_other_requests() {
+    let exporter = captured_exporter();
+    let addr = exporter.local_addr();
+
+    assert_eq!(request(addr, "GET", "/").status, "HTTP/1.1 404 Not Found");
+    assert_eq!(
+        request(addr, "POST", "/metrics").status,
+        "HTTP/1.1 405 Method Not Allowed"
+    );
+    // The server keeps serving after an error response.
+    assert_eq!(
+        request(addr, "GET", "/metrics?x=1").status,
+        "HTTP/1.1 200 OK"
+    );
+}
+
+#[test]
+fn stops_serving_when_dropped() {
+    let exporter = captured_exporter();
+    let addr = exporter.local_addr();
+    drop(exporter);
+    assert!(TcpStream::connect(addr).is_err());
+}
@@ -0,0 +1,308 @@
+//! Checks that every SIMD level supported by the CPU produces the same output as the
+//! scalar kernels.
+
+use wgc::kernels::{RowKernel, SimdLevel, TensorKernel, YuvKernel};
+use wgc::*;
+
+const SOURCES: [PixelFormat; 2] = [PixelFormat::RGBA8, PixelFormat::BGRA8];
+
+const MATRICES: [ColorMatrix; 3] = [ColorMatrix::Bt601, ColorMatrix::Bt709, ColorMatrix::Bt2020];
+
+const RANGES: [ColorRange; 2] = [ColorRange::Limited, ColorRange::Full];
+
+/// The longest row tested, which covers several iterations of every vector kernel and
+/// every length of the scalar remainder.
+const MAX_PIXELS: usize = 100;
+
+fn targets() -> Vec<PixelFormat> {
+    let mut targets = vec![
+        PixelFormat::RGBA8,
+        PixelFormat::BGRA8,
+        PixelFormat::RGB24,
+        PixelFormat::BGR24,
+    ];
+    for matrix in MATRICES {
+        targets.push(PixelFormat::gray8(matrix));
+        targets.push(PixelFormat::gray16(matrix));
+    }
+    targets
+}
+
+/// Returns deterministic bytes that cover every value in every channel.
+fn test_bytes(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|i| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            // Mix in a counter so that short inputs still contain every byte value.
+            (state as u8).wrapping_add((i / 4) as u8)
+        })
+        .collect()
+}
+
+fn run(kernel: &RowKernel, source: &[u8]) -> Vec<u8> {
+    let pixels = source.len() / kernel.source_format().bytes_per_pixel() as usize;
+    // Fill the output with a marker, so that bytes a kernel fails to write are caught.
+    let mut target = vec![0xA5; pixels * kernel.target_format().bytes_per_pixel() as usize];
+    kernel.run(source, &mut target);
+    target
+}
+
+/// Returns the YCbCr kernels from `source` with every matrix and range.
+fn yuv_kernels(source: PixelFormat) -> Vec<YuvKernel> {
+    let mut kernels = Vec::new();
+    for matrix in MATRICES {
+        for range in RANGES {
+            let yuv = PixelFormat::yuv(YuvLayout::Nv12, matrix, range);
+            kernels.push(YuvKernel::new(source, yuv).unwrap());
+        }
+    }
+    kernels
+}
+
+/// Returns the luma and the bits of the chroma of the pixels of `source`.
+fn run_to_yuv(kernel: &YuvKernel, source: &[u8]) -> (Vec<u8>, Vec<u32>, Vec<u32>) {
+    let pixels = source.len() / 4;
+    // Fill the outputs with markers, so that values a kernel fails to write are caught.
+    let mut luma = vec![0xA5; pixels];
+    let mut u = vec![f32::NAN; pixels];
+    let mut v = vec![f32::NAN; pixels];
+    kernel.to_yuv(source, &mut luma, &mut u, &mut v);
+    let bits = |values: Vec<f32>| -> Vec<u32> { values.into_iter().map(f32::to_bits).collect() };
+    (luma, bits(u), bits(v))
+}
+
+fn run_to_rgb(kernel: &YuvKernel, luma: &[u8], u: &[u8], v: &[u8]) -> Vec<u8> {
+    let mut target = vec![0xA5; luma.len() * 4];
+    kernel.to_rgb(luma, u, v, &mut target);
+    target
+}
+
+#[test]
+fn row_kernels_match_scalar_for_every_length() {
+    let bytes = test_bytes(MAX_PIXELS * 4);
+    for source_format in SOURCES {
+        for target_format in targets() {
+            let kernel = RowKernel::new(source_format, target_format).unwrap();
+            let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+            for level in SimdLevel::supported() {
+                let kernel = kernel.with_level(level).unwrap();
+                for pixels in 0..=MAX_PIXELS {
+                    let source = &bytes[..pixels * 4];
+                    assert_eq!(
+                        run(&kernel, source),
+                        run(&scalar, source),
+                        "{source_format:?} to {target_format:?} with {level:?}, {pixels} pixels"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn luma_kernels_match_scalar_for_every_color() {
+    // One row per red value, holding every combination of green and blue.
+    let mut row = vec![0; 256 * 256 * 4];
+    for target_format in targets() {
+        if target_format.bytes_per_pixel() > 2 {
+            continue;
+        }
+        let kernel = RowKernel::new(PixelFormat::RGBA8, target_format).unwrap();
+        let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+        let levels: Vec<_> = SimdLevel::supported()
+            .into_iter()
+            .filter(|&level| level != SimdLevel::Scalar)
+            .collect();
+        for red in 0..=255 {
+            for (i, pixel) in row.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(&[red, (i >> 8) as u8, i as u8, 255]);
+            }
+            let expected = run(&scalar, &row);
+            for &level in &levels {
+                let kernel = kernel.with_level(level).unwrap();
+                assert!(
+                    run(&kernel, &row) == expected,
+                    "{target_format:?} with {level:?} differs for red {red}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn tensor_kernels_match_scalar_for_every_length() {
+    let bytes = test_bytes(MAX_PIXELS * 4);
+    let option_sets = [TensorOptions::default(), TensorOptions::imagenet()];
+    for source_format in SOURCES {
+        for channels in [TensorChannels::Rgb, TensorChannels::Bgr] {
+            for options in option_sets {
+                let options = TensorOptions {
+                    channels,
+                    ..options
+                };
+                let kernel = TensorKernel::new(source_format, &options).unwrap();
+                let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+                for level in SimdLevel::supported() {
+                    let kernel = kernel.with_level(level).unwrap();
+                    for pixels in 0..=MAX_PIXELS {
+                        let source = &bytes[..pixels * 4];
+                        let mut expected = vec![f32::NAN; pixels * 3];
+                        let mut actual = vec![f32::NAN; pixels * 3];
+                        scalar.run(source, &mut expected);
+                        kernel.run(source, &mut actual);
+                        // Compare the bits, so that the results are identical and none
+                        // is left unwritten.
+                        let bits = |values: &[f32]| -> Vec<u32> {
+                            values.iter().map(|value| value.to_bits()).collect()
+                        };
+                        assert_eq!(
+                            bits(&actual),
+                            bits(&expected),
+                            "{source_format:?} to {channels:?} with {level:?}, {pixels} pixels"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn yuv_kernels_match_scalar_for_every_length() {
+    let bytes = test_bytes(MAX_PIXELS * 4);
+    for source_format in SOURCES {
+        for kernel in yuv_kernels(source_format) {
+            let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+            for level in SimdLevel::supported() {
+                let kernel = kernel.with_level(level).unwrap();
+                for pixels in 0..=MAX_PIXELS {
+                    let source = &bytes[..pixels * 4];
+                    assert_eq!(
+                        run_to_yuv(&kernel, source),
+                        run_to_yuv(&scalar, source),
+                        "{kernel:?} to YCbCr, {pixels} pixels"
+                    );
+                    let chroma = pixels.div_ceil(2);
+                    let (luma, u, v) = (
+                        &bytes[..pixels],
+                        &bytes[pixels..pixels + chroma],
+                        &bytes[MAX_PIXELS * 2..MAX_PIXELS * 2 + chroma],
+                    );
+                    assert_eq!(
+                        run_to_rgb(&kernel, luma, u, v),
+                        run_to_rgb(&scalar, luma, u, v),
+                        "{kernel:?} to RGB, {pixels} pixels"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rgb_to_yuv_kernels_match_scalar_for_every_color() {
+    // One row per red value, holding every combination of green and blue.
+    let mut row = vec![0; 256 * 256 * 4];
+    let levels: Vec<_> = SimdLevel::supported()
+        .into_iter()
+        .filter(|&level| level != SimdLevel::Scalar)
+        .collect();
+    for kernel in yuv_kernels(PixelFormat::RGBA8) {
+        let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+        for red in 0..=255 {
+            for (i, pixel) in row.chunks_exact_mut(4).enumerate() {
+                pixel.copy_from_slice(&[red, (i >> 8) as u8, i as u8, 255]);
+            }
+            let expected = run_to_yuv(&scalar, &row);
+            for &level in &levels {
+                let kernel = kernel.with_level(level).unwrap();
+                assert!(
+                    run_to_yuv(&kernel, &row) == expected,
+                    "{kernel:?} differs for red {red}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn yuv_to_rgb_kernels_match_scalar_for_every_sample() {
+    // One row per pair of luma values, each pair covered by one of every combination of
+    // chroma samples.
+    let mut luma = vec![0; 256 * 256 * 2];
+    let u: Vec<u8> = (0..256 * 256).map(|i| (i >> 8) as u8).collect();
+    let v: Vec<u8> = (0..256 * 256).map(|i| i as u8).collect();
+    let levels: Vec<_> = SimdLevel::supported()
+        .into_iter()
+        .filter(|&level| level != SimdLevel::Scalar)
+        .collect();
+    for kernel in yuv_kernels(PixelFormat::RGBA8) {
+        let scalar = kernel.with_level(SimdLevel::Scalar).unwrap();
+        for first in (0..=254).step_by(2) {
+            for pair in luma.chunks_exact_mut(2) {
+                pair.copy_from_slice(&[first, first + 1]);
+            }
+            let expected = run_to_rgb(&scalar, &luma, &u, &v);
+            for &level in &levels {
+                let kernel = kernel.with_level(level).unwrap();
+                assert!(
+                    run_to_rgb(&kernel, &luma, &u, &v) == expected,
+                    "{kernel:?} differs for luma {first}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn kernels_are_used_by_conversions() {
+    let size = FrameSize::new(MAX_PIXELS as u32, 1);
+    let frame =
+        CpuFrame::from_packed(test_bytes(MAX_PIXELS * 4), size, PixelFormat::BGRA8).unwrap();
+    for target_format in targets() {
+        let scalar = RowKernel::new(PixelFormat::BGRA8, target_format)
+            .unwrap()
+            .with_level(SimdLevel::Scalar)
+            .unwrap();
+        assert_eq!(
+            frame.convert(target_format).unwrap().data(),
+            run(&scalar, frame.data()),
+            "{target_format:?}"
+        );
+    }
+}
+
+#[test]
+fn yuv_kernels_are_used_by_conversions() {
+    let size = FrameSize::new(MAX_PIXELS as u32, 1);
+    let frame =
+        CpuFrame::from_packed(test_bytes(MAX_PIXELS * 4), size, PixelFormat::BGRA8).unwrap();
+    let format = PixelFormat::yuv(YuvLayout::I420, ColorMatrix::Bt709, ColorRange::Full);
+    let scalar = YuvKernel::new(PixelFormat::BGRA8, format)
+        .unwrap()
+        .with_level(SimdLevel::Scalar)
+        .unwrap();
+
+    let mut luma = vec![0; MAX_PIXELS];
+    let mut u = vec![0.0; MAX_PIXELS];
+    let mut v = vec![0.0; MAX_PIXELS];
+    scalar.to_yuv(frame.data(), &mut luma, &mut u, &mut v);
+    // A single row averages the chroma of pairs of pixels.
+    let average = |values: &[f32]| -> Vec<u8> {
+        values
+            .chunks_exact(2)
+            .map(|pair| scalar.quantize_chroma((pair[0] + pair[1]) / 2.0))
+            .collect()
+    };
+    let yuv = frame.to_yuv(format).unwrap();
+    assert_eq!(yuv.planes(), [&luma[..], &average(&u), &average(&v)]);
+
+    let planes = yuv.planes();
+    assert_eq!(
+        yuv.to_rgb(PixelFormat::BGRA8).unwrap().data(),
+        run_to_rgb(&scalar, planes[0], planes[1], planes[2])
+    );
+}
@@ -0,0 +1,131 @@
+//! Pins the output of every `FrameInterpolationMode` on gradients, upscaled and
+//! downscaled, so that the filter weights of each mode are checked.
+
+use wgc::*;
+
+/// A gradient of 4 gray pixels, with steps of 60, 120 and 60.
+const RAMP_4: [u8; 4] = [0, 60, 180, 240];
+/// A gradient of 8 gray pixels, with uneven steps.
+const RAMP_8: [u8; 8] = [0, 20, 40, 80, 120, 160, 220, 255];
+
+/// Stretches a single row of opaque gray pixels to `width` and returns its gray values.
+fn stretch(values: &[u8], width: u32, mode: FrameInterpolationMode) -> Vec<u8> {
+    let data = values.iter().flat_map(|&v| [v, v, v, 255]).collect();
+    let frame = CpuFrame::from_packed(
+        data,
+        FrameSize::new(values.len() as u32, 1),
+        PixelFormat::RGBA8,
+    )
+    .unwrap();
+    let resized = frame
+        .resize(FrameSize::new(width, 1), ResizeMode::Stretch, mode)
+        .unwrap();
+    assert_eq!(resized.size(), FrameSize::new(width, 1));
+    resized
+        .data()
+        .chunks(4)
+        .map(|pixel| {
+            assert_eq!(pixel[..3], [pixel[0]; 3]);
+            assert_eq!(pixel[3], 255);
+            pixel[0]
+        })
+        .collect()
+}
+
+#[test]
+fn nearest_neighbor() {
+    let mode = FrameInterpolationMode::NearestNeighbor;
+    assert_eq!(
+        stretch(&RAMP_4, 8, mode),
+        [0, 0, 60, 60, 180, 180, 240, 240]
+    );
+    // The source pixels under the centers 4/3, 4 and 20/3.
+    assert_eq!(stretch(&RAMP_8, 3, mode), [20, 120, 220]);
+}
+
+#[test]
+fn linear() {
+    let mode = FrameInterpolationMode::Linear;
+    assert_eq!(
+        stretch(&RAMP_4, 8, mode),
+        [0, 15, 45, 90, 150, 195, 225, 240]
+    );
+    assert_eq!(stretch(&RAMP_8, 3, mode), [17, 100, 226]);
+}
+
+#[test]
+fn cubic_is_catmull_rom() {
+    let mode = FrameInterpolationMode::Cubic;
+    // Catmull-Rom overshoots the linear ramp around the steep middle step: -4.2 is clamped
+    // to 0 and 152.8 exceeds the linear 150.
+    assert_eq!(
+        stretch(&RAMP_4, 8, mode),
+        [0, 9, 39, 87, 153, 201, 231, 244]
+    );
+    // Downscaling samples the spline at the centers, like an upscale.
+    assert_eq!(stretch(&RAMP_8, 3, mode), [16, 100, 228]);
+}
+
+#[test]
+fn multi_sample_linear_averages_two_samples_per_axis() {
+    let mode = FrameInterpolationMode::MultiSampleLinear;
+    // Samples a quarter of an output pixel on either side of the center: at 3x, the
+    // averages differ from the linear values where the ramp changes slope.
+    let ramp = [0, 45, 165, 240];
+    assert_eq!(
+        stretch(&ramp, 12, mode),
+        [0, 2, 15, 30, 48, 85, 125, 163, 190, 215, 237, 240]
+    );
+    assert_eq!(
+        stretch(&ramp, 12, FrameInterpolationMode::Linear),
+        [0, 0, 15, 30, 45, 85, 125, 165, 190, 215, 240, 240]
+    );
+    assert_eq!(stretch(&RAMP_8, 3, mode), [17, 100, 220]);
+}
+
+#[test]
+fn high_quality_cubic_averages_areas_when_downscaling() {
+    let mode = FrameInterpolationMode::HighQualityCubic;
+    // Each output pixel averages the 2 source pixels it covers.
+    assert_eq!(stretch(&RAMP_8, 4, mode), [10, 60, 140, 238]);
+    // Each output pixel covers 8/3 source pixels, partially covering those on its edges:
+    // (0 + 20 + 40 * 2/3) * 3/8, (40/3 + 80 + 120 + 160/3) * 3/8 and
+    // (160 * 2/3 + 220 + 255) * 3/8.
+    assert_eq!(stretch(&RAMP_8, 3, mode), [18, 100, 218]);
+    // Upscaling is Catmull-Rom.
+    assert_eq!(
+        stretch(&RAMP_4, 8, mode),
+        stretch(&RAMP_4, 8, FrameInterpolationMode::Cubic)
+    );
+}
+
+#[test]
+fn every_mode_scales_both_axes() {
+    // A 2x2 frame with the same gradient along each axis, upscaled to 4x4: the rows and
+    // the columns are filtered with the same weights, so the result is symmetric.
+    let data = [0, 100, 100, 200]
+        .iter()
+        .flat_map(|&v| [v, v, v, 255])
+        .collect();
+    let frame = CpuFrame::from_packed(data, FrameSize::new(2, 2), PixelFormat::RGBA8).unwrap();
+    for mode in [
+        FrameInterpolationMode::NearestNeighbor,
+        FrameInterpolationMode::Linear,
+        FrameInterpolationMode::Cubic,
+        FrameInterpolationMode::MultiSampleLinear,
+        FrameInterpolationMode::HighQualityCubic,
+    ] {
+        let resized = frame
+            .resize(FrameSize::new(4, 4), ResizeMode::Stretch, mode)
+            .unwrap();
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(
+                    resized.pixel(x, y),
+                    resized.pixel(y, x),
+                    "{mode:?} at ({x}, {y})"
+                );
+            }
+        }
+    }
+}
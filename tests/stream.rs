@@ -0,0 +1,171 @@
+//! Streams synthetic captures through a `FrameStream` and checks each backpressure policy.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use futures::executor::block_on;
+use wgc::*;
+
+fn item(frame_count: u64) -> SyntheticItem {
+    SyntheticItem {
+        size: FrameSize::new(32, 24),
+        frame_count: Some(frame_count),
+        ..Default::default()
+    }
+}
+
+fn spawn(item: SyntheticItem, options: StreamOptions) -> FrameStream<SyntheticFrame> {
+    FrameStream::spawn(options, move || {
+        SyntheticSource::new(item, WgcSettings::default())
+    })
+    .unwrap()
+}
+
+fn indices(stream: &mut FrameStream<SyntheticFrame>) -> Vec<u64> {
+    block_on(
+        stream
+            .map(|frame| frame.unwrap().index())
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Waits until the capture thread has dropped `count` frames.
+fn wait_for_dropped<T>(stream: &FrameStream<T>, count: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while stream.dropped() < count {
+        assert!(
+            Instant::now() < deadline,
+            "only {} dropped",
+            stream.dropped()
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn block_delivers_every_frame() {
+    let options = StreamOptions {
+        capacity: 1,
+        backpressure: Backpressure::Block,
+    };
+    let mut stream = spawn(item(20), options);
+    // Give the capture thread time to fill the channel and block.
+    std::thread::sleep(Duration::from_millis(20));
+    assert_eq!(indices(&mut stream), (0..20).collect::<Vec<_>>());
+    assert_eq!(stream.dropped(), 0);
+}
+
+#[test]
+fn drop_newest_keeps_the_first_frames() {
+    let options = StreamOptions {
+        capacity: 2,
+        backpressure: Backpressure::DropNewest,
+    };
+    let mut stream = spawn(item(10), options);
+    wait_for_dropped(&stream, 8);
+    assert_eq!(indices(&mut stream), [0, 1]);
+    assert_eq!(stream.dropped(), 8);
+}
+
+#[test]
+fn drop_oldest_keeps_the_latest_frames() {
+    let options = StreamOptions {
+        capacity: 3,
+        backpressure: Backpressure::DropOldest,
+    };
+    let mut stream = spawn(item(10), options);
+    wait_for_dropped(&stream, 7);
+    assert_eq!(indices(&mut stream), [7, 8, 9]);
+    assert_eq!(stream.dropped(), 7);
+}
+
+#[test]
+fn wakes_the_consumer_for_paced_frames() {
+    let item = SyntheticItem {
+        frame_interval: Duration::from_millis(5),
+        realtime: true,
+        ..item(10)
+    };
+    let mut stream = spawn(item, StreamOptions::default());
+    assert_eq!(indices(&mut stream), (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn maps_frames_on_the_capture_thread() {
+    let item = SyntheticItem {
+        size_changes: vec![(2, FrameSize::new(16, 12))],
+        ..item(4)
+    };
+    let stream = FrameStream::spawn_map(
+        StreamOptions::default(),
+        move || SyntheticSource::new(item, WgcSettings::default()),
+        |frame| frame.to_cpu_frame(None),
+    )
+    .unwrap();
+    let sizes: Vec<FrameSize> = block_on(stream.map(|frame| frame.unwrap().size()).collect());
+    assert_eq!(
+        sizes,
+        [
+            FrameSize::new(32, 24),
+            FrameSize::new(32, 24),
+            FrameSize::new(16, 12)
+        ]
+    );
+}
+
+#[test]
+fn returns_the_error_of_open() {
+    let result = FrameStream::spawn(StreamOptions::default(), || {
+        Err::<SyntheticSource, _>(WgcError::NoItemSelected)
+    });
+    assert!(matches!(result, Err(WgcError::NoItemSelected)));
+}
+
+/// An endless source that never delivers a frame, and records when it is dropped.
+struct Idle {
+    dropped: Arc<AtomicBool>,
+}
+
+impl FrameWaiter for Idle {
+    type Frame = ();
+
+    fn clock(&self) -> &dyn Clock {
+        &QpcClock
+    }
+
+    fn try_take(&mut self) -> Result<Option<()>, WgcError> {
+        Ok(None)
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool, WgcError> {
+        std::thread::sleep(timeout);
+        Ok(true)
+    }
+}
+
+impl Drop for Idle {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn dropping_the_stream_stops_the_capture() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let stream = FrameStream::spawn(StreamOptions::default(), {
+        let dropped = dropped.clone();
+        move || Ok(Idle { dropped })
+    })
+    .unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    assert!(!dropped.load(Ordering::SeqCst));
+
+    drop(stream);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !dropped.load(Ordering::SeqCst) {
+        assert!(Instant::now() < deadline, "the source was not dropped");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
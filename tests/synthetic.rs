@@ -0,0 +1,271 @@
+//! Pins the exact output of every `TestPattern`, and the frame sequence of a
+//! `SyntheticSource` across content size changes.
+
+use std::time::Duration;
+
+use wgc::*;
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// Returns the frames of a source drawing `pattern` at `size`, from index 0.
+fn frames(pattern: TestPattern, size: FrameSize, pixel_format: PixelFormat) -> SyntheticSource {
+    let item = SyntheticItem {
+        pattern,
+        size,
+        ..Default::default()
+    };
+    let settings = WgcSettings {
+        pixel_format,
+        ..Default::default()
+    };
+    SyntheticSource::new(item, settings).unwrap()
+}
+
+/// Returns the RGBA8 pixels of the frame at `index`.
+fn rgba_at(pattern: TestPattern, size: FrameSize, index: usize) -> Vec<u8> {
+    frames(pattern, size, PixelFormat::RGBA8)
+        .nth(index)
+        .unwrap()
+        .unwrap()
+        .read_pixels(None)
+        .unwrap()
+}
+
+/// Flattens rows of RGBA colors.
+fn pixels(rows: &[&[[u8; 4]]]) -> Vec<u8> {
+    rows.iter()
+        .flat_map(|row| row.iter().flatten())
+        .copied()
+        .collect()
+}
+
+/// The 64-bit FNV-1a hash, to pin full-size frames without storing them.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[test]
+fn solid_is_premultiplied() {
+    let pattern = TestPattern::Solid([255, 64, 0, 128]);
+    let row = [[128, 32, 0, 128]; 3];
+    let expected = pixels(&[&row, &row]);
+    for index in 0..3 {
+        assert_eq!(rgba_at(pattern, FrameSize::new(3, 2), index), expected);
+    }
+}
+
+#[test]
+fn color_bars() {
+    const BARS: [[u8; 4]; 8] = [
+        WHITE,
+        [255, 255, 0, 255],
+        [0, 255, 255, 255],
+        [0, 255, 0, 255],
+        [255, 0, 255, 255],
+        [255, 0, 0, 255],
+        [0, 0, 255, 255],
+        BLACK,
+    ];
+    assert_eq!(
+        rgba_at(TestPattern::ColorBars, FrameSize::new(8, 2), 0),
+        pixels(&[&BARS, &BARS])
+    );
+
+    // Bars of uneven width: bar `x * 8 / width`.
+    let row = [
+        BARS[0], BARS[0], BARS[1], BARS[2], BARS[2], BARS[3], BARS[4], BARS[5], BARS[5], BARS[6],
+        BARS[7],
+    ];
+    let expected = pixels(&[&row]);
+    for index in 0..3 {
+        assert_eq!(
+            rgba_at(TestPattern::ColorBars, FrameSize::new(11, 1), index),
+            expected
+        );
+    }
+}
+
+#[test]
+fn moving_gradient_scrolls_one_pixel_per_frame() {
+    let size = FrameSize::new(4, 3);
+    // Red is `((x + index) % 4) * 255 / 3`, green is `y * 255 / 2`.
+    let frame = |reds: [u8; 4]| {
+        let rows: Vec<Vec<[u8; 4]>> = [0, 127, 255]
+            .iter()
+            .map(|&green| reds.iter().map(|&red| [red, green, 128, 255]).collect())
+            .collect();
+        let rows: Vec<&[[u8; 4]]> = rows.iter().map(Vec::as_slice).collect();
+        pixels(&rows)
+    };
+    assert_eq!(
+        rgba_at(TestPattern::MovingGradient, size, 0),
+        frame([0, 85, 170, 255])
+    );
+    assert_eq!(
+        rgba_at(TestPattern::MovingGradient, size, 1),
+        frame([85, 170, 255, 0])
+    );
+    assert_eq!(
+        rgba_at(TestPattern::MovingGradient, size, 6),
+        frame([170, 255, 0, 85])
+    );
+}
+
+#[test]
+fn frame_counter_encodes_the_index() {
+    let size = FrameSize::new(8, 8);
+    for index in [0_u64, 1, 165, 256 + 3] {
+        let mut expected = vec![BLACK; 64];
+        for (bit, cell) in expected.iter_mut().enumerate() {
+            if (index >> bit) & 1 == 1 {
+                *cell = WHITE;
+            }
+        }
+        assert_eq!(
+            rgba_at(TestPattern::FrameCounter, size, index as usize),
+            pixels(&[&expected]),
+            "frame {index}"
+        );
+    }
+
+    // Cells span several pixels in larger frames: frame 5 sets cells 0 and 2.
+    let frame = rgba_at(TestPattern::FrameCounter, FrameSize::new(16, 8), 5);
+    let first_row: Vec<[u8; 4]> = frame[..16 * 4]
+        .chunks(4)
+        .map(|pixel| pixel.try_into().unwrap())
+        .collect();
+    assert_eq!(
+        first_row,
+        [
+            [WHITE; 2], [BLACK; 2], [WHITE; 2], [BLACK; 2], [BLACK; 2], [BLACK; 2], [BLACK; 2],
+            [BLACK; 2]
+        ]
+        .concat()
+    );
+    assert!(frame[16 * 4..].chunks(4).all(|pixel| pixel == BLACK));
+}
+
+#[test]
+fn checkerboard() {
+    let pattern = TestPattern::Checkerboard { cell_size: 2 };
+    let even = [BLACK, BLACK, WHITE, WHITE, BLACK];
+    let odd = [WHITE, WHITE, BLACK, BLACK, WHITE];
+    assert_eq!(
+        rgba_at(pattern, FrameSize::new(5, 5), 0),
+        pixels(&[&even, &even, &odd, &odd, &even])
+    );
+
+    // A cell size of 0 is treated as 1.
+    let pattern = TestPattern::Checkerboard { cell_size: 0 };
+    assert_eq!(
+        rgba_at(pattern, FrameSize::new(3, 2), 4),
+        pixels(&[&[BLACK, WHITE, BLACK], &[WHITE, BLACK, WHITE]])
+    );
+}
+
+#[test]
+fn bgra_swaps_red_and_blue() {
+    let size = FrameSize::new(11, 3);
+    let rgba = rgba_at(TestPattern::MovingGradient, size, 2);
+    let bgra = frames(TestPattern::MovingGradient, size, PixelFormat::BGRA8)
+        .nth(2)
+        .unwrap()
+        .unwrap()
+        .read_pixels(None)
+        .unwrap();
+    let swapped: Vec<u8> = rgba
+        .chunks(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+    assert_eq!(bgra, swapped);
+}
+
+/// Golden hashes of the first frames at the default size of 640x480, which catch any
+/// change to the patterns beyond the small frames checked above.
+#[test]
+fn golden_full_size_frames() {
+    let golden: [(TestPattern, [u64; 3]); 5] = [
+        (
+            TestPattern::Solid([10, 20, 30, 255]),
+            [0x91da770f89926325, 0x91da770f89926325, 0x91da770f89926325],
+        ),
+        (
+            TestPattern::ColorBars,
+            [0xac9478a2a85c5325, 0xac9478a2a85c5325, 0xac9478a2a85c5325],
+        ),
+        (
+            TestPattern::MovingGradient,
+            [0x5962dd9b99f97319, 0xd1c89e7b1e4a15cd, 0x992f37ba7aacc9d9],
+        ),
+        (
+            TestPattern::FrameCounter,
+            [0x742a0703fa4da325, 0x637919d74b3eb425, 0x9705fe9e7909b425],
+        ),
+        (
+            TestPattern::Checkerboard { cell_size: 16 },
+            [0x45f2d2eebc7fc325, 0x45f2d2eebc7fc325, 0x45f2d2eebc7fc325],
+        ),
+    ];
+    for (pattern, hashes) in golden {
+        let actual: Vec<u64> = frames(pattern, FrameSize::new(640, 480), PixelFormat::RGBA8)
+            .take(3)
+            .map(|frame| fnv1a(&frame.unwrap().read_pixels(None).unwrap()))
+            .collect();
+        assert_eq!(actual, hashes, "{pattern:?}");
+    }
+}
+
+#[test]
+fn drops_the_first_frame_after_each_resize() {
+    let clock = FakeClock::new(Duration::from_secs(5));
+    let item = SyntheticItem {
+        pattern: TestPattern::ColorBars,
+        size: FrameSize::new(16, 2),
+        size_changes: vec![
+            (2, FrameSize::new(8, 1)),
+            (3, FrameSize::new(24, 2)),
+            (5, FrameSize::new(24, 2)),
+        ],
+        frame_interval: Duration::from_millis(10),
+        frame_count: Some(7),
+        ..Default::default()
+    };
+    let mut source = SyntheticSource::new(item, WgcSettings::default())
+        .unwrap()
+        .with_clock(clock);
+
+    let mut delivered = Vec::new();
+    for frame in source.by_ref() {
+        let frame = frame.unwrap();
+        let size = frame.size().unwrap();
+        let pixels = frame.read_pixels(None).unwrap();
+        assert_eq!(pixels.len(), size.pixel_count() * 4);
+        // The content is drawn at the new size: the last bar is black.
+        assert_eq!(pixels[pixels.len() - 4..], BLACK);
+        delivered.push((
+            frame.index(),
+            frame.sequence(),
+            size,
+            frame.timestamp().unwrap(),
+        ));
+    }
+
+    // Frames 2 and 3 are dropped for their new sizes. A change to the same size does not
+    // recreate the pool, so frame 5 is kept.
+    assert_eq!(
+        delivered,
+        [
+            (0, 0, FrameSize::new(16, 2), Duration::from_millis(5_000)),
+            (1, 1, FrameSize::new(16, 2), Duration::from_millis(5_010)),
+            (4, 2, FrameSize::new(24, 2), Duration::from_millis(5_040)),
+            (5, 3, FrameSize::new(24, 2), Duration::from_millis(5_050)),
+            (6, 4, FrameSize::new(24, 2), Duration::from_millis(5_060)),
+        ]
+    );
+    let counters = source.counters();
+    assert_eq!(counters.delivered, 5);
+    assert_eq!(counters.dropped_for_resize, 2);
+}
@@ -0,0 +1,103 @@
+//! Drives the provided methods of `FrameWaiter` with a scripted waiter on a `FakeClock`.
+
+use std::time::{Duration, Instant};
+
+use wgc::*;
+
+/// A waiter whose frames arrive at scripted times, and that records each wait.
+struct Scripted {
+    clock: FakeClock,
+    arrivals: Vec<Duration>,
+    /// The frames delivered when an event is processed, in order of arrival.
+    pending: Vec<Duration>,
+    waits: Vec<Duration>,
+}
+
+impl Scripted {
+    fn new(clock: &FakeClock, arrivals: &[u64]) -> Self {
+        Self {
+            clock: clock.clone(),
+            arrivals: arrivals
+                .iter()
+                .rev()
+                .map(|&ms| Duration::from_millis(ms))
+                .collect(),
+            pending: Vec::new(),
+            waits: Vec::new(),
+        }
+    }
+}
+
+impl FrameWaiter for Scripted {
+    type Frame = Duration;
+
+    fn clock(&self) -> &dyn Clock {
+        &self.clock
+    }
+
+    fn try_take(&mut self) -> Result<Option<Duration>, WgcError> {
+        Ok(self.pending.pop())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<bool, WgcError> {
+        self.waits.push(timeout);
+        let Some(&arrival) = self.arrivals.last() else {
+            return Ok(false);
+        };
+        let until_arrival = arrival.saturating_sub(self.clock.elapsed_since_boot());
+        self.clock.sleep(until_arrival.min(timeout));
+        if self.clock.elapsed_since_boot() >= arrival {
+            self.pending.push(self.arrivals.pop().unwrap());
+        }
+        Ok(true)
+    }
+}
+
+#[test]
+fn passed_deadline_processes_pending_events_once() {
+    let clock = FakeClock::new(Duration::from_secs(1));
+    let passed = Instant::now() - Duration::from_millis(1);
+
+    let mut waiter = Scripted::new(&clock, &[1_000]);
+    assert_eq!(
+        waiter.next_deadline(passed).unwrap(),
+        NextFrame::Frame(Duration::from_secs(1))
+    );
+    assert_eq!(waiter.waits, [Duration::ZERO]);
+
+    let mut waiter = Scripted::new(&clock, &[1_050]);
+    assert_eq!(waiter.next_deadline(passed).unwrap(), NextFrame::TimedOut);
+    assert_eq!(waiter.waits, [Duration::ZERO]);
+    assert_eq!(clock.elapsed_since_boot(), Duration::from_secs(1));
+
+    let mut waiter = Scripted::new(&clock, &[]);
+    assert_eq!(waiter.next_deadline(passed).unwrap(), NextFrame::Closed);
+}
+
+#[test]
+fn future_deadline_is_measured_on_the_clock() {
+    let clock = FakeClock::new(Duration::from_secs(1));
+    let mut waiter = Scripted::new(&clock, &[1_050, 1_500]);
+
+    let deadline = Instant::now() + Duration::from_secs(60);
+    assert_eq!(
+        waiter.next_deadline(deadline).unwrap(),
+        NextFrame::Frame(Duration::from_millis(1_050))
+    );
+    // The fake clock moved, not the real one.
+    assert_eq!(clock.elapsed_since_boot(), Duration::from_millis(1_050));
+    assert!(Instant::now() < deadline);
+
+    // A deadline 100 ms away times out after 100 ms on the clock.
+    let deadline = Instant::now() + Duration::from_millis(100);
+    assert_eq!(waiter.next_deadline(deadline).unwrap(), NextFrame::TimedOut);
+    let elapsed = clock.elapsed_since_boot() - Duration::from_millis(1_050);
+    assert!(
+        elapsed <= Duration::from_millis(100) && elapsed > Duration::from_millis(50),
+        "{elapsed:?}"
+    );
+    assert_eq!(
+        waiter.next_timeout(Duration::from_secs(1)).unwrap(),
+        NextFrame::Frame(Duration::from_millis(1_500))
+    );
+}
@@ -0,0 +1,200 @@
+//! Round-trips RGB frames through every YCbCr format at odd sizes, with non-uniform
+//! content, and checks the error against a tolerance per color range.
+
+use wgc::*;
+
+const LAYOUTS: [YuvLayout; 3] = [YuvLayout::Nv12, YuvLayout::I420, YuvLayout::Yuy2];
+const MATRICES: [ColorMatrix; 3] = [ColorMatrix::Bt601, ColorMatrix::Bt709, ColorMatrix::Bt2020];
+const RANGES: [ColorRange; 2] = [ColorRange::Limited, ColorRange::Full];
+
+/// Sizes that cover even and odd widths and heights, down to a single pixel.
+const SIZES: [(u32, u32); 8] = [
+    (1, 1),
+    (2, 2),
+    (3, 1),
+    (1, 3),
+    (3, 3),
+    (5, 4),
+    (6, 7),
+    (33, 17),
+];
+
+/// The largest difference per channel after a round trip of content whose chroma is
+/// uniform over each chroma sample, where only quantization is lost: luma and chroma are
+/// rounded to 8 bits, then the RGB result is rounded again. Limited range quantizes to
+/// 219 luma and 224 chroma levels, so it loses more.
+fn quantization_tolerance(range: ColorRange) -> u8 {
+    match range {
+        ColorRange::Full => 1,
+        ColorRange::Limited => 2,
+    }
+}
+
+fn formats() -> impl Iterator<Item = PixelFormat> {
+    LAYOUTS.into_iter().flat_map(|layout| {
+        MATRICES.into_iter().flat_map(move |matrix| {
+            RANGES
+                .into_iter()
+                .map(move |range| PixelFormat::yuv(layout, matrix, range))
+        })
+    })
+}
+
+/// Deterministic pseudo-random bytes.
+fn random_bytes(seed: u32) -> impl FnMut() -> u8 {
+    let mut state = seed | 1;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state >> 24) as u8
+    }
+}
+
+fn rgba_frame(width: u32, height: u32, mut color: impl FnMut(u32, u32) -> [u8; 3]) -> CpuFrame {
+    let mut data = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = color(x, y);
+            data.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    CpuFrame::from_packed(data, FrameSize::new(width, height), PixelFormat::RGBA8).unwrap()
+}
+
+/// Returns the largest difference per channel between `frame` and its round trip through
+/// `pixel_format`.
+fn round_trip_error(frame: &CpuFrame, pixel_format: PixelFormat) -> u8 {
+    let yuv = frame.to_yuv(pixel_format).unwrap();
+    assert_eq!(yuv.data().len(), pixel_format.buffer_len(frame.size()));
+    let round_trip = yuv.to_rgb(PixelFormat::RGBA8).unwrap();
+    assert_eq!(round_trip.size(), frame.size());
+    frame
+        .data()
+        .iter()
+        .zip(round_trip.data())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn random_gray_round_trips_at_odd_sizes() {
+    // Gray has no chroma, so subsampling loses nothing whatever the content.
+    for (width, height) in SIZES {
+        let mut random = random_bytes(width * 31 + height);
+        let frame = rgba_frame(width, height, |_, _| [random(); 3]);
+        for pixel_format in formats() {
+            let tolerance = quantization_tolerance(pixel_format.color_range().unwrap());
+            let error = round_trip_error(&frame, pixel_format);
+            assert!(
+                error <= tolerance,
+                "{width}x{height} {pixel_format:?}: {error} > {tolerance}"
+            );
+        }
+    }
+}
+
+#[test]
+fn random_colors_round_trip_at_odd_sizes() {
+    // A random color per 2x2 block, so that each chroma sample covers one color, including
+    // the partial samples of an odd last row or column.
+    for (width, height) in SIZES {
+        let mut random = random_bytes(width * 17 + height * 5);
+        let blocks: Vec<[u8; 3]> = (0..width.div_ceil(2) * height.div_ceil(2))
+            .map(|_| [random(), random(), random()])
+            .collect();
+        let frame = rgba_frame(width, height, |x, y| {
+            blocks[((y / 2) * width.div_ceil(2) + x / 2) as usize]
+        });
+        for pixel_format in formats() {
+            let tolerance = quantization_tolerance(pixel_format.color_range().unwrap());
+            let error = round_trip_error(&frame, pixel_format);
+            assert!(
+                error <= tolerance,
+                "{width}x{height} {pixel_format:?}: {error} > {tolerance}"
+            );
+        }
+    }
+}
+
+#[test]
+fn gradients_round_trip_within_a_gradient_step() {
+    // Within a chroma sample, the colors of a gradient differ by up to one step per
+    // pixel, and the averaged chroma is off by about half of it on each side. The first
+    // frame is used, as later ones wrap from full red to none within the frame.
+    for (width, height) in [(33, 17), (101, 49), (255, 85)] {
+        let item = SyntheticItem {
+            pattern: TestPattern::MovingGradient,
+            size: FrameSize::new(width, height),
+            ..Default::default()
+        };
+        let frame = SyntheticSource::new(item, WgcSettings::default())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .to_cpu_frame(None)
+            .unwrap();
+        let step = (255 / (width - 1)).max(255 / (height - 1)) as u8;
+        for pixel_format in formats() {
+            let tolerance = quantization_tolerance(pixel_format.color_range().unwrap()) + step;
+            let error = round_trip_error(&frame, pixel_format);
+            assert!(
+                error <= tolerance,
+                "{width}x{height} {pixel_format:?}: {error} > {tolerance}"
+            );
+        }
+    }
+}
+
+#[test]
+fn planes_cover_odd_sizes() {
+    let frame = rgba_frame(5, 3, |x, y| [x as u8 * 40, y as u8 * 80, 7]);
+    let nv12 = frame.to_yuv(PixelFormat::NV12).unwrap();
+    let lengths: Vec<usize> = nv12.planes().iter().map(|plane| plane.len()).collect();
+    assert_eq!(lengths, [15, 2 * 3 * 2]);
+
+    let i420 = frame.to_yuv(PixelFormat::I420).unwrap();
+    let lengths: Vec<usize> = i420.planes().iter().map(|plane| plane.len()).collect();
+    assert_eq!(lengths, [15, 3 * 2, 3 * 2]);
+    // Both layouts share the luma plane and the chroma samples.
+    assert_eq!(nv12.planes()[0], i420.planes()[0]);
+    let interleaved: Vec<u8> = i420.planes()[1]
+        .iter()
+        .zip(i420.planes()[2])
+        .flat_map(|(&u, &v)| [u, v])
+        .collect();
+    assert_eq!(nv12.planes()[1], interleaved);
+
+    let yuy2 = frame.to_yuv(PixelFormat::YUY2).unwrap();
+    assert_eq!(yuy2.planes().len(), 1);
+    assert_eq!(yuy2.planes()[0].len(), 3 * 3 * 4);
+}
+
+#[test]
+fn yuy2_repeats_an_odd_last_pixel() {
+    let red = [255, 0, 0];
+    let blue = [0, 0, 255];
+    let frame = rgba_frame(3, 2, |x, _| if x == 2 { red } else { blue });
+    let yuy2 = frame.to_yuv(PixelFormat::YUY2).unwrap();
+    let data = yuy2.data();
+    assert_eq!(data.len(), 2 * 2 * 4);
+
+    for row in data.chunks(8) {
+        let (blue_pair, red_pair) = row.split_at(4);
+        // The last group holds the red pixel twice, with its own chroma.
+        assert_eq!(red_pair[0], red_pair[2]);
+        assert_ne!(red_pair[1], blue_pair[1]);
+        assert_ne!(red_pair[3], blue_pair[3]);
+    }
+
+    let round_trip = yuy2.to_rgb(PixelFormat::RGBA8).unwrap();
+    let tolerance = quantization_tolerance(ColorRange::Limited);
+    for (x, pixel) in round_trip.data().chunks(4).enumerate() {
+        let expected = if x % 3 == 2 { red } else { blue };
+        for (a, b) in expected.iter().zip(pixel) {
+            assert!(a.abs_diff(*b) <= tolerance, "pixel {x}: {pixel:?}");
+        }
+    }
+}